@@ -0,0 +1,308 @@
+//! Compute budget and priority fees: fee modes, re-pricing built transactions
+//! and the hourly budget for priority fees paid by landed transactions.
+
+use std::collections::VecDeque;
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+use solana_sdk::{compute_budget::ComputeBudgetInstruction, message::VersionedMessage};
+
+// Maximum compute units a transaction can request
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+// Rolling window for `max_lamports_per_hour`
+const BUDGET_WINDOW_MS: u64 = 3_600_000;
+
+/// How the compute unit price is chosen
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeMode {
+    // Same price on every attempt (micro-lamports per CU)
+    Fixed { micro_lamports: u64 },
+    // Percentile (0-100) of fees recently paid to lock the same accounts
+    Percentile { percentile: f64 },
+    // Percentile of recent fees, multiplied on every re-send
+    Adaptive { percentile: f64, multiplier: f64 },
+}
+
+/// Compute unit limit and priority fee settings
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PriorityFeeConfig {
+    // Compute units requested per transaction, priority fees are charged on this
+    pub compute_unit_limit: u32,
+    pub mode: FeeMode,
+    // Price bounds after percentile and escalation (micro-lamports per CU)
+    pub min_micro_lamports: u64,
+    pub max_micro_lamports: u64,
+    // Priority fees allowed per rolling hour, transactions over it are sent without one
+    pub max_lamports_per_hour: Option<u64>,
+}
+
+impl Default for PriorityFeeConfig {
+    fn default() -> Self {
+        Self {
+            // A cancel and re-place of a one-level quote with headroom; deep
+            // ladders packed into one transaction may need more
+            compute_unit_limit: 300_000,
+            mode: FeeMode::Fixed { micro_lamports: 0 },
+            min_micro_lamports: 0,
+            max_micro_lamports: 1_000_000,
+            max_lamports_per_hour: None,
+        }
+    }
+}
+
+impl PriorityFeeConfig {
+    /// Check the settings are usable
+    pub fn validate(&self) -> Result<()> {
+        if self.compute_unit_limit == 0 || self.compute_unit_limit > MAX_COMPUTE_UNIT_LIMIT {
+            bail!(
+                "compute_unit_limit must be between 1 and {}, got {}",
+                MAX_COMPUTE_UNIT_LIMIT,
+                self.compute_unit_limit
+            );
+        }
+        if self.min_micro_lamports > self.max_micro_lamports {
+            bail!(
+                "min_micro_lamports {} exceeds the max {}",
+                self.min_micro_lamports,
+                self.max_micro_lamports
+            );
+        }
+        if let Some(percentile) = self.percentile() {
+            if !(0.0..=100.0).contains(&percentile) {
+                bail!("percentile must be between 0 and 100, got {}", percentile);
+            }
+        }
+        if let FeeMode::Adaptive { multiplier, .. } = self.mode {
+            if multiplier.is_nan() || multiplier < 1.0 {
+                bail!("multiplier must be >= 1, got {}", multiplier);
+            }
+        }
+        if self.max_lamports_per_hour == Some(0) {
+            bail!("max_lamports_per_hour must be > 0");
+        }
+        Ok(())
+    }
+
+    /// Percentile of recent fees the mode is based on, None for fixed prices
+    pub fn percentile(&self) -> Option<f64> {
+        match self.mode {
+            FeeMode::Fixed { .. } => None,
+            FeeMode::Percentile { percentile } | FeeMode::Adaptive { percentile, .. } => {
+                Some(percentile)
+            }
+        }
+    }
+
+    /// Compute unit price for an attempt (1-based), from the recent fee
+    /// percentile when the mode uses one
+    pub fn price(&self, recent_fee: u64, attempt: u32) -> u64 {
+        let (base, escalation) = match self.mode {
+            FeeMode::Fixed { micro_lamports } => (micro_lamports, 1.0),
+            FeeMode::Percentile { .. } => (recent_fee, 1.0),
+            FeeMode::Adaptive { multiplier, .. } => (
+                recent_fee,
+                multiplier.powi(attempt.saturating_sub(1).min(32) as i32),
+            ),
+        };
+        let escalated = (base as f64 * escalation).min(u64::MAX as f64) as u64;
+        escalated.clamp(self.min_micro_lamports, self.max_micro_lamports)
+    }
+
+    /// Priority fee in lamports for a transaction at `micro_lamports` per CU
+    pub fn lamports(&self, micro_lamports: u64) -> u64 {
+        (micro_lamports as u128 * self.compute_unit_limit as u128).div_ceil(1_000_000) as u64
+    }
+}
+
+/// Value at percentile `p` (0-100) of `fees`, 0 when empty
+pub fn percentile(fees: &mut [u64], p: f64) -> u64 {
+    if fees.is_empty() {
+        return 0;
+    }
+    fees.sort_unstable();
+    let rank = (p.clamp(0.0, 100.0) / 100.0 * (fees.len() - 1) as f64).round() as usize;
+    fees[rank]
+}
+
+/// Set the price of a message's `SetComputeUnitPrice` instruction. Returns
+/// false if the message doesn't have one.
+pub fn set_compute_unit_price(message: &mut VersionedMessage, micro_lamports: u64) -> bool {
+    let price = ComputeBudgetInstruction::set_compute_unit_price(micro_lamports);
+    let Some(program_index) = message
+        .static_account_keys()
+        .iter()
+        .position(|key| *key == price.program_id)
+    else {
+        return false;
+    };
+
+    let instructions = match message {
+        VersionedMessage::Legacy(message) => &mut message.instructions,
+        VersionedMessage::V0(message) => &mut message.instructions,
+    };
+    for instruction in instructions {
+        // Same program and instruction discriminant
+        if instruction.program_id_index as usize == program_index
+            && instruction.data.first() == price.data.first()
+        {
+            instruction.data = price.data;
+            return true;
+        }
+    }
+    false
+}
+
+/// Priority fees paid over the last hour. Fees are checked against the cap
+/// before sending but only charged once a transaction lands, since expired
+/// and rejected transactions pay nothing; transactions in flight together can
+/// overshoot it by their fees.
+#[derive(Debug, Default)]
+pub struct FeeBudget {
+    max_lamports_per_hour: Option<u64>,
+    // (time ms, lamports) of each fee, oldest first
+    spent: VecDeque<(u64, u64)>,
+}
+
+impl FeeBudget {
+    pub fn new(max_lamports_per_hour: Option<u64>) -> Self {
+        Self {
+            max_lamports_per_hour,
+            spent: VecDeque::new(),
+        }
+    }
+
+    pub fn set_limit(&mut self, max_lamports_per_hour: Option<u64>) {
+        self.max_lamports_per_hour = max_lamports_per_hour;
+    }
+
+    /// Lamports paid in the hour up to `now_ms`
+    pub fn spent(&mut self, now_ms: u64) -> u64 {
+        while self
+            .spent
+            .front()
+            .is_some_and(|(time, _)| now_ms.saturating_sub(*time) >= BUDGET_WINDOW_MS)
+        {
+            self.spent.pop_front();
+        }
+        self.spent.iter().map(|(_, lamports)| lamports).sum()
+    }
+
+    /// Whether a fee still fits in the hourly budget
+    pub fn fits(&mut self, now_ms: u64, lamports: u64) -> bool {
+        match self.max_lamports_per_hour {
+            Some(limit) => lamports == 0 || self.spent(now_ms) + lamports <= limit,
+            None => true,
+        }
+    }
+
+    /// Record a fee paid by a transaction that landed
+    pub fn spend(&mut self, now_ms: u64, lamports: u64) {
+        if lamports > 0 {
+            self.spent.push_back((now_ms, lamports));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{instruction::Instruction, message::Message, pubkey::Pubkey};
+
+    #[test]
+    fn test_fee_modes() {
+        let mut config = PriorityFeeConfig {
+            compute_unit_limit: 400_000,
+            mode: FeeMode::Adaptive {
+                percentile: 75.0,
+                multiplier: 2.0,
+            },
+            min_micro_lamports: 1_000,
+            max_micro_lamports: 50_000,
+            max_lamports_per_hour: None,
+        };
+
+        // Escalates on every re-send up to the cap
+        assert_eq!(config.price(10_000, 1), 10_000);
+        assert_eq!(config.price(10_000, 2), 20_000);
+        assert_eq!(config.price(10_000, 4), 50_000);
+        // Floor when recent fees are zero
+        assert_eq!(config.price(0, 1), 1_000);
+
+        config.mode = FeeMode::Percentile { percentile: 75.0 };
+        assert_eq!(config.price(10_000, 3), 10_000);
+        config.mode = FeeMode::Fixed {
+            micro_lamports: 5_000,
+        };
+        assert_eq!(config.price(10_000, 3), 5_000);
+        assert_eq!(config.percentile(), None);
+
+        // 5000 micro-lamports x 400k CU
+        assert_eq!(config.lamports(5_000), 2_000);
+        assert_eq!(config.lamports(1), 1);
+
+        let mut fees = vec![0, 500, 100, 0, 2_000];
+        assert_eq!(percentile(&mut fees, 50.0), 100);
+        assert_eq!(percentile(&mut fees, 100.0), 2_000);
+        assert_eq!(percentile(&mut [], 75.0), 0);
+    }
+
+    #[test]
+    fn test_set_compute_unit_price() {
+        let payer = Pubkey::new_unique();
+        let memo = Instruction::new_with_bytes(Pubkey::new_unique(), &[3, 1, 2], vec![]);
+        let mut message = VersionedMessage::Legacy(Message::new(
+            &[
+                ComputeBudgetInstruction::set_compute_unit_limit(400_000),
+                ComputeBudgetInstruction::set_compute_unit_price(1),
+                memo,
+            ],
+            Some(&payer),
+        ));
+
+        assert!(set_compute_unit_price(&mut message, 25_000));
+        let expected = ComputeBudgetInstruction::set_compute_unit_price(25_000).data;
+        let instructions = message.instructions();
+        assert_eq!(instructions[1].data, expected);
+        // Limit and other programs' instructions are untouched
+        assert_eq!(
+            instructions[0].data,
+            ComputeBudgetInstruction::set_compute_unit_limit(400_000).data
+        );
+        assert_eq!(instructions[2].data, vec![3, 1, 2]);
+
+        let mut without = VersionedMessage::Legacy(Message::new(
+            &[ComputeBudgetInstruction::set_compute_unit_limit(400_000)],
+            Some(&payer),
+        ));
+        assert!(!set_compute_unit_price(&mut without, 25_000));
+        assert_eq!(
+            without.static_account_keys()[1],
+            ComputeBudgetInstruction::set_compute_unit_price(0).program_id
+        );
+    }
+
+    #[test]
+    fn test_hourly_budget() {
+        let mut budget = FeeBudget::new(Some(10_000));
+        assert!(budget.fits(0, 6_000));
+        // Checking doesn't charge, only fees of landed transactions count
+        assert!(budget.fits(0, 10_000));
+        budget.spend(0, 6_000);
+        assert!(!budget.fits(1_000, 6_000));
+        assert!(budget.fits(1_000, 4_000));
+        budget.spend(1_000, 4_000);
+        assert_eq!(budget.spent(2_000), 10_000);
+        // Zero fees always go through
+        assert!(budget.fits(2_000, 0));
+
+        // The first fee leaves the window after an hour
+        assert_eq!(budget.spent(BUDGET_WINDOW_MS), 4_000);
+        assert!(budget.fits(BUDGET_WINDOW_MS, 6_000));
+
+        budget.set_limit(None);
+        assert!(budget.fits(BUDGET_WINDOW_MS, 1_000_000));
+    }
+}
@@ -0,0 +1,8 @@
+//! Building blocks shared by the example bots.
+
+pub mod fees;
+pub mod shutdown;
+pub mod supervisor;
+pub mod tx;
+pub mod units;
+pub mod validate;
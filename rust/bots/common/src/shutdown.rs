@@ -0,0 +1,539 @@
+//! Shutdown policies: what happens to open orders and positions when the bot
+//! stops, the orders closing positions and the final state check before the
+//! process exits.
+
+use std::{
+    fmt,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail, Result};
+use drift_rs::types::{MarketType, OrderParams, OrderType, PerpPosition, PositionDirection};
+use log::info;
+use serde::Deserialize;
+
+use crate::units::{BaseAmount, Bps, Price};
+use crate::validate::{check_range, ensure_positive};
+
+// User account checks while waiting for shutdown orders to fill or cancel
+pub const SHUTDOWN_POLL: Duration = Duration::from_secs(1);
+
+// OrderParamsBitFlag::ImmediateOrCancel, unfilled size is cancelled instead of resting
+const IMMEDIATE_OR_CANCEL: u8 = 0b01;
+
+/// How open positions are handled on shutdown. Orders are always cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShutdownPolicy {
+    // Keep positions open
+    CancelOnly,
+    // Reduce-only limit IOC at most `max_slippage_bps` through the oracle
+    Limit {
+        max_slippage_bps: f64,
+    },
+    // Reduce-only limit IOC slices spread evenly over `minutes`
+    Twap {
+        minutes: f64,
+        slices: u32,
+        max_slippage_bps: f64,
+    },
+    // Reduce-only market order auctioned from `start_offset_bps` to
+    // `max_slippage_bps` through the oracle over `duration_slots`
+    Auction {
+        duration_slots: u8,
+        start_offset_bps: f64,
+        max_slippage_bps: f64,
+    },
+}
+
+impl ShutdownPolicy {
+    /// Whether the policy closes positions
+    pub fn flattens(&self) -> bool {
+        !matches!(self, ShutdownPolicy::CancelOnly)
+    }
+}
+
+impl fmt::Display for ShutdownPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ShutdownPolicy::CancelOnly => write!(f, "cancel only"),
+            ShutdownPolicy::Limit { max_slippage_bps } => {
+                write!(f, "limit IOC within {} bps", max_slippage_bps)
+            }
+            ShutdownPolicy::Twap {
+                minutes,
+                slices,
+                max_slippage_bps,
+            } => write!(
+                f,
+                "TWAP over {} min in {} slices within {} bps",
+                minutes, slices, max_slippage_bps
+            ),
+            ShutdownPolicy::Auction {
+                duration_slots,
+                start_offset_bps,
+                max_slippage_bps,
+            } => write!(
+                f,
+                "{}-slot auction from {} to {} bps",
+                duration_slots, start_offset_bps, max_slippage_bps
+            ),
+        }
+    }
+}
+
+/// Shutdown behavior and how long to wait for it to show on-chain
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShutdownConfig {
+    pub policy: ShutdownPolicy,
+    // How long auction orders may rest before the remainder is cancelled (seconds)
+    pub auction_timeout_secs: u64,
+    // How long to wait for the user account to show the final state (seconds)
+    pub confirm_timeout_secs: u64,
+    // Hard limit on the whole cleanup after a signal, error or panic (seconds)
+    pub timeout_secs: u64,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            policy: ShutdownPolicy::Limit {
+                max_slippage_bps: 50.0,
+            },
+            auction_timeout_secs: 30,
+            confirm_timeout_secs: 10,
+            timeout_secs: 120,
+        }
+    }
+}
+
+impl ShutdownConfig {
+    /// Check the settings are usable
+    pub fn validate(&self) -> Result<()> {
+        match self.policy {
+            ShutdownPolicy::CancelOnly => {}
+            ShutdownPolicy::Limit { max_slippage_bps } => {
+                ensure_positive("max_slippage_bps", max_slippage_bps)?;
+            }
+            ShutdownPolicy::Twap {
+                minutes,
+                slices,
+                max_slippage_bps,
+            } => {
+                ensure_positive("minutes", minutes)?;
+                if slices == 0 {
+                    bail!("slices must be > 0");
+                }
+                ensure_positive("max_slippage_bps", max_slippage_bps)?;
+            }
+            ShutdownPolicy::Auction {
+                duration_slots,
+                start_offset_bps,
+                max_slippage_bps,
+            } => {
+                if duration_slots == 0 {
+                    bail!("duration_slots must be > 0");
+                }
+                ensure_positive("max_slippage_bps", max_slippage_bps)?;
+                check_range("start_offset_bps", start_offset_bps, max_slippage_bps)?;
+                if self.auction_timeout_secs == 0 {
+                    bail!("auction_timeout_secs must be > 0");
+                }
+            }
+        }
+        if (self.timeout_secs as f64) < self.policy_secs() {
+            bail!(
+                "timeout_secs must cover the shutdown policy and confirmation, at least {}s",
+                self.policy_secs().ceil()
+            );
+        }
+        Ok(())
+    }
+
+    /// Seconds the policy is expected to take, from the TWAP or auction
+    /// duration and the final state check
+    pub fn policy_secs(&self) -> f64 {
+        let close_secs = match self.policy {
+            ShutdownPolicy::Twap { minutes, .. } => minutes * 60.0,
+            ShutdownPolicy::Auction { .. } => self.auction_timeout_secs as f64,
+            ShutdownPolicy::CancelOnly | ShutdownPolicy::Limit { .. } => 0.0,
+        };
+        close_secs + self.confirm_timeout_secs as f64
+    }
+
+    /// Limit on the whole cleanup: `timeout_secs`, raised to cover the policy
+    /// for configs that skip `validate`
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs_f64(self.policy_secs().max(self.timeout_secs as f64))
+    }
+}
+
+/// Worst acceptable price for a reduce-only close `bps` through the oracle:
+/// below it when selling a long, above when buying back a short. At least one
+/// unit, `None` on overflow.
+pub fn close_price(oracle_price: Price, closing_long: bool, bps: Bps) -> Option<Price> {
+    let offset = bps.of(oracle_price)?;
+    let price = if closing_long {
+        oracle_price.checked_sub(offset)?
+    } else {
+        oracle_price.checked_add(offset)?
+    };
+    Some(price.max(Price::from_raw(1)))
+}
+
+/// Base amount to close in the next TWAP slice out of a `remaining` position
+/// size, the rest of it in the last one
+pub fn twap_slice(remaining: BaseAmount, slices_left: u32) -> BaseAmount {
+    if slices_left <= 1 {
+        return remaining;
+    }
+    let (raw, slices) = (remaining.raw(), i64::from(slices_left));
+    BaseAmount::from_raw(raw / slices + i64::from(raw % slices != 0))
+}
+
+/// Reduce-only order closing `base_asset_amount` of a position, `None` for
+/// policies that keep positions
+pub fn close_order_params(
+    policy: &ShutdownPolicy,
+    pos: &PerpPosition,
+    base_asset_amount: BaseAmount,
+    oracle_price: Price,
+    max_ts: i64,
+) -> Result<Option<OrderParams>> {
+    let closing_long = pos.base_asset_amount > 0;
+    let price = |bps: f64| {
+        close_price(oracle_price, closing_long, Bps(bps))
+            .ok_or_else(|| anyhow!("close price {} bps from {} out of range", bps, oracle_price))
+    };
+    let base_asset_amount = base_asset_amount
+        .to_unsigned()
+        .ok_or_else(|| anyhow!("close size {} out of range", base_asset_amount))?;
+    let close = OrderParams {
+        market_type: MarketType::Perp,
+        direction: if closing_long {
+            PositionDirection::Short
+        } else {
+            PositionDirection::Long
+        },
+        base_asset_amount,
+        market_index: pos.market_index,
+        reduce_only: true,
+        ..Default::default()
+    };
+
+    Ok(match *policy {
+        ShutdownPolicy::CancelOnly => None,
+        ShutdownPolicy::Limit { max_slippage_bps }
+        | ShutdownPolicy::Twap {
+            max_slippage_bps, ..
+        } => Some(OrderParams {
+            order_type: OrderType::Limit,
+            // Close prices are at least one unit
+            price: price(max_slippage_bps)?.raw().unsigned_abs(),
+            bit_flags: IMMEDIATE_OR_CANCEL,
+            ..close
+        }),
+        ShutdownPolicy::Auction {
+            duration_slots,
+            start_offset_bps,
+            max_slippage_bps,
+        } => {
+            let end_price = price(max_slippage_bps)?;
+            Some(OrderParams {
+                order_type: OrderType::Market,
+                // Limit once the auction ends
+                price: end_price.raw().unsigned_abs(),
+                auction_duration: Some(duration_slots),
+                auction_start_price: Some(price(start_offset_bps)?.raw()),
+                auction_end_price: Some(end_price.raw()),
+                max_ts: Some(max_ts),
+                ..close
+            })
+        }
+    })
+}
+
+/// A bot's side of `close_positions`: its positions, prices and orders
+// The bots await shutdown on their own task, so the futures need not be Send
+#[allow(async_fn_in_trait)]
+pub trait PositionCloser {
+    /// Non-zero perp positions to close
+    async fn open_positions(&mut self) -> Result<Vec<PerpPosition>>;
+
+    /// Oracle price the close orders in `market_index` are priced from
+    async fn oracle_price(&mut self, market_index: u16) -> Result<Price>;
+
+    /// Send reduce-only close orders in one transaction. A failed send is
+    /// logged by the bot, closing goes on with the next slice.
+    async fn place_close_orders(&mut self, orders: Vec<OrderParams>) -> Result<()>;
+
+    /// Cancel every open order, including unfilled auction orders
+    async fn cancel_all_orders(&mut self) -> Result<()>;
+}
+
+/// Close open positions under a shutdown policy. Limit and TWAP orders are
+/// IOC, auction orders rest until `auction_timeout_secs` and are then
+/// cancelled.
+pub async fn close_positions(
+    closer: &mut impl PositionCloser,
+    policy: ShutdownPolicy,
+    auction_timeout_secs: u64,
+) -> Result<()> {
+    match policy {
+        ShutdownPolicy::CancelOnly => {}
+        ShutdownPolicy::Limit { .. } => {
+            send_close_orders(closer, policy, 1, auction_timeout_secs).await?;
+        }
+        ShutdownPolicy::Twap {
+            minutes, slices, ..
+        } => {
+            let interval = Duration::from_secs_f64(minutes * 60.0 / slices as f64);
+            for slice in 0..slices {
+                let slices_left = slices - slice;
+                info!("TWAP close slice {}/{}", slice + 1, slices);
+                if !send_close_orders(closer, policy, slices_left, auction_timeout_secs).await?
+                    || slices_left == 1
+                {
+                    break;
+                }
+                tokio::time::sleep(interval).await;
+            }
+        }
+        ShutdownPolicy::Auction { .. } => {
+            if send_close_orders(closer, policy, 1, auction_timeout_secs).await? {
+                let deadline = Instant::now() + Duration::from_secs(auction_timeout_secs);
+                while Instant::now() < deadline && !closer.open_positions().await?.is_empty() {
+                    tokio::time::sleep(SHUTDOWN_POLL).await;
+                }
+                // Unfilled auction orders would otherwise rest until max_ts
+                closer.cancel_all_orders().await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Send reduce-only orders closing `1 / slices_left` of every open position.
+/// Returns false if there was nothing to close.
+async fn send_close_orders(
+    closer: &mut impl PositionCloser,
+    policy: ShutdownPolicy,
+    slices_left: u32,
+    auction_timeout_secs: u64,
+) -> Result<bool> {
+    let positions = closer.open_positions().await?;
+    if positions.is_empty() {
+        return Ok(false);
+    }
+
+    let now_secs = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+    let max_ts = now_secs + auction_timeout_secs as i64;
+    let mut orders = Vec::with_capacity(positions.len());
+    for pos in &positions {
+        let oracle_price = closer.oracle_price(pos.market_index).await?;
+        let size = BaseAmount::from_raw(pos.base_asset_amount)
+            .checked_abs()
+            .ok_or_else(|| anyhow!("position in market {} out of range", pos.market_index))?;
+        orders.extend(close_order_params(
+            &policy,
+            pos,
+            twap_slice(size, slices_left),
+            oracle_price,
+            max_ts,
+        )?);
+    }
+
+    info!("Closing {} position(s): {}", orders.len(), policy);
+    closer.place_close_orders(orders).await?;
+    Ok(true)
+}
+
+/// Open orders and positions left after shutdown
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShutdownState {
+    pub open_orders: usize,
+    // (market label, base units) of positions still open
+    pub positions: Vec<(String, f64)>,
+}
+
+impl ShutdownState {
+    /// No orders left, and no positions if the policy flattens
+    pub fn is_complete(&self, policy: &ShutdownPolicy) -> bool {
+        self.open_orders == 0 && (!policy.flattens() || self.positions.is_empty())
+    }
+}
+
+impl fmt::Display for ShutdownState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} open orders, ", self.open_orders)?;
+        if self.positions.is_empty() {
+            return write!(f, "flat");
+        }
+        write!(f, "positions")?;
+        for (market, base) in &self.positions {
+            write!(f, " {} {:.6}", market, base)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_close_prices_and_slices() {
+        // 50 bps through a $100 oracle
+        let oracle = Price::from_raw(100_000_000);
+        let close = |price, long, bps| close_price(price, long, Bps(bps)).map(Price::raw);
+        assert_eq!(close(oracle, true, 50.0), Some(99_500_000));
+        assert_eq!(close(oracle, false, 50.0), Some(100_500_000));
+        assert_eq!(close(Price::from_raw(100), true, 20_000.0), Some(1));
+        assert_eq!(close(Price::from_raw(i64::MAX), false, 50.0), None);
+
+        // 10 over 3 slices: 4, then 3, then the rest
+        let slice = |remaining, slices| twap_slice(BaseAmount::from_raw(remaining), slices).raw();
+        assert_eq!(slice(10, 3), 4);
+        assert_eq!(slice(6, 2), 3);
+        assert_eq!(slice(3, 1), 3);
+        assert_eq!(slice(3, 0), 3);
+    }
+
+    #[test]
+    fn test_final_state() {
+        let cancel_only = ShutdownPolicy::CancelOnly;
+        let limit = ShutdownPolicy::Limit {
+            max_slippage_bps: 50.0,
+        };
+        let mut state = ShutdownState {
+            open_orders: 0,
+            positions: vec![("SOL-PERP".to_string(), -0.25)],
+        };
+        assert!(state.is_complete(&cancel_only));
+        assert!(!state.is_complete(&limit));
+        assert_eq!(
+            state.to_string(),
+            "0 open orders, positions SOL-PERP -0.250000"
+        );
+
+        state.positions.clear();
+        assert!(state.is_complete(&limit));
+        state.open_orders = 1;
+        assert!(!state.is_complete(&cancel_only));
+        assert_eq!(state.to_string(), "1 open orders, flat");
+
+        let config: ShutdownConfig = toml::from_str(
+            "policy = { twap = { minutes = 5.0, slices = 10, max_slippage_bps = 30.0 } }",
+        )
+        .unwrap();
+        assert!(config.policy.flattens());
+        assert_eq!(config.confirm_timeout_secs, 10);
+        assert_eq!(config.policy_secs(), 310.0);
+        assert_eq!(config.timeout(), Duration::from_secs(310));
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_close_orders() {
+        let oracle = Price::from_raw(100_000_000);
+        let short = PerpPosition {
+            market_index: 1,
+            base_asset_amount: -2_000_000_000,
+            ..Default::default()
+        };
+        let size = BaseAmount::from_raw(500_000_000);
+        let close = |policy| close_order_params(&policy, &short, size, oracle, 1_000).unwrap();
+
+        // Buying back a short IOC at most 50 bps above the oracle
+        let limit = close(ShutdownPolicy::Limit {
+            max_slippage_bps: 50.0,
+        })
+        .unwrap();
+        assert_eq!(limit.direction, PositionDirection::Long);
+        assert_eq!(limit.order_type, OrderType::Limit);
+        assert_eq!(limit.price, 100_500_000);
+        assert_eq!(limit.base_asset_amount, 500_000_000);
+        assert_eq!(limit.bit_flags, IMMEDIATE_OR_CANCEL);
+        assert!(limit.reduce_only);
+
+        let auction = close(ShutdownPolicy::Auction {
+            duration_slots: 20,
+            start_offset_bps: 10.0,
+            max_slippage_bps: 50.0,
+        })
+        .unwrap();
+        assert_eq!(auction.order_type, OrderType::Market);
+        assert_eq!(auction.auction_start_price, Some(100_100_000));
+        assert_eq!(auction.auction_end_price, Some(100_500_000));
+        assert_eq!(auction.max_ts, Some(1_000));
+        assert!(close(ShutdownPolicy::CancelOnly).is_none());
+    }
+
+    // Fills every close order in full
+    struct FillingCloser {
+        positions: Vec<PerpPosition>,
+        sent: Vec<Vec<OrderParams>>,
+    }
+
+    impl PositionCloser for FillingCloser {
+        async fn open_positions(&mut self) -> Result<Vec<PerpPosition>> {
+            Ok(self.positions.clone())
+        }
+
+        async fn oracle_price(&mut self, _market_index: u16) -> Result<Price> {
+            Ok(Price::from_raw(100_000_000))
+        }
+
+        async fn place_close_orders(&mut self, orders: Vec<OrderParams>) -> Result<()> {
+            for order in &orders {
+                let pos = self
+                    .positions
+                    .iter_mut()
+                    .find(|pos| pos.market_index == order.market_index)
+                    .unwrap();
+                pos.base_asset_amount -=
+                    pos.base_asset_amount.signum() * order.base_asset_amount as i64;
+            }
+            self.positions.retain(|pos| pos.base_asset_amount != 0);
+            self.sent.push(orders);
+            Ok(())
+        }
+
+        async fn cancel_all_orders(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_twap_close() {
+        let mut closer = FillingCloser {
+            positions: vec![PerpPosition {
+                market_index: 0,
+                base_asset_amount: 10,
+                ..Default::default()
+            }],
+            sent: Vec::new(),
+        };
+        let twap = ShutdownPolicy::Twap {
+            minutes: 0.0001,
+            slices: 3,
+            max_slippage_bps: 30.0,
+        };
+        close_positions(&mut closer, twap, 30).await.unwrap();
+
+        // 10 over 3 slices, selling the long
+        let sizes: Vec<u64> = closer
+            .sent
+            .iter()
+            .map(|orders| orders[0].base_asset_amount)
+            .collect();
+        assert_eq!(sizes, [4, 3, 3]);
+        assert_eq!(closer.sent[0][0].direction, PositionDirection::Short);
+        assert!(closer.positions.is_empty());
+
+        // Nothing left to close
+        close_positions(&mut closer, twap, 30).await.unwrap();
+        assert_eq!(closer.sent.len(), 3);
+    }
+}
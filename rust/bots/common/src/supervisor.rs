@@ -0,0 +1,176 @@
+//! Shutdown supervisor: runs the trading loop until a signal, an error or a
+//! panic stops it, then runs the cleanup path within a timeout.
+
+use std::{any::Any, fmt, future::Future, panic::AssertUnwindSafe, time::Duration};
+
+use anyhow::{anyhow, Result};
+use futures_util::{future::select_all, FutureExt};
+use tokio::signal::unix::{signal, Signal, SignalKind};
+
+/// Signals that stop the bots. SIGHUP from a closed terminal stops them like
+/// SIGTERM rather than leaving quotes up.
+pub const STOP_SIGNALS: [(SignalKind, &str); 3] = [
+    (SignalKind::interrupt(), "SIGINT"),
+    (SignalKind::terminate(), "SIGTERM"),
+    (SignalKind::hangup(), "SIGHUP"),
+];
+
+/// Why the trading loop stopped
+#[derive(Debug)]
+pub enum Exit {
+    Signal(&'static str),
+    Finished,
+    Error(anyhow::Error),
+    Panic(String),
+}
+
+impl fmt::Display for Exit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Exit::Signal(name) => write!(f, "received {}", name),
+            Exit::Finished => write!(f, "trading loop finished"),
+            Exit::Error(e) => write!(f, "fatal error: {:#}", e),
+            Exit::Panic(message) => write!(f, "trading loop panicked: {}", message),
+        }
+    }
+}
+
+/// Result of the cleanup path
+#[derive(Debug)]
+pub enum Cleanup {
+    // Final state confirmed on-chain
+    Confirmed,
+    // Cleanup ran but the final state wasn't seen before its own timeout
+    Unconfirmed,
+    Failed(anyhow::Error),
+    TimedOut(Duration),
+}
+
+impl Cleanup {
+    /// Whether the final state was confirmed on-chain
+    pub fn is_confirmed(&self) -> bool {
+        matches!(self, Cleanup::Confirmed)
+    }
+}
+
+impl fmt::Display for Cleanup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cleanup::Confirmed => write!(f, "final state confirmed"),
+            Cleanup::Unconfirmed => write!(f, "final state not confirmed"),
+            Cleanup::Failed(e) => write!(f, "failed: {:#}", e),
+            Cleanup::TimedOut(timeout) => write!(f, "timed out after {}s", timeout.as_secs()),
+        }
+    }
+}
+
+/// Process exit code: non-zero if trading stopped on an error or panic, or
+/// cleanup wasn't confirmed
+pub fn exit_code(exit: &Exit, cleanup: &Cleanup) -> u8 {
+    match exit {
+        Exit::Error(_) | Exit::Panic(_) => 1,
+        _ if !cleanup.is_confirmed() => 1,
+        _ => 0,
+    }
+}
+
+/// Run `trading` until it returns, panics or one of `signals` arrives
+pub async fn run<F>(trading: F, signals: &[(SignalKind, &'static str)]) -> Result<Exit>
+where
+    F: Future<Output = Result<()>>,
+{
+    // Registered before trading starts so no signal is missed
+    let mut streams = signals
+        .iter()
+        .map(|(kind, name)| Ok((signal(*kind)?, *name)))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(tokio::select! {
+        result = AssertUnwindSafe(trading).catch_unwind() => match result {
+            Ok(Ok(())) => Exit::Finished,
+            Ok(Err(e)) => Exit::Error(e),
+            Err(panic) => Exit::Panic(panic_message(&*panic)),
+        },
+        name = recv_any(&mut streams) => Exit::Signal(name),
+    })
+}
+
+/// Run the cleanup path, bounded by `timeout`. A panic in it counts as a failure.
+pub async fn cleanup<F>(stop: F, timeout: Duration) -> Cleanup
+where
+    F: Future<Output = Result<bool>>,
+{
+    match tokio::time::timeout(timeout, AssertUnwindSafe(stop).catch_unwind()).await {
+        Ok(Ok(Ok(true))) => Cleanup::Confirmed,
+        Ok(Ok(Ok(false))) => Cleanup::Unconfirmed,
+        Ok(Ok(Err(e))) => Cleanup::Failed(e),
+        Ok(Err(panic)) => Cleanup::Failed(anyhow!("panicked: {}", panic_message(&*panic))),
+        Err(_) => Cleanup::TimedOut(timeout),
+    }
+}
+
+async fn recv_any(streams: &mut [(Signal, &'static str)]) -> &'static str {
+    if streams.is_empty() {
+        return std::future::pending().await;
+    }
+    let received = streams.iter_mut().map(|(stream, name)| {
+        Box::pin(async move {
+            stream.recv().await;
+            *name
+        })
+    });
+    select_all(received).await.0
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_trading_exits() {
+        let exit = run(async { Ok(()) }, &[]).await.unwrap();
+        assert!(matches!(exit, Exit::Finished));
+
+        let exit = run(async { Err(anyhow!("no oracle")) }, &[]).await.unwrap();
+        assert_eq!(exit.to_string(), "fatal error: no oracle");
+
+        let exit = run(async { panic!("no markets") }, &[]).await.unwrap();
+        assert_eq!(exit.to_string(), "trading loop panicked: no markets");
+        assert_eq!(exit_code(&exit, &Cleanup::Confirmed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cleanup() {
+        let timeout = Duration::from_millis(50);
+        let confirmed = cleanup(async { Ok(true) }, timeout).await;
+        assert!(confirmed.is_confirmed());
+        assert_eq!(exit_code(&Exit::Signal("SIGTERM"), &confirmed), 0);
+
+        let unconfirmed = cleanup(async { Ok(false) }, timeout).await;
+        assert_eq!(unconfirmed.to_string(), "final state not confirmed");
+        assert_eq!(exit_code(&Exit::Signal("SIGTERM"), &unconfirmed), 1);
+
+        let stuck = cleanup(
+            async {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                Ok(true)
+            },
+            timeout,
+        )
+        .await;
+        assert!(matches!(stuck, Cleanup::TimedOut(_)));
+
+        let panicked = cleanup(async { panic!("lost client") }, timeout).await;
+        assert_eq!(panicked.to_string(), "failed: panicked: lost client");
+    }
+}
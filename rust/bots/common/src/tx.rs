@@ -0,0 +1,608 @@
+//! Transaction sending: priority fees, confirmation tracking, Drift program
+//! error decoding and re-sends with a fresh blockhash.
+
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context, Result};
+use drift_rs::{types::RpcSendTransactionConfig, DriftClient};
+use log::warn;
+use serde::Deserialize;
+use solana_rpc_client_api::{
+    client_error::{Error as ClientError, ErrorKind as ClientErrorKind},
+    config::RpcTransactionConfig,
+    request::RpcError,
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, instruction::InstructionError, message::VersionedMessage,
+    signature::Signature, transaction::TransactionError,
+};
+use solana_transaction_status_client_types::UiTransactionEncoding;
+
+use crate::fees::{percentile, set_compute_unit_price, FeeBudget, PriorityFeeConfig};
+
+// Blockhashes are valid for 150 blocks (~60s). A transaction neither seen nor
+// past its last valid block height after this long is reported as unknown.
+const MAX_CONFIRM_TIME: Duration = Duration::from_secs(120);
+
+// Recent prioritization fees are fetched at most this often
+const FEE_REFRESH: Duration = Duration::from_secs(2);
+
+/// Confirmation and retry policy for sent transactions
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TxConfig {
+    // Re-sends with a fresh blockhash after the first attempt
+    pub max_retries: u32,
+    // Wait before the first re-send, doubled for each further one (ms)
+    pub retry_backoff_ms: u64,
+    // Interval between signature status checks (ms)
+    pub poll_interval_ms: u64,
+    // Drift errors that are worth re-sending, e.g. "OracleNotFound"
+    pub retry_errors: Vec<String>,
+    // Simulate before sending so failing transactions are rejected by the RPC node
+    pub preflight: bool,
+    // Compute unit limit and priority fee per attempt
+    pub priority_fee: PriorityFeeConfig,
+}
+
+impl Default for TxConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            retry_backoff_ms: 250,
+            poll_interval_ms: 400,
+            retry_errors: Vec::new(),
+            preflight: false,
+            priority_fee: PriorityFeeConfig::default(),
+        }
+    }
+}
+
+impl TxConfig {
+    /// Check the settings are usable
+    pub fn validate(&self) -> Result<()> {
+        if self.poll_interval_ms == 0 {
+            bail!("poll_interval_ms must be > 0");
+        }
+        self.priority_fee.validate().context("priority_fee")
+    }
+
+    /// Delay before re-sending a transaction whose last attempt ended in
+    /// `status`, `None` to give up. Expired transactions and RPC errors are
+    /// re-sent, program errors only when listed in `retry_errors`. Unknown
+    /// outcomes may still land, so they are never re-sent.
+    fn retry_delay(&self, attempts: u32, status: &TxStatus) -> Option<Duration> {
+        let retryable = match status {
+            TxStatus::Confirmed | TxStatus::Unknown => false,
+            TxStatus::Expired => true,
+            // Set only when the transaction never left the node or failed on-chain
+            TxStatus::Failed(error) => match (&error.code, &error.name) {
+                (None, _) => true,
+                (Some(_), Some(name)) => self.retry_errors.contains(name),
+                (Some(_), None) => false,
+            },
+        };
+        if !retryable || attempts > self.max_retries {
+            return None;
+        }
+        let backoff = self.retry_backoff_ms << (attempts - 1).min(16);
+        Some(Duration::from_millis(backoff))
+    }
+}
+
+/// Why a transaction failed, decoded from the program logs when available
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxError {
+    // Program error name, e.g. `OrderDoesNotExist`
+    pub name: Option<String>,
+    // Program error number, None for RPC and runtime errors
+    pub code: Option<u32>,
+    pub message: String,
+}
+
+impl TxError {
+    /// Decode a failure from transaction or simulation logs, falling back to
+    /// the runtime error
+    pub fn decode(logs: &[String], error: Option<&TransactionError>) -> Self {
+        // Program log: AnchorError occurred. Error Code: OrderDoesNotExist. Error Number: 6087. Error Message: Order does not exist.
+        for line in logs {
+            let Some((_, anchor_error)) = line.split_once("AnchorError") else {
+                continue;
+            };
+            let field = |key: &str| {
+                let (_, rest) = anchor_error.split_once(key)?;
+                Some(rest.split(". ").next()?.trim_end_matches('.').to_string())
+            };
+            if let Some(name) = field("Error Code: ") {
+                return Self {
+                    code: field("Error Number: ").and_then(|code| code.parse().ok()),
+                    message: field("Error Message: ").unwrap_or_else(|| name.clone()),
+                    name: Some(name),
+                };
+            }
+        }
+
+        // Program <id> failed: custom program error: 0x1787
+        let custom_code = logs.iter().find_map(|line| {
+            let (_, hex) = line.split_once("custom program error: 0x")?;
+            u32::from_str_radix(hex.trim(), 16).ok()
+        });
+        let code = custom_code.or(match error {
+            Some(TransactionError::InstructionError(_, InstructionError::Custom(code))) => {
+                Some(*code)
+            }
+            _ => None,
+        });
+
+        Self {
+            name: None,
+            code,
+            message: error.map_or_else(|| "unknown error".to_string(), |e| e.to_string()),
+        }
+    }
+
+    /// Decode a failed send. Preflight failures carry the simulation logs in
+    /// the error text.
+    fn from_send_error(error: &impl fmt::Display) -> Self {
+        let text = error.to_string();
+        let logs: Vec<String> = text.lines().map(str::to_string).collect();
+        let decoded = Self::decode(&logs, None);
+        Self {
+            message: match decoded.name {
+                Some(_) => decoded.message,
+                None => logs.first().cloned().unwrap_or(text),
+            },
+            ..decoded
+        }
+    }
+}
+
+impl fmt::Display for TxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.name, self.code) {
+            (Some(name), Some(code)) => write!(f, "{} ({}): {}", name, code, self.message),
+            (None, Some(code)) => write!(f, "{} (program error {})", self.message, code),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Final state of a sent transaction
+#[derive(Debug, Clone, PartialEq)]
+pub enum TxStatus {
+    Confirmed,
+    // Rejected by the RPC node or failed on-chain
+    Failed(TxError),
+    // Blockhash expired before the transaction landed
+    Expired,
+    // Sent, but neither landed nor expired before `MAX_CONFIRM_TIME`
+    Unknown,
+}
+
+impl TxStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            TxStatus::Confirmed => "confirmed",
+            TxStatus::Failed(_) => "failed",
+            TxStatus::Expired => "expired",
+            TxStatus::Unknown => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for TxStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TxStatus::Failed(error) => write!(f, "failed: {}", error),
+            status => f.write_str(status.label()),
+        }
+    }
+}
+
+/// Outcome of sending a transaction, after any re-sends
+#[derive(Debug, Clone)]
+pub struct TxOutcome {
+    // Signature of the last attempt, None if it was never sent
+    pub signature: Option<Signature>,
+    pub status: TxStatus,
+    pub attempts: u32,
+    // From the first send to the final status
+    pub elapsed: Duration,
+    // Priority fees paid by the attempts that landed
+    pub priority_fee_lamports: u64,
+}
+
+impl TxOutcome {
+    pub fn is_confirmed(&self) -> bool {
+        self.status == TxStatus::Confirmed
+    }
+
+    /// Signature of the confirmed transaction, or an error describing the failure
+    pub fn into_result(self) -> Result<Signature> {
+        match (self.status, self.signature) {
+            (TxStatus::Confirmed, Some(signature)) => Ok(signature),
+            (status, _) => bail!("Transaction {} after {} attempt(s)", status, self.attempts),
+        }
+    }
+}
+
+impl fmt::Display for TxOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.status)?;
+        if let Some(signature) = &self.signature {
+            write!(f, ", sig {}", signature)?;
+        }
+        write!(
+            f,
+            " ({} attempt(s), {}ms, priority fee {} lamports)",
+            self.attempts,
+            self.elapsed.as_millis(),
+            self.priority_fee_lamports
+        )
+    }
+}
+
+/// Sends transactions and tracks them until they confirm, fail or expire,
+/// re-sending with a fresh blockhash under the configured retry policy.
+/// Clones share the priority fee budget.
+#[derive(Clone)]
+pub struct TxSender {
+    client: DriftClient,
+    config: TxConfig,
+    budget: Arc<Mutex<FeeBudget>>,
+    // Last recent fee percentile and when it was fetched
+    recent_fee: Arc<Mutex<Option<(Instant, u64)>>>,
+}
+
+impl TxSender {
+    pub fn new(client: DriftClient, config: TxConfig) -> Self {
+        Self {
+            client,
+            budget: Arc::new(Mutex::new(FeeBudget::new(
+                config.priority_fee.max_lamports_per_hour,
+            ))),
+            recent_fee: Arc::new(Mutex::new(None)),
+            config,
+        }
+    }
+
+    pub fn set_config(&mut self, config: TxConfig) {
+        self.budget
+            .lock()
+            .unwrap()
+            .set_limit(config.priority_fee.max_lamports_per_hour);
+        self.config = config;
+    }
+
+    /// Sign, send and confirm a transaction built with compute budget
+    /// instructions, pricing each attempt under the priority fee settings
+    pub async fn send(&self, mut tx: VersionedMessage) -> TxOutcome {
+        let start = Instant::now();
+        let mut attempts = 0;
+        let mut priority_fee_lamports = 0;
+        loop {
+            attempts += 1;
+            let price = self.compute_unit_price(&tx, attempts).await;
+            let priced = set_compute_unit_price(&mut tx, price);
+            let (signature, status) = self.send_once(tx.clone()).await;
+            if priced && landed(signature, &status) {
+                let lamports = self.config.priority_fee.lamports(price);
+                self.budget.lock().unwrap().spend(now_ms(), lamports);
+                priority_fee_lamports += lamports;
+            }
+
+            let Some(delay) = self.config.retry_delay(attempts, &status) else {
+                return TxOutcome {
+                    signature,
+                    status,
+                    attempts,
+                    elapsed: start.elapsed(),
+                    priority_fee_lamports,
+                };
+            };
+            warn!(
+                "Transaction {}, re-sending in {}ms (attempt {}/{})",
+                status,
+                delay.as_millis(),
+                attempts + 1,
+                self.config.max_retries + 1
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Compute unit price for an attempt, zero once the hourly budget is spent
+    async fn compute_unit_price(&self, tx: &VersionedMessage, attempt: u32) -> u64 {
+        let fees = &self.config.priority_fee;
+        let recent_fee = match fees.percentile() {
+            Some(p) => self.recent_fee(tx, p).await,
+            None => 0,
+        };
+        let price = fees.price(recent_fee, attempt);
+
+        if !self
+            .budget
+            .lock()
+            .unwrap()
+            .fits(now_ms(), fees.lamports(price))
+        {
+            warn!(
+                "Priority fee budget of {} lamports/hour spent, sending without a priority fee",
+                fees.max_lamports_per_hour.unwrap_or_default()
+            );
+            return 0;
+        }
+        price
+    }
+
+    /// Percentile of fees recently paid by transactions locking the same
+    /// accounts, refreshed at most every `FEE_REFRESH`
+    async fn recent_fee(&self, tx: &VersionedMessage, p: f64) -> u64 {
+        let cached = *self.recent_fee.lock().unwrap();
+        if let Some((fetched, fee)) = cached {
+            if fetched.elapsed() < FEE_REFRESH {
+                return fee;
+            }
+        }
+
+        match self
+            .client
+            .rpc()
+            .get_recent_prioritization_fees(tx.static_account_keys())
+            .await
+        {
+            Ok(recent) => {
+                let mut fees: Vec<u64> = recent.iter().map(|f| f.prioritization_fee).collect();
+                let fee = percentile(&mut fees, p);
+                *self.recent_fee.lock().unwrap() = Some((Instant::now(), fee));
+                fee
+            }
+            Err(e) => {
+                warn!("Failed to get recent prioritization fees: {}", e);
+                cached.map_or(0, |(_, fee)| fee)
+            }
+        }
+    }
+
+    /// Send with a fresh blockhash and wait until it lands or the blockhash expires
+    async fn send_once(&self, tx: VersionedMessage) -> (Option<Signature>, TxStatus) {
+        let rpc = self.client.rpc();
+        let (blockhash, last_valid_height) = match rpc
+            .get_latest_blockhash_with_commitment(CommitmentConfig::confirmed())
+            .await
+        {
+            Ok(latest) => latest,
+            Err(e) => return (None, TxStatus::Failed(TxError::from_send_error(&e))),
+        };
+        // Signed here so the signature is known even if the send errors
+        let tx = match self.client.wallet().sign_tx(tx, blockhash) {
+            Ok(tx) => tx,
+            Err(e) => return (None, TxStatus::Failed(TxError::from_send_error(&e))),
+        };
+        let signature = tx.signatures[0];
+
+        let config = RpcSendTransactionConfig {
+            skip_preflight: !self.config.preflight,
+            ..Default::default()
+        };
+        if let Err(e) = rpc.send_transaction_with_config(&tx, config).await {
+            if rejected(&e) {
+                return (None, TxStatus::Failed(TxError::from_send_error(&e)));
+            }
+            // The node may have taken it before the error, so a re-send could
+            // land twice. Follow this one until it lands or expires instead.
+            warn!(
+                "Sending {} failed, checking whether it landed: {}",
+                signature, e
+            );
+        }
+        let status = self.confirm(signature, last_valid_height).await;
+        (Some(signature), status)
+    }
+
+    /// Poll the signature until it confirms, fails or can no longer land
+    async fn confirm(&self, signature: Signature, last_valid_height: u64) -> TxStatus {
+        let rpc = self.client.rpc();
+        let poll_interval = Duration::from_millis(self.config.poll_interval_ms);
+        let start = Instant::now();
+
+        while start.elapsed() < MAX_CONFIRM_TIME {
+            tokio::time::sleep(poll_interval).await;
+
+            // Read before the status: unseen at a height past its blockhash,
+            // the transaction can no longer land
+            let height = rpc
+                .get_block_height_with_commitment(CommitmentConfig::confirmed())
+                .await;
+
+            match rpc.get_signature_statuses(&[signature]).await {
+                Ok(response) => match response.value.into_iter().next().flatten() {
+                    Some(status) => {
+                        if let Some(error) = status.err {
+                            let logs = self.logs(signature).await;
+                            return TxStatus::Failed(TxError::decode(&logs, Some(&error)));
+                        }
+                        if status.satisfies_commitment(CommitmentConfig::confirmed()) {
+                            return TxStatus::Confirmed;
+                        }
+                        // Processed but not confirmed yet
+                    }
+                    None => {
+                        if height.is_ok_and(|height| height > last_valid_height) {
+                            return TxStatus::Expired;
+                        }
+                    }
+                },
+                Err(e) => warn!("Status check for {} failed: {}", signature, e),
+            }
+        }
+
+        TxStatus::Unknown
+    }
+
+    /// Log messages of a landed transaction, empty if they can't be fetched
+    async fn logs(&self, signature: Signature) -> Vec<String> {
+        let config = RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::Json),
+            commitment: Some(CommitmentConfig::confirmed()),
+            max_supported_transaction_version: Some(0),
+        };
+        self.client
+            .rpc()
+            .get_transaction_with_config(&signature, config)
+            .await
+            .ok()
+            .and_then(|tx| tx.transaction.meta)
+            .and_then(|meta| Option::<Vec<String>>::from(meta.log_messages))
+            .unwrap_or_default()
+    }
+}
+
+/// Whether the RPC node answered a send with an error, so the transaction was
+/// never forwarded. Timeouts and dropped connections leave that unknown.
+fn rejected(error: &ClientError) -> bool {
+    matches!(
+        error.kind(),
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { .. })
+            | ClientErrorKind::SigningError(_)
+    )
+}
+
+/// Whether an attempt may have landed and paid its fees: confirmed, failed
+/// on-chain or unknown, rather than rejected by the RPC node or expired
+fn landed(signature: Option<Signature>, status: &TxStatus) -> bool {
+    signature.is_some() && *status != TxStatus::Expired
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use solana_rpc_client_api::request::RpcResponseErrorData;
+
+    use super::*;
+
+    fn logs(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn test_decode_drift_errors() {
+        let error = TxError::decode(
+            &logs(&[
+                "Program dRiftyHA39MWEi3m9aunc5MzRF1JYuBsbn6VPcn33UH invoke [1]",
+                "Program log: Instruction: CancelOrdersByIds",
+                "Program log: AnchorError occurred. Error Code: OrderDoesNotExist. Error Number: 6087. Error Message: Order does not exist.",
+                "Program dRiftyHA39MWEi3m9aunc5MzRF1JYuBsbn6VPcn33UH failed: custom program error: 0x17c7",
+            ]),
+            Some(&TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(6087),
+            )),
+        );
+        assert_eq!(error.name.as_deref(), Some("OrderDoesNotExist"));
+        assert_eq!(error.code, Some(6087));
+        assert_eq!(
+            error.to_string(),
+            "OrderDoesNotExist (6087): Order does not exist"
+        );
+
+        // No anchor log, only the custom error code
+        let error = TxError::decode(
+            &[],
+            Some(&TransactionError::InstructionError(
+                2,
+                InstructionError::Custom(6003),
+            )),
+        );
+        assert_eq!(error.name, None);
+        assert_eq!(error.code, Some(6003));
+
+        // Preflight failures carry the logs in the error text
+        let error = TxError::from_send_error(
+            &"RPC response error -32002: Transaction simulation failed: Error processing Instruction 1: custom program error: 0x1773; 3 log messages:\n  Program log: AnchorError thrown in programs/drift/src/controller/orders.rs:123. Error Code: InsufficientCollateral. Error Number: 6003. Error Message: Insufficient collateral.",
+        );
+        assert_eq!(error.name.as_deref(), Some("InsufficientCollateral"));
+        assert_eq!(error.code, Some(6003));
+
+        let error = TxError::from_send_error(&"error sending request for url");
+        assert_eq!(error.code, None);
+        assert_eq!(error.message, "error sending request for url");
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        let config = TxConfig {
+            max_retries: 2,
+            retry_backoff_ms: 100,
+            retry_errors: vec!["OracleNotFound".to_string()],
+            ..Default::default()
+        };
+        let program_error = |name: &str| {
+            TxStatus::Failed(TxError {
+                name: Some(name.to_string()),
+                code: Some(6000),
+                message: String::new(),
+            })
+        };
+
+        assert_eq!(config.retry_delay(1, &TxStatus::Confirmed), None);
+        // Dropped transactions back off and give up after max_retries re-sends
+        assert_eq!(
+            config.retry_delay(1, &TxStatus::Expired),
+            Some(Duration::from_millis(100))
+        );
+        assert_eq!(
+            config.retry_delay(2, &TxStatus::Expired),
+            Some(Duration::from_millis(200))
+        );
+        assert_eq!(config.retry_delay(3, &TxStatus::Expired), None);
+
+        // RPC errors are re-sent, program errors only when listed
+        let rpc_error = TxStatus::Failed(TxError {
+            name: None,
+            code: None,
+            message: "timeout".to_string(),
+        });
+        assert!(config.retry_delay(1, &rpc_error).is_some());
+        assert!(config
+            .retry_delay(1, &program_error("OracleNotFound"))
+            .is_some());
+        assert_eq!(
+            config.retry_delay(1, &program_error("InsufficientCollateral")),
+            None
+        );
+
+        // Sent but never seen expiring, it may still land
+        assert_eq!(config.retry_delay(1, &TxStatus::Unknown), None);
+        assert!(landed(Some(Signature::default()), &TxStatus::Unknown));
+    }
+
+    #[test]
+    fn test_ambiguous_send_errors() {
+        // Answered by the node, e.g. a failed preflight: never forwarded
+        let response = ClientError::from(RpcError::RpcResponseError {
+            code: -32002,
+            message: "Transaction simulation failed".to_string(),
+            data: RpcResponseErrorData::Empty,
+        });
+        assert!(rejected(&response));
+
+        // The node may have taken it before the connection dropped
+        let timeout = ClientError::from(io::Error::new(io::ErrorKind::TimedOut, "timed out"));
+        assert!(!rejected(&timeout));
+        let unreadable = ClientError::from(RpcError::ParseError("Signature".to_string()));
+        assert!(!rejected(&unreadable));
+    }
+}
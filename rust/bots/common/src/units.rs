@@ -0,0 +1,249 @@
+//! Fixed-point protocol units: prices, base amounts and quote amounts as
+//! distinct types over the integers Drift uses, so passing one where another
+//! is expected doesn't compile. Arithmetic is checked and conversions from
+//! floats are range checked instead of saturating.
+
+use std::fmt;
+
+use drift_rs::math::constants::{BASE_PRECISION, PRICE_PRECISION, QUOTE_PRECISION};
+
+macro_rules! fixed_point {
+    ($(#[$doc:meta])* $name:ident, $precision:expr) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name(i64);
+
+        impl $name {
+            pub const ZERO: Self = Self(0);
+            const PRECISION: i64 = $precision as i64;
+
+            /// From protocol units
+            pub const fn from_raw(raw: i64) -> Self {
+                Self(raw)
+            }
+
+            /// Protocol units
+            pub const fn raw(self) -> i64 {
+                self.0
+            }
+
+            /// From unsigned protocol units, `None` past `i64::MAX`
+            pub fn from_unsigned(raw: u64) -> Option<Self> {
+                i64::try_from(raw).ok().map(Self)
+            }
+
+            /// Unsigned protocol units for order fields, `None` if negative
+            pub fn to_unsigned(self) -> Option<u64> {
+                u64::try_from(self.0).ok()
+            }
+
+            /// From a decimal value, rounded to the nearest unit. `None` if
+            /// it isn't finite or doesn't fit.
+            pub fn from_f64(value: f64) -> Option<Self> {
+                let raw = (value * Self::PRECISION as f64).round();
+                // i64::MAX as f64 rounds up to 2^63, which doesn't fit
+                (raw.is_finite() && raw >= i64::MIN as f64 && raw < i64::MAX as f64)
+                    .then_some(Self(raw as i64))
+            }
+
+            /// Decimal value, for quote models and metrics
+            pub fn to_f64(self) -> f64 {
+                self.0 as f64 / Self::PRECISION as f64
+            }
+
+            pub fn checked_add(self, other: Self) -> Option<Self> {
+                self.0.checked_add(other.0).map(Self)
+            }
+
+            pub fn checked_sub(self, other: Self) -> Option<Self> {
+                self.0.checked_sub(other.0).map(Self)
+            }
+
+            pub fn checked_neg(self) -> Option<Self> {
+                self.0.checked_neg().map(Self)
+            }
+
+            pub fn checked_abs(self) -> Option<Self> {
+                self.0.checked_abs().map(Self)
+            }
+
+            pub fn abs_diff(self, other: Self) -> u64 {
+                self.0.abs_diff(other.0)
+            }
+
+            pub fn is_zero(self) -> bool {
+                self.0 == 0
+            }
+
+            pub fn is_negative(self) -> bool {
+                self.0 < 0
+            }
+
+            /// Round to a multiple of `increment`, up or down. `None` for a
+            /// non-positive increment or on overflow.
+            pub fn checked_round_to(self, increment: Self, up: bool) -> Option<Self> {
+                if increment.0 <= 0 {
+                    return None;
+                }
+                let down = self.0.div_euclid(increment.0).checked_mul(increment.0)?;
+                if up && down != self.0 {
+                    down.checked_add(increment.0).map(Self)
+                } else {
+                    Some(Self(down))
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let precision = Self::PRECISION.unsigned_abs();
+                let abs = self.0.unsigned_abs();
+                write!(
+                    f,
+                    "{}{}.{:0width$}",
+                    if self.0 < 0 { "-" } else { "" },
+                    abs / precision,
+                    abs % precision,
+                    width = precision.ilog10() as usize
+                )
+            }
+        }
+    };
+}
+
+fixed_point!(
+    /// Price or price offset (PRICE_PRECISION)
+    Price,
+    PRICE_PRECISION
+);
+
+fixed_point!(
+    /// Base asset amount (BASE_PRECISION), positive = long for positions
+    BaseAmount,
+    BASE_PRECISION
+);
+
+fixed_point!(
+    /// Quote asset amount (QUOTE_PRECISION)
+    QuoteAmount,
+    QUOTE_PRECISION
+);
+
+impl Price {
+    /// From an order's oracle price offset
+    pub fn from_offset(offset: i32) -> Self {
+        Self(offset as i64)
+    }
+
+    /// As an order's oracle price offset, `None` if it doesn't fit
+    pub fn to_offset(self) -> Option<i32> {
+        i32::try_from(self.0).ok()
+    }
+
+    /// Quote value of `base` at this price
+    pub fn checked_notional(self, base: BaseAmount) -> Option<QuoteAmount> {
+        let notional = (self.0 as i128 * base.0 as i128)
+            .checked_mul(QuoteAmount::PRECISION as i128)?
+            / (Self::PRECISION as i128 * BaseAmount::PRECISION as i128);
+        i64::try_from(notional).ok().map(QuoteAmount)
+    }
+}
+
+/// Basis points. A ratio rather than a protocol unit, so it stays floating
+/// point; applying it to a price rounds to a whole price unit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+pub struct Bps(pub f64);
+
+impl Bps {
+    /// `self` of `price`, rounded to the nearest unit
+    pub fn of(self, price: Price) -> Option<Price> {
+        let raw = (price.0 as f64 * self.0 / 10_000.0).round();
+        (raw.is_finite() && raw >= i64::MIN as f64 && raw < i64::MAX as f64)
+            .then_some(Price(raw as i64))
+    }
+
+    /// Move from `from` to `to` relative to `from`, `None` from a zero price
+    pub fn change(from: Price, to: Price) -> Option<Bps> {
+        if from.is_zero() {
+            return None;
+        }
+        Some(Bps(
+            (to.0 as f64 - from.0 as f64) * 10_000.0 / from.0.unsigned_abs() as f64
+        ))
+    }
+}
+
+impl fmt::Display for Bps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.2} bps", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions_and_display() {
+        let price = Price::from_f64(101.25).unwrap();
+        assert_eq!(price.raw(), 101_250_000);
+        assert_eq!(price.to_string(), "101.250000");
+        assert_eq!(Price::from_raw(-1).to_string(), "-0.000001");
+        assert_eq!(BaseAmount::from_f64(0.001).unwrap().raw(), 1_000_000);
+        assert_eq!(
+            BaseAmount::from_raw(-1_500_000_000).to_string(),
+            "-1.500000000"
+        );
+        assert_eq!(QuoteAmount::from_raw(2_500_000).to_f64(), 2.5);
+
+        // Floats that don't fit are rejected instead of saturating
+        assert_eq!(BaseAmount::from_f64(f64::NAN), None);
+        assert_eq!(BaseAmount::from_f64(1e12), None);
+        assert_eq!(BaseAmount::from_raw(-1).to_unsigned(), None);
+        assert_eq!(BaseAmount::from_unsigned(u64::MAX), None);
+        assert_eq!(Price::from_raw(i32::MAX as i64 + 1).to_offset(), None);
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        let price = Price::from_raw(100_000_000);
+        assert_eq!(Price::from_raw(i64::MAX).checked_add(price), None);
+        assert_eq!(Price::from_raw(i64::MIN).checked_abs(), None);
+        assert_eq!(
+            price.checked_sub(Price::from_raw(250_000)),
+            Some(Price::from_raw(99_750_000))
+        );
+
+        // 0.5 base at $100
+        let notional = price.checked_notional(BaseAmount::from_raw(-500_000_000));
+        assert_eq!(notional, Some(QuoteAmount::from_raw(-50_000_000)));
+        assert_eq!(
+            Price::from_raw(i64::MAX).checked_notional(BaseAmount::from_raw(i64::MAX)),
+            None
+        );
+
+        let tick = Price::from_raw(100);
+        assert_eq!(
+            Price::from_raw(-12_340).checked_round_to(tick, false),
+            Some(Price::from_raw(-12_400))
+        );
+        assert_eq!(
+            Price::from_raw(-12_340).checked_round_to(tick, true),
+            Some(Price::from_raw(-12_300))
+        );
+        assert_eq!(price.checked_round_to(Price::ZERO, true), None);
+    }
+
+    #[test]
+    fn test_bps() {
+        let price = Price::from_raw(100_000_000);
+        assert_eq!(Bps(50.0).of(price), Some(Price::from_raw(500_000)));
+        assert_eq!(Bps(-1.0).of(price), Some(Price::from_raw(-10_000)));
+        assert_eq!(Bps(f64::INFINITY).of(price), None);
+
+        let change = Bps::change(price, Price::from_raw(100_100_000)).unwrap();
+        assert!((change.0 - 10.0).abs() < 1e-9);
+        assert_eq!(change.to_string(), "10.00 bps");
+        assert_eq!(Bps::change(Price::ZERO, price), None);
+    }
+}
@@ -0,0 +1,58 @@
+//! Config checks shared by the bots' `validate` methods, so the same mistake
+//! is reported the same way everywhere.
+
+use anyhow::{bail, Result};
+
+/// Fail unless `value` is above zero
+pub fn ensure_positive(name: &str, value: f64) -> Result<()> {
+    if value > 0.0 {
+        Ok(())
+    } else {
+        bail!("{} must be > 0, got {}", name, value)
+    }
+}
+
+/// Fail unless `value` is zero or above
+pub fn ensure_non_negative(name: &str, value: f64) -> Result<()> {
+    if value >= 0.0 {
+        Ok(())
+    } else {
+        bail!("{} must be >= 0, got {}", name, value)
+    }
+}
+
+/// Fail unless `min` is non-negative and at most `max`
+pub fn check_range(name: &str, min: f64, max: f64) -> Result<()> {
+    ensure_non_negative(name, min)?;
+    if min > max {
+        bail!("{} {} exceeds the max {}", name, min, max);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checks() {
+        assert!(ensure_positive("spread_multiplier", 1.0).is_ok());
+        assert_eq!(
+            ensure_positive("spread_multiplier", 0.0)
+                .unwrap_err()
+                .to_string(),
+            "spread_multiplier must be > 0, got 0"
+        );
+        assert!(ensure_positive("gamma", f64::NAN).is_err());
+        assert!(ensure_non_negative("skew_bps", 0.0).is_ok());
+
+        assert!(check_range("start_offset_bps", 10.0, 50.0).is_ok());
+        assert_eq!(
+            check_range("start_offset_bps", 60.0, 50.0)
+                .unwrap_err()
+                .to_string(),
+            "start_offset_bps 60 exceeds the max 50"
+        );
+        assert!(check_range("start_offset_bps", -1.0, 50.0).is_err());
+    }
+}
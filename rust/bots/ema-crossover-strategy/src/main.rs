@@ -11,20 +11,29 @@
 //!
 //! ## Requirements
 //! Set RPC_ENDPOINT and PRIVATE_KEY environment variables.
-//! Press Ctrl+C for graceful shutdown.
+//! Set `metrics_addr` to serve Prometheus metrics at `/metrics`.
+//! Trades are confirmed on-chain (and re-sent per `tx`) before the signal advances.
+//! Press Ctrl+C or send SIGTERM/SIGHUP for graceful shutdown. Fatal errors and
+//! panics shut down the same way.
 
+mod metrics;
 mod prices;
 mod signal;
 mod trading;
 
 use anyhow::Result;
+use bot_common::fees::{FeeMode, PriorityFeeConfig};
+use bot_common::shutdown::{ShutdownConfig, ShutdownPolicy};
+use bot_common::supervisor::{self, Exit};
+use bot_common::tx::TxConfig;
 use dotenv::dotenv;
 use log::info;
+use std::process::ExitCode;
 use std::time::Duration;
 use trading::{BotConfig, EmaBot};
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> Result<ExitCode> {
     dotenv().ok();
     env_logger::init();
 
@@ -43,20 +52,63 @@ async fn main() -> Result<()> {
         price_history_limit: 100,                 // Initial history size
         price_update_limit: 1,                    // Single price per update
         authority: None,
-        subaccount_id: 0, // Default subaccount
+        subaccount_id: 0,   // Default subaccount
+        metrics_addr: None, // e.g. Some("127.0.0.1:9091".parse()?)
+        tx: TxConfig {
+            max_retries: 2,           // Re-sends with a fresh blockhash
+            retry_backoff_ms: 250,    // Doubled for each further re-send
+            poll_interval_ms: 400,    // Signature status checks
+            retry_errors: Vec::new(), // Drift errors worth re-sending
+            preflight: true,          // Simulate before sending
+            priority_fee: PriorityFeeConfig {
+                compute_unit_limit: 400_000, // Compute units per transaction
+                // Recent 75th percentile, x1.5 on every re-send
+                mode: FeeMode::Adaptive {
+                    percentile: 75.0,
+                    multiplier: 1.5,
+                },
+                min_micro_lamports: 1_000, // Price bounds (micro-lamports per CU)
+                max_micro_lamports: 200_000,
+                max_lamports_per_hour: Some(20_000_000), // 0.02 SOL
+            },
+        },
+        shutdown: ShutdownConfig {
+            // Reduce-only IOC within 50 bps of the oracle, see `ShutdownPolicy`
+            policy: ShutdownPolicy::Limit {
+                max_slippage_bps: 50.0,
+            },
+            auction_timeout_secs: 30, // Auction orders rest this long
+            confirm_timeout_secs: 10, // Wait for the final state on-chain
+            timeout_secs: 60,         // Hard limit on the whole cleanup
+        },
     };
 
+    let metrics_addr = config.metrics_addr;
     let mut bot = EmaBot::new(config).await?;
 
-    tokio::select! {
-        result = bot.start() => {
-            result?;
-        }
-        _ = tokio::signal::ctrl_c() => {
-            info!("Received Ctrl+C, shutting down...");
-            bot.stop().await;
-        }
+    if let Some(addr) = metrics_addr {
+        let metrics = bot.metrics();
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(addr, metrics).await {
+                log::error!("Metrics server stopped: {}", e);
+            }
+        });
     }
 
-    Ok(())
+    // Trade until a signal, a fatal error or a panic, then always run the cancel path
+    let exit = supervisor::run(bot.start(), &supervisor::STOP_SIGNALS).await?;
+    match &exit {
+        Exit::Signal(_) | Exit::Finished => info!("Stopping: {}", exit),
+        _ => log::error!("Stopping: {}", exit),
+    }
+
+    let timeout = bot.shutdown_timeout();
+    let cleanup = supervisor::cleanup(bot.stop(), timeout).await;
+    if cleanup.is_confirmed() {
+        info!("Shutdown completed: {}", cleanup);
+    } else {
+        log::error!("Shutdown incomplete: {}", cleanup);
+    }
+
+    Ok(ExitCode::from(supervisor::exit_code(&exit, &cleanup)))
 }
@@ -0,0 +1,151 @@
+use crate::signal::Signal;
+
+use anyhow::Result;
+use axum::{Router, http::StatusCode, http::header, routing::get};
+use log::{error, info};
+use prometheus::{Gauge, IntCounter, IntCounterVec, Opts, Registry, TextEncoder, core::Collector};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// Prometheus metrics exported by the bot.
+pub struct Metrics {
+    registry: Registry,
+    // Perp position in the traded market (base units)
+    pub position: Gauge,
+    // Settled plus unsettled realized PnL of the position (USD)
+    pub realized_pnl: Gauge,
+    // Unrealized PnL at the oracle price (USD)
+    pub unrealized_pnl: Gauge,
+    pub orders_placed: IntCounter,
+    pub orders_cancelled: IntCounter,
+    // Errors by the operation that failed
+    pub errors: IntCounterVec,
+    pub fast_ema: Gauge,
+    pub slow_ema: Gauge,
+    // 1 long, -1 short, 0 neutral
+    pub signal: Gauge,
+    // Sent transactions by final status
+    pub transactions: IntCounterVec,
+    // Priority fees paid by attempts that landed (lamports)
+    pub priority_fees: IntCounter,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self> {
+        let registry = Registry::new_custom(Some("ema_bot".to_string()), None)?;
+
+        Ok(Self {
+            position: register(&registry, Gauge::new("position", "Perp position (base)")?)?,
+            realized_pnl: register(
+                &registry,
+                Gauge::new("realized_pnl_usd", "Realized PnL of the position")?,
+            )?,
+            unrealized_pnl: register(
+                &registry,
+                Gauge::new("unrealized_pnl_usd", "Unrealized PnL at the oracle price")?,
+            )?,
+            orders_placed: register(
+                &registry,
+                IntCounter::new("orders_placed_total", "Orders placed")?,
+            )?,
+            orders_cancelled: register(
+                &registry,
+                IntCounter::new("orders_cancelled_total", "Orders cancelled")?,
+            )?,
+            errors: register(
+                &registry,
+                IntCounterVec::new(Opts::new("errors_total", "Errors by kind"), &["kind"])?,
+            )?,
+            fast_ema: register(&registry, Gauge::new("fast_ema", "Fast EMA")?)?,
+            slow_ema: register(&registry, Gauge::new("slow_ema", "Slow EMA")?)?,
+            signal: register(
+                &registry,
+                Gauge::new("signal", "Current signal (1 long, -1 short, 0 neutral)")?,
+            )?,
+            transactions: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new("transactions_total", "Transactions by final status"),
+                    &["status"],
+                )?,
+            )?,
+            priority_fees: register(
+                &registry,
+                IntCounter::new(
+                    "priority_fees_lamports_total",
+                    "Priority fees paid (lamports)",
+                )?,
+            )?,
+            registry,
+        })
+    }
+
+    /// Counts an error of the given kind.
+    pub fn error(&self, kind: &str) {
+        self.errors.with_label_values(&[kind]).inc();
+    }
+
+    /// Records the EMA values and signal after an update.
+    pub fn set_signal(&self, fast: f64, slow: f64, signal: Signal) {
+        self.fast_ema.set(fast);
+        self.slow_ema.set(slow);
+        self.signal.set(match signal {
+            Signal::Long => 1.0,
+            Signal::Short => -1.0,
+            Signal::Neutral => 0.0,
+        });
+    }
+
+    /// Renders all metrics in the Prometheus text format.
+    pub fn encode(&self) -> Result<String> {
+        Ok(TextEncoder::new().encode_to_string(&self.registry.gather())?)
+    }
+}
+
+fn register<M: Collector + Clone + 'static>(registry: &Registry, metric: M) -> Result<M> {
+    registry.register(Box::new(metric.clone()))?;
+    Ok(metric)
+}
+
+/// Serves `GET /metrics` until the process exits.
+pub async fn serve(addr: SocketAddr, metrics: Arc<Metrics>) -> Result<()> {
+    let app = Router::new().route(
+        "/metrics",
+        get(move || async move {
+            match metrics.encode() {
+                Ok(body) => Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)),
+                Err(e) => {
+                    error!("Failed to encode metrics: {}", e);
+                    Err(StatusCode::INTERNAL_SERVER_ERROR)
+                }
+            }
+        }),
+    );
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!("Serving metrics at http://{}/metrics", addr);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let metrics = Metrics::new().unwrap();
+        metrics.set_signal(101.5, 100.0, Signal::Short);
+        metrics.orders_placed.inc_by(2);
+        metrics.error("cycle");
+        metrics.transactions.with_label_values(&["expired"]).inc();
+
+        let text = metrics.encode().unwrap();
+        assert!(text.contains("ema_bot_fast_ema 101.5"));
+        assert!(text.contains("ema_bot_slow_ema 100"));
+        assert!(text.contains("ema_bot_signal -1"));
+        assert!(text.contains("ema_bot_orders_placed_total 2"));
+        assert!(text.contains("ema_bot_errors_total{kind=\"cycle\"} 1"));
+        assert!(text.contains("ema_bot_transactions_total{status=\"expired\"} 1"));
+    }
+}
@@ -1,17 +1,25 @@
+use crate::metrics::Metrics;
 use crate::prices::fetch_binance_prices;
 use crate::signal::{EMA, Signal};
 
-use anyhow::Result;
-use drift_rs::types::{MarketType, OrderType, PerpPosition, PositionDirection};
+use anyhow::{Context as _, Result, anyhow};
+use bot_common::shutdown::{self, PositionCloser, SHUTDOWN_POLL, ShutdownConfig, ShutdownState};
+use bot_common::tx::{TxConfig, TxSender};
+use bot_common::units::{BaseAmount, Price, QuoteAmount};
+use drift_rs::types::{
+    MarketId, MarketType, OrderStatus, OrderType, PerpPosition, PositionDirection,
+};
 use drift_rs::{
-    DriftClient, Pubkey, RpcClient, Wallet,
+    DriftClient, Pubkey, RpcClient, TransactionBuilder, Wallet,
     types::{Context, OrderParams},
 };
 use log::{error, info};
-use solana_sdk::signature::Signature;
+use solana_sdk::{message::VersionedMessage, signature::Signature};
 use std::env;
+use std::net::SocketAddr;
 use std::str::FromStr;
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// Bot configuration parameters.
 #[derive(Debug, Clone)]
@@ -29,6 +37,17 @@ pub struct BotConfig {
     pub price_update_limit: u32,
     pub authority: Option<String>,
     pub subaccount_id: u16,
+    pub metrics_addr: Option<SocketAddr>,
+    pub tx: TxConfig,
+    pub shutdown: ShutdownConfig,
+}
+
+impl BotConfig {
+    /// Checks the transaction and shutdown settings before connecting.
+    pub fn validate(&self) -> Result<()> {
+        self.tx.validate().context("tx")?;
+        self.shutdown.validate().context("shutdown")
+    }
 }
 
 /// Trading bot that executes EMA crossover strategy.
@@ -39,12 +58,15 @@ pub struct EmaBot {
     current_signal: Signal,
     is_running: bool,
     is_processing: bool,
+    metrics: Arc<Metrics>,
+    tx_sender: TxSender,
 }
 
 impl EmaBot {
     /// Creates new bot instance and initializes EMA with historical data.
     pub async fn new(config: BotConfig) -> Result<Self> {
         info!("Initializing bot...");
+        config.validate()?;
 
         let client = Self::init_drift_client().await?;
         let ema = Self::init_ema(&config).await?;
@@ -53,12 +75,14 @@ impl EmaBot {
         info!("Bot initialized with initial signal: {:?}", initial_signal);
 
         Ok(Self {
+            tx_sender: TxSender::new(client.clone(), config.tx.clone()),
             client,
             ema,
             config,
             current_signal: initial_signal,
             is_running: false,
             is_processing: false,
+            metrics: Arc::new(Metrics::new()?),
         })
     }
 
@@ -111,6 +135,11 @@ impl EmaBot {
         while self.is_running {
             if let Err(e) = self.process_cycle().await {
                 error!("Cycle failed: {}", e);
+                self.metrics.error("cycle");
+            }
+            if let Err(e) = self.update_position_metrics().await {
+                error!("Failed to update position metrics: {}", e);
+                self.metrics.error("position");
             }
             tokio::time::sleep(self.config.update_interval).await;
         }
@@ -119,20 +148,50 @@ impl EmaBot {
         Ok(())
     }
 
-    /// Stops bot and closes all positions.
-    pub async fn stop(&mut self) {
+    /// Stops bot, cancels orders and handles positions under `config.shutdown`.
+    /// Returns whether the final state was confirmed on-chain.
+    pub async fn stop(&mut self) -> Result<bool> {
         info!("Stopping bot...");
 
-        if let Err(e) = self.close_positions().await {
-            error!("Failed to close positions during shutdown: {}", e);
+        let confirmed = self.shutdown().await;
+        if confirmed.is_err() {
+            self.metrics.error("shutdown");
         }
 
         if let Err(e) = self.client.unsubscribe().await {
             error!("Failed to unsubscribe from Drift client: {}", e);
+            self.metrics.error("unsubscribe");
         }
 
         self.is_running = false;
         info!("Bot stopped");
+        confirmed
+    }
+
+    /// Cancels orders, applies the shutdown policy and polls the user account
+    /// until the final state matches it or `confirm_timeout_secs` passes.
+    async fn shutdown(&mut self) -> Result<bool> {
+        let config = self.config.shutdown.clone();
+        info!("Shutting down: {}", config.policy);
+        self.cancel_orders().await?;
+        shutdown::close_positions(self, config.policy, config.auction_timeout_secs).await?;
+
+        let deadline = Instant::now() + Duration::from_secs(config.confirm_timeout_secs);
+        loop {
+            let state = self.final_state().await?;
+            if state.is_complete(&config.policy) {
+                info!("Shutdown state confirmed: {}", state);
+                return Ok(true);
+            }
+            if Instant::now() >= deadline {
+                error!(
+                    "Shutdown state not confirmed after {}s: {}",
+                    config.confirm_timeout_secs, state
+                );
+                return Ok(false);
+            }
+            tokio::time::sleep(SHUTDOWN_POLL).await;
+        }
     }
 
     /// Processes single trading cycle: updates signal and executes trades.
@@ -142,7 +201,14 @@ impl EmaBot {
         }
 
         self.is_processing = true;
+        let result = self.apply_signal().await;
+        self.is_processing = false;
+        result
+    }
 
+    /// Updates the signal and trades on a change. The current signal only
+    /// advances once the trade is confirmed, so a failed trade is retried next cycle.
+    async fn apply_signal(&mut self) -> Result<()> {
         let new_signal = self.update_signal().await?;
 
         if new_signal != self.current_signal {
@@ -154,7 +220,6 @@ impl EmaBot {
             self.current_signal = new_signal;
         }
 
-        self.is_processing = false;
         Ok(())
     }
 
@@ -170,6 +235,8 @@ impl EmaBot {
         let current_price = prices[0];
         self.ema.update(current_price)?;
         let signal = self.ema.crossover_signal();
+        self.metrics
+            .set_signal(self.ema.current_fast, self.ema.current_slow, signal);
 
         info!(
             "Price: ${:.2}, Fast EMA: {:.2}, Slow EMA: {:.2}, Signal: {:?}",
@@ -262,52 +329,96 @@ impl EmaBot {
             }
         }
 
+        let order_count = reduce_orders.len() as u64;
         let tx = self
-            .client
-            .init_tx(&subaccount, self.is_delegated())
+            .init_tx(&subaccount)
             .await?
             .place_orders(reduce_orders)
             .build();
 
-        let sig = self.client.sign_and_send(tx).await?;
+        let sig = self.send_tx(tx).await?;
+        self.metrics.orders_placed.inc_by(order_count);
         info!("Flattened all positions: {}", sig);
 
         Ok(sig)
     }
 
+    /// Cancels all open orders. A failed cancel is logged so closing can go ahead.
+    async fn cancel_orders(&self) -> Result<()> {
+        let subaccount = self.get_subaccount();
+        let tx = self.init_tx(&subaccount).await?.cancel_all_orders().build();
+
+        match self.send_tx(tx).await {
+            Ok(sig) => info!("Cancelled all orders: {}", sig),
+            Err(e) => {
+                error!("Failed to cancel orders: {}", e);
+                self.metrics.error("cancel");
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns open orders and positions left in the user account.
+    async fn final_state(&self) -> Result<ShutdownState> {
+        let subaccount = self.get_subaccount();
+        let user_account = self.client.get_user_account(&subaccount).await?;
+
+        Ok(ShutdownState {
+            open_orders: user_account
+                .orders
+                .iter()
+                .filter(|o| o.status == OrderStatus::Open)
+                .count(),
+            positions: user_account
+                .perp_positions
+                .iter()
+                .filter(|pos| pos.base_asset_amount != 0)
+                .map(|pos| {
+                    (
+                        format!("market {}", pos.market_index),
+                        BaseAmount::from_raw(pos.base_asset_amount).to_f64(),
+                    )
+                })
+                .collect(),
+        })
+    }
+
     /// Places market order in specified direction.
     async fn update(&mut self, direction: PositionDirection) -> Result<Signature> {
         let subaccount = self.get_subaccount();
+        let base_asset_amount = self.order_size()?;
 
         let order_params = OrderParams {
             order_type: OrderType::Market,
             market_type: MarketType::Perp,
             direction,
-            base_asset_amount: (self.config.order_size * 1e9) as u64,
+            base_asset_amount,
             market_index: self.config.market_index,
             ..Default::default()
         };
 
         let tx = self
-            .client
-            .init_tx(&subaccount, self.is_delegated())
+            .init_tx(&subaccount)
             .await?
             .place_orders(vec![order_params])
             .build();
 
-        self.client.sign_and_send(tx).await.map_err(Into::into)
+        let sig = self.send_tx(tx).await?;
+        self.metrics.orders_placed.inc();
+        Ok(sig)
     }
 
     /// Closes existing position and opens new one atomically.
     async fn close_and_update(&mut self, direction: PositionDirection) -> Result<Signature> {
         let subaccount = self.get_subaccount();
+        let base_asset_amount = self.order_size()?;
 
         // Close existing position
         let order_params = OrderParams {
             order_type: OrderType::Market,
             market_type: MarketType::Perp,
             direction,
-            base_asset_amount: (self.config.order_size * 1e9) as u64,
+            base_asset_amount,
             market_index: self.config.market_index,
             reduce_only: true,
             ..Default::default()
@@ -318,20 +429,84 @@ impl EmaBot {
             order_type: OrderType::Market,
             market_type: MarketType::Perp,
             direction,
-            base_asset_amount: (self.config.order_size * 1e9) as u64,
+            base_asset_amount,
             market_index: self.config.market_index,
             reduce_only: false,
             ..Default::default()
         };
 
         let tx = self
-            .client
-            .init_tx(&subaccount, self.is_delegated())
+            .init_tx(&subaccount)
             .await?
             .place_orders(vec![order_params, new_order_params])
             .build();
 
-        self.client.sign_and_send(tx).await.map_err(Into::into)
+        let sig = self.send_tx(tx).await?;
+        self.metrics.orders_placed.inc_by(2);
+        Ok(sig)
+    }
+
+    /// Returns the configured order size in order units.
+    fn order_size(&self) -> Result<u64> {
+        BaseAmount::from_f64(self.config.order_size)
+            .and_then(BaseAmount::to_unsigned)
+            .ok_or_else(|| anyhow!("Order size {} out of range", self.config.order_size))
+    }
+
+    /// Starts a transaction with compute budget instructions. The price is a
+    /// placeholder the sender re-prices on every attempt, non-zero so the
+    /// price instruction is included.
+    async fn init_tx(&self, subaccount: &Pubkey) -> Result<TransactionBuilder<'_>> {
+        let fees = &self.config.tx.priority_fee;
+        Ok(self
+            .client
+            .init_tx(subaccount, self.is_delegated())
+            .await?
+            .with_priority_fee(
+                fees.min_micro_lamports.max(1),
+                Some(fees.compute_unit_limit),
+            ))
+    }
+
+    /// Sends a transaction and waits for it to confirm, re-sending per `config.tx`.
+    async fn send_tx(&self, tx: VersionedMessage) -> Result<Signature> {
+        let outcome = self.tx_sender.send(tx).await;
+        self.metrics
+            .transactions
+            .with_label_values(&[outcome.status.label()])
+            .inc();
+        self.metrics
+            .priority_fees
+            .inc_by(outcome.priority_fee_lamports);
+        info!("Transaction {}", outcome);
+        outcome.into_result()
+    }
+
+    /// Refreshes position and PnL metrics from the user account and oracle price.
+    async fn update_position_metrics(&self) -> Result<()> {
+        let position = self.get_current_position().await?.unwrap_or_default();
+        let oracle_price = Price::from_raw(
+            self.client
+                .oracle_price(MarketId::perp(self.config.market_index))
+                .await?,
+        );
+
+        let base_amount = BaseAmount::from_raw(position.base_asset_amount);
+        let entry_amount = QuoteAmount::from_raw(position.quote_entry_amount);
+        let realized_pnl = QuoteAmount::from_raw(position.settled_pnl)
+            .checked_add(QuoteAmount::from_raw(position.quote_asset_amount))
+            .and_then(|pnl| pnl.checked_sub(entry_amount));
+        let unrealized_pnl = oracle_price
+            .checked_notional(base_amount)
+            .and_then(|value| value.checked_add(entry_amount));
+        let (Some(realized_pnl), Some(unrealized_pnl)) = (realized_pnl, unrealized_pnl) else {
+            anyhow::bail!("Position PnL out of range");
+        };
+
+        self.metrics.position.set(base_amount.to_f64());
+        self.metrics.realized_pnl.set(realized_pnl.to_f64());
+        self.metrics.unrealized_pnl.set(unrealized_pnl.to_f64());
+        Ok(())
     }
 
     fn get_subaccount(&self) -> Pubkey {
@@ -355,7 +530,69 @@ impl EmaBot {
             .cloned())
     }
 
+    async fn get_open_positions(&self) -> Result<Vec<PerpPosition>> {
+        let subaccount = self.get_subaccount();
+        let user_account = self.client.get_user_account(&subaccount).await?;
+
+        Ok(user_account
+            .perp_positions
+            .iter()
+            .filter(|pos| pos.base_asset_amount != 0)
+            .cloned()
+            .collect())
+    }
+
     fn is_delegated(&self) -> bool {
         self.config.authority.is_some()
     }
+
+    /// Returns the limit on the cleanup run by `stop`.
+    pub fn shutdown_timeout(&self) -> Duration {
+        self.config.shutdown.timeout()
+    }
+
+    /// Shared metrics, served by `metrics::serve`.
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+}
+
+impl PositionCloser for EmaBot {
+    async fn open_positions(&mut self) -> Result<Vec<PerpPosition>> {
+        self.get_open_positions().await
+    }
+
+    async fn oracle_price(&mut self, market_index: u16) -> Result<Price> {
+        let price = self
+            .client
+            .oracle_price(MarketId::perp(market_index))
+            .await?;
+        Ok(Price::from_raw(price))
+    }
+
+    async fn place_close_orders(&mut self, orders: Vec<OrderParams>) -> Result<()> {
+        let order_count = orders.len() as u64;
+        let subaccount = self.get_subaccount();
+        let tx = self
+            .init_tx(&subaccount)
+            .await?
+            .place_orders(orders)
+            .build();
+
+        match self.send_tx(tx).await {
+            Ok(sig) => {
+                self.metrics.orders_placed.inc_by(order_count);
+                info!("Sent {} close orders: {}", order_count, sig);
+            }
+            Err(e) => {
+                error!("Failed to send close orders: {}", e);
+                self.metrics.error("close");
+            }
+        }
+        Ok(())
+    }
+
+    async fn cancel_all_orders(&mut self) -> Result<()> {
+        self.cancel_orders().await
+    }
 }
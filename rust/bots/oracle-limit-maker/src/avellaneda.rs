@@ -0,0 +1,233 @@
+//! Avellaneda–Stoikov reservation price quoting.
+//!
+//! Works in relative terms so one set of parameters fits markets at any price:
+//! - reservation price `r = s * (1 - q * γ * σ² * τ)`
+//! - optimal spread `δ = γ * σ² * τ + (2 / γ) * ln(1 + γ / k)`
+//!
+//! where `s` is the oracle price, `q` the inventory in order-size lots, `σ`
+//! the realized volatility per sqrt(second), `τ` the quoting horizon and `k`
+//! the order arrival intensity decay.
+
+use anyhow::{bail, Result};
+use bot_common::validate::{check_range, ensure_positive};
+use log::info;
+use serde::Deserialize;
+
+use crate::quote::{calculate_dynamic_sizing, MarketSnapshot, Quote, QuoteModel};
+
+/// Avellaneda–Stoikov parameters
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AvellanedaStoikovConfig {
+    // Risk aversion (γ)
+    pub risk_aversion: f64,
+    // Quoting horizon T - t (seconds)
+    pub horizon_secs: f64,
+    // Order arrival intensity decay (k), used when not estimated or the book is empty
+    pub intensity: f64,
+    // Estimate k from L2 depth instead of using the fixed value
+    pub estimate_intensity: bool,
+    // Realized volatility window (seconds)
+    pub volatility_window_secs: u64,
+    // Spread bounds (BPS)
+    pub min_spread_bps: f64,
+    pub max_spread_bps: f64,
+}
+
+impl Default for AvellanedaStoikovConfig {
+    fn default() -> Self {
+        Self {
+            risk_aversion: 0.1,
+            horizon_secs: 60.0,
+            intensity: 2_000.0,
+            estimate_intensity: true,
+            volatility_window_secs: 300,
+            min_spread_bps: 1.0,
+            max_spread_bps: 100.0,
+        }
+    }
+}
+
+impl AvellanedaStoikovConfig {
+    /// Check the settings are usable
+    pub fn validate(&self) -> Result<()> {
+        ensure_positive("risk_aversion", self.risk_aversion)?;
+        ensure_positive("intensity", self.intensity)?;
+        ensure_positive("horizon_secs", self.horizon_secs)?;
+        if self.volatility_window_secs == 0 {
+            bail!("volatility_window_secs must be > 0");
+        }
+        check_range("min_spread_bps", self.min_spread_bps, self.max_spread_bps)
+    }
+}
+
+/// Quote model using the Avellaneda–Stoikov reservation price and spread
+#[derive(Debug, Clone)]
+pub struct AvellanedaStoikovModel {
+    config: AvellanedaStoikovConfig,
+}
+
+impl AvellanedaStoikovModel {
+    pub fn new(config: AvellanedaStoikovConfig) -> Self {
+        Self { config }
+    }
+
+    /// Estimate the intensity decay k from L2 depth. If resting size decays
+    /// exponentially with distance from the oracle, the size-weighted mean
+    /// relative distance is 1 / k.
+    fn estimate_intensity(&self, snapshot: &MarketSnapshot) -> Option<f64> {
+        let oracle_price = snapshot.oracle_price;
+        let (weighted_distance, total_size) = snapshot
+            .bids
+            .iter()
+            .chain(snapshot.asks.iter())
+            .fold((0.0, 0.0), |(dist, size), level| {
+                let distance = (level.price - oracle_price).abs() / oracle_price;
+                (dist + distance * level.size, size + level.size)
+            });
+
+        if total_size <= 0.0 || weighted_distance <= 0.0 {
+            return None;
+        }
+        Some(total_size / weighted_distance)
+    }
+}
+
+impl QuoteModel for AvellanedaStoikovModel {
+    fn quote(&mut self, snapshot: &MarketSnapshot) -> Result<Quote> {
+        let config = &self.config;
+        let oracle_price = snapshot.oracle_price;
+        if oracle_price <= 0.0 {
+            bail!("Invalid oracle price {}", oracle_price);
+        }
+
+        let gamma = config.risk_aversion;
+        let sigma = snapshot.volatility.unwrap_or_default();
+        let k = if config.estimate_intensity {
+            self.estimate_intensity(snapshot)
+                .unwrap_or(config.intensity)
+        } else {
+            config.intensity
+        };
+
+        // Inventory in lots of order size
+        let q = snapshot.position / snapshot.config.order_size;
+        let variance = sigma.powi(2) * config.horizon_secs;
+
+        let reservation = oracle_price * (1.0 - q * gamma * variance);
+        let spread = (gamma * variance + (2.0 / gamma) * (1.0 + gamma / k).ln()).clamp(
+            config.min_spread_bps / 10_000.0,
+            config.max_spread_bps / 10_000.0,
+        );
+        let half_spread = oracle_price * spread / 2.0;
+
+        let (bid_size, ask_size) =
+            calculate_dynamic_sizing(snapshot.config.order_size, snapshot.position_ratio());
+
+        info!(
+            "[{}] A-S: q={:.3}, sigma={:.3e}, k={:.1}, reservation ${:.4}, spread {:.2} bps",
+            snapshot.config.symbol,
+            q,
+            sigma,
+            k,
+            reservation,
+            spread * 10_000.0
+        );
+
+        Ok(Quote {
+            bid_price: reservation - half_spread,
+            bid_size,
+            ask_price: reservation + half_spread,
+            ask_size,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MarketConfig;
+    use crate::precision::MarketPrecision;
+    use crate::quote::L2Level;
+
+    fn market_config() -> MarketConfig {
+        MarketConfig {
+            order_size: 1.0,
+            max_position_size: 10.0,
+            ..MarketConfig::test_default()
+        }
+    }
+
+    fn snapshot(config: &MarketConfig, position: f64, volatility: f64) -> MarketSnapshot<'_> {
+        MarketSnapshot {
+            oracle_price: 100.0,
+            bids: vec![],
+            asks: vec![],
+            position,
+            volatility: Some(volatility),
+            precision: MarketPrecision::default(),
+            config,
+        }
+    }
+
+    #[test]
+    fn test_flat_inventory_centers_on_oracle() {
+        let market = market_config();
+        let mut model = AvellanedaStoikovModel::new(AvellanedaStoikovConfig::default());
+        let quote = model.quote(&snapshot(&market, 0.0, 1e-4)).unwrap();
+
+        assert!((quote.mid() - 100.0).abs() < 1e-9);
+        assert!(quote.spread() > 0.0);
+    }
+
+    #[test]
+    fn test_inventory_shifts_reservation() {
+        let market = market_config();
+        let config = AvellanedaStoikovConfig {
+            risk_aversion: 1.0,
+            horizon_secs: 3600.0,
+            ..Default::default()
+        };
+        let mut model = AvellanedaStoikovModel::new(config);
+
+        let long = model.quote(&snapshot(&market, 5.0, 1e-3)).unwrap();
+        let short = model.quote(&snapshot(&market, -5.0, 1e-3)).unwrap();
+
+        assert!(long.mid() < 100.0);
+        assert!(short.mid() > 100.0);
+        assert!(long.bid_size < long.ask_size);
+    }
+
+    #[test]
+    fn test_spread_bounds_and_volatility() {
+        let market = market_config();
+        let mut model = AvellanedaStoikovModel::new(AvellanedaStoikovConfig::default());
+
+        let calm = model.quote(&snapshot(&market, 0.0, 0.0)).unwrap();
+        let volatile = model.quote(&snapshot(&market, 0.0, 1e-2)).unwrap();
+
+        assert!(calm.spread() >= 0.01 - 1e-12);
+        assert!(volatile.spread() > calm.spread());
+        assert!(volatile.spread() <= 1.0 + 1e-12);
+    }
+
+    #[test]
+    fn test_intensity_estimate() {
+        let market = market_config();
+        let model = AvellanedaStoikovModel::new(AvellanedaStoikovConfig::default());
+        let mut snap = snapshot(&market, 0.0, 0.0);
+        assert_eq!(model.estimate_intensity(&snap), None);
+
+        // Mean distance of 10 bps -> k = 1000
+        snap.bids = vec![L2Level {
+            price: 99.9,
+            size: 1.0,
+        }];
+        snap.asks = vec![L2Level {
+            price: 100.1,
+            size: 1.0,
+        }];
+        let k = model.estimate_intensity(&snap).unwrap();
+        assert!((k - 1000.0).abs() < 1e-6);
+    }
+}
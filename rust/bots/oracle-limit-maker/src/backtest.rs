@@ -0,0 +1,862 @@
+//! Offline backtester: replays recorded oracle prices and L2 snapshots through
+//! the live quote pipeline and fills the resulting quotes with a simple model.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use anyhow::{bail, Context, Result};
+use bot_common::units::Price;
+use bot_common::validate::ensure_positive;
+use flate2::read::MultiGzDecoder;
+use serde::{Deserialize, Serialize};
+
+use crate::avellaneda::AvellanedaStoikovModel;
+use crate::config::{BotConfig, MarketConfig};
+use crate::fills::{Fill, FillTracker, Side};
+use crate::oracle_guard::{OracleCheck, OracleGuard, OracleReading};
+use crate::orders::{OrderDiff, QuoteOrder};
+use crate::pipeline::{plan_quotes, requote_trigger};
+use crate::precision::MarketPrecision;
+use crate::quote::{L2Level, MarketSnapshot, QuoteModel, SpreadMultiplierModel};
+use crate::recorder::{recorded_files, BOOK_PREFIX};
+use crate::volatility::RealizedVol;
+
+/// Remaining size below which a simulated order is considered filled
+const SIZE_EPSILON: f64 = 1e-12;
+
+/// Oracle price and book of one market at one point in time, stored as one
+/// JSON object per line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarketRecord {
+    // Unix timestamp (ms)
+    pub ts: u64,
+    // Market symbol
+    pub market: String,
+    // Oracle price (quote units)
+    pub oracle: f64,
+    // Oracle confidence interval (quote units)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
+    // Slot the oracle was read at
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slot: Option<u64>,
+    // Slots since the oracle last published
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delay_slots: Option<u64>,
+    // [price, size] levels, best first
+    pub bids: Vec<(f64, f64)>,
+    pub asks: Vec<(f64, f64)>,
+}
+
+/// How simulated quotes get filled
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FillModel {
+    // Fill against any book liquidity at or through the quote price
+    Touch,
+    // Join the back of the queue at the quote price, fill only once the
+    // book has traded through the size resting ahead
+    QueuePosition,
+    // Fill the whole quote with `probability` per snapshot when the book
+    // touches it, decaying by e every `decay_bps` the book is away from it
+    Probabilistic { probability: f64, decay_bps: f64 },
+}
+
+/// Backtest settings, only used with `--backtest`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BacktestConfig {
+    pub fill_model: FillModel,
+    // Fee on simulated fills (BPS of notional), negative for a rebate
+    pub maker_fee_bps: f64,
+    // Horizons to mark fills to the oracle price at (seconds)
+    pub markout_secs: Vec<u64>,
+    // Seed for the probabilistic fill model
+    pub seed: u64,
+}
+
+impl Default for BacktestConfig {
+    fn default() -> Self {
+        Self {
+            fill_model: FillModel::Touch,
+            maker_fee_bps: 0.0,
+            markout_secs: vec![1, 10, 60],
+            seed: 1,
+        }
+    }
+}
+
+impl BacktestConfig {
+    /// Check the settings are usable
+    pub fn validate(&self) -> Result<()> {
+        if !self.maker_fee_bps.is_finite() {
+            bail!("maker_fee_bps must be a number, got {}", self.maker_fee_bps);
+        }
+        if self.markout_secs.contains(&0) {
+            bail!("markout_secs must be > 0");
+        }
+        if let FillModel::Probabilistic {
+            probability,
+            decay_bps,
+        } = self.fill_model
+        {
+            ensure_positive("probability", probability)?;
+            if probability > 1.0 {
+                bail!("probability must be <= 1, got {}", probability);
+            }
+            ensure_positive("decay_bps", decay_bps)?;
+        }
+        Ok(())
+    }
+}
+
+/// Position and PnL after a fill
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InventoryPoint {
+    pub ts: u64,
+    // Base units, positive = long
+    pub position: f64,
+    // Mark to market PnL at the fill price after fees
+    pub pnl: f64,
+}
+
+/// Backtest results for one market
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BacktestReport {
+    pub market: String,
+    pub snapshots: usize,
+    // Update cycles that passed the requote trigger
+    pub requotes: usize,
+    // Requotes skipped because the quote model returned an error
+    pub quote_errors: usize,
+    pub fills: usize,
+    // Traded notional (quote units)
+    pub volume: f64,
+    // FIFO realized PnL after fees and rebates
+    pub realized_pnl: f64,
+    // Net fees paid, negative when rebates dominate
+    pub fees: f64,
+    // Mark to market PnL at the last oracle price after fees
+    pub total_pnl: f64,
+    // Largest peak to trough fall of total PnL
+    pub max_drawdown: f64,
+    pub final_position: f64,
+    pub min_position: f64,
+    pub max_position: f64,
+    // (horizon seconds, average markout in bps if any fill had data that far ahead),
+    // positive when the oracle moved in the fill's favour
+    pub markouts: Vec<(u64, Option<f64>)>,
+    pub inventory: Vec<InventoryPoint>,
+}
+
+impl BacktestReport {
+    pub fn unrealized_pnl(&self) -> f64 {
+        self.total_pnl - self.realized_pnl
+    }
+}
+
+impl fmt::Display for BacktestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "[{}] {} snapshots, {} requotes ({} quote errors)",
+            self.market, self.snapshots, self.requotes, self.quote_errors
+        )?;
+        writeln!(
+            f,
+            "  PnL: total ${:.4}, realized ${:.4}, unrealized ${:.4}, fees ${:.4}",
+            self.total_pnl,
+            self.realized_pnl,
+            self.unrealized_pnl(),
+            self.fees
+        )?;
+        writeln!(
+            f,
+            "  Fills: {}, volume ${:.2}, max drawdown ${:.4}",
+            self.fills, self.volume, self.max_drawdown
+        )?;
+        writeln!(
+            f,
+            "  Inventory: final {:.6}, min {:.6}, max {:.6}",
+            self.final_position, self.min_position, self.max_position
+        )?;
+        write!(f, "  Markouts:")?;
+        for (secs, bps) in &self.markouts {
+            match bps {
+                Some(bps) => write!(f, " {}s {:+.2} bps", secs, bps)?,
+                None => write!(f, " {}s n/a", secs)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Read market records from a JSONL file (gzipped if it ends in `.gz`) or from
+/// every recorded book file in a directory, sorted by timestamp
+pub fn read_records(path: &Path) -> Result<Vec<MarketRecord>> {
+    let files = if path.is_dir() {
+        recorded_files(path, BOOK_PREFIX)?
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let mut records = Vec::new();
+    for file in &files {
+        read_file(file, &mut records)?;
+    }
+
+    records.sort_by_key(|r| r.ts);
+    Ok(records)
+}
+
+fn read_file(path: &Path, records: &mut Vec<MarketRecord>) -> Result<()> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let reader: Box<dyn Read> = if path.extension().is_some_and(|ext| ext == "gz") {
+        Box::new(MultiGzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+
+    for (i, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line.with_context(|| format!("Failed to read {}", path.display()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: MarketRecord =
+            serde_json::from_str(&line).with_context(|| format!("{}:{}", path.display(), i + 1))?;
+        records.push(record);
+    }
+    Ok(())
+}
+
+/// Write every market's inventory path as CSV
+pub fn write_inventory_csv(path: &Path, reports: &[BacktestReport]) -> Result<()> {
+    let file =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut out = BufWriter::new(file);
+
+    writeln!(out, "ts,market,position,pnl")?;
+    for report in reports {
+        for point in &report.inventory {
+            writeln!(
+                out,
+                "{},{},{},{}",
+                point.ts, report.market, point.position, point.pnl
+            )?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Small xorshift generator so probabilistic runs are reproducible
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // Zero is a fixed point of xorshift
+        Self(seed.max(1))
+    }
+
+    /// Uniform in [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Resting simulated quote
+#[derive(Debug, Clone, Copy)]
+struct SimOrder {
+    order: QuoteOrder,
+    // Remaining size (base units)
+    remaining: f64,
+    // Book size ahead of the order at its price when it was placed
+    queue_ahead: f64,
+}
+
+impl SimOrder {
+    fn price(&self, oracle_price: f64) -> f64 {
+        oracle_price + self.order.oracle_price_offset.to_f64()
+    }
+}
+
+/// One market's quoting state, mirroring `MarketState` in the maker
+struct SimMarket {
+    config: MarketConfig,
+    model: Box<dyn QuoteModel>,
+    volatility: RealizedVol,
+    user_order_id_base: u8,
+    // What the live bot would believe is resting, None after a fill
+    live_orders: Option<Vec<QuoteOrder>>,
+    resting: Vec<SimOrder>,
+    oracle_guard: Option<OracleGuard>,
+    oracle_widen: f64,
+    quotes_pulled: bool,
+    prev_oracle_price: Price,
+    last_update_time: u64,
+    fills: FillTracker,
+    // Base units, positive = long
+    position: f64,
+    // Quote units received (positive) or paid (negative), after fees
+    cash: f64,
+    peak_pnl: f64,
+    // (timestamp, oracle price) per snapshot, for markouts
+    oracle_path: Vec<(u64, f64)>,
+    // (timestamp, side, price) per fill
+    fill_log: Vec<(u64, Side, f64)>,
+    report: BacktestReport,
+}
+
+impl SimMarket {
+    fn new(config: &BotConfig, market: &MarketConfig, user_order_id_base: u8) -> Self {
+        let model: Box<dyn QuoteModel> = match &config.avellaneda_stoikov {
+            Some(as_config) => Box::new(AvellanedaStoikovModel::new(as_config.clone())),
+            None => Box::new(SpreadMultiplierModel),
+        };
+
+        Self {
+            config: market.clone(),
+            model,
+            volatility: RealizedVol::new(config.volatility_window_ms()),
+            user_order_id_base,
+            live_orders: None,
+            resting: Vec::new(),
+            oracle_guard: config.oracle_guard.clone().map(OracleGuard::new),
+            oracle_widen: 1.0,
+            quotes_pulled: false,
+            prev_oracle_price: Price::ZERO,
+            last_update_time: 0,
+            fills: FillTracker::default(),
+            position: 0.0,
+            cash: 0.0,
+            peak_pnl: 0.0,
+            oracle_path: Vec::new(),
+            fill_log: Vec::new(),
+            report: BacktestReport {
+                market: market.symbol.clone(),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Process one snapshot: fill resting quotes, then requote like the live loop
+    fn step(
+        &mut self,
+        config: &BotConfig,
+        backtest: &BacktestConfig,
+        rng: &mut Rng,
+        record: &MarketRecord,
+    ) {
+        let oracle_price = record.oracle;
+        let levels = |levels: &[(f64, f64)]| -> Vec<L2Level> {
+            levels
+                .iter()
+                .map(|&(price, size)| L2Level { price, size })
+                .collect()
+        };
+        let (bids, asks) = (levels(&record.bids), levels(&record.asks));
+
+        self.report.snapshots += 1;
+        self.oracle_path.push((record.ts, oracle_price));
+        self.match_orders(backtest, rng, record.ts, oracle_price, &bids, &asks);
+        self.mark(oracle_price);
+
+        let Some(new_price) = Price::from_f64(oracle_price) else {
+            self.report.quote_errors += 1;
+            return;
+        };
+
+        // Recordings without confidence or delay only trip the jump guard
+        let reading = OracleReading {
+            price: new_price.raw(),
+            confidence: record
+                .confidence
+                .and_then(|c| Price::from_f64(c)?.to_unsigned())
+                .unwrap_or_default(),
+            delay_slots: record.delay_slots.unwrap_or_default(),
+        };
+        let check = self
+            .oracle_guard
+            .as_mut()
+            .map_or(OracleCheck::Ok, |guard| guard.check(&reading));
+        let widen = match check {
+            OracleCheck::Ok => 1.0,
+            OracleCheck::Widen { multiplier, .. } => multiplier,
+            OracleCheck::Pull(_) => {
+                self.resting.clear();
+                self.live_orders = Some(Vec::new());
+                self.quotes_pulled = true;
+                return;
+            }
+        };
+        if self.quotes_pulled || (widen - self.oracle_widen).abs() > 0.1 {
+            self.quotes_pulled = false;
+            self.prev_oracle_price = Price::ZERO;
+        }
+        self.oracle_widen = widen;
+
+        self.volatility.update(record.ts, oracle_price);
+
+        if requote_trigger(
+            config,
+            self.prev_oracle_price,
+            self.last_update_time,
+            record.ts,
+            new_price,
+        )
+        .is_none()
+        {
+            return;
+        }
+        self.report.requotes += 1;
+
+        let snapshot = MarketSnapshot {
+            oracle_price,
+            bids,
+            asks,
+            position: self.position,
+            volatility: self
+                .volatility
+                .volatility_over(config.model_volatility_window_secs() * 1000),
+            // Recordings have no market precision, sizes and offsets round to the smallest unit
+            precision: MarketPrecision::default(),
+            config: &self.config,
+        };
+        let plan = match plan_quotes(
+            self.model.as_mut(),
+            &snapshot,
+            // Recordings have no funding rates
+            0.0,
+            &self.volatility,
+            config.volatility.as_ref(),
+            self.oracle_widen,
+            self.user_order_id_base,
+        ) {
+            Ok(plan) => plan,
+            Err(_) => {
+                self.report.quote_errors += 1;
+                return;
+            }
+        };
+        let desired = plan.desired();
+
+        // Same diff as the live bot, cancel/replace everything when resting orders are unknown
+        match &self.live_orders {
+            Some(live) => {
+                let diff = OrderDiff::new(live, &desired, &config.order_tolerance, new_price);
+                self.resting
+                    .retain(|o| !diff.cancel.contains(&o.order.user_order_id));
+                for order in diff.modify.iter().chain(&diff.place) {
+                    rest_order(&mut self.resting, *order, &snapshot);
+                }
+                self.live_orders = Some(diff.into_live());
+            }
+            None => {
+                self.resting.clear();
+                for order in &desired {
+                    rest_order(&mut self.resting, *order, &snapshot);
+                }
+                self.live_orders = Some(desired);
+            }
+        }
+
+        self.prev_oracle_price = new_price;
+        self.last_update_time = record.ts;
+    }
+
+    /// Fill resting quotes against the snapshot with the configured fill model.
+    /// Each book level is used once per snapshot, fills are at the quote price.
+    fn match_orders(
+        &mut self,
+        backtest: &BacktestConfig,
+        rng: &mut Rng,
+        ts: u64,
+        oracle_price: f64,
+        bids: &[L2Level],
+        asks: &[L2Level],
+    ) {
+        let mut bid_liquidity = bids.to_vec();
+        let mut ask_liquidity = asks.to_vec();
+        let mut fills = Vec::new();
+
+        for order in self.resting.iter_mut() {
+            let price = order.price(oracle_price);
+            // A resting bid is hit by asks at or below it and vice versa
+            let levels = match order.order.side {
+                Side::Buy => &mut ask_liquidity,
+                Side::Sell => &mut bid_liquidity,
+            };
+            let crosses = |level: f64| match order.order.side {
+                Side::Buy => level <= price,
+                Side::Sell => level >= price,
+            };
+
+            let filled = match backtest.fill_model {
+                FillModel::Touch => take_liquidity(levels, crosses, order.remaining),
+                FillModel::QueuePosition => {
+                    let traded = book_size(levels, crosses);
+                    let filled = (traded - order.queue_ahead).clamp(0.0, order.remaining);
+                    order.queue_ahead = (order.queue_ahead - traded).max(0.0);
+                    take_liquidity(levels, crosses, filled)
+                }
+                FillModel::Probabilistic {
+                    probability,
+                    decay_bps,
+                } => {
+                    let distance_bps = levels.first().map_or(f64::INFINITY, |level| {
+                        let gap = match order.order.side {
+                            Side::Buy => level.price - price,
+                            Side::Sell => price - level.price,
+                        };
+                        gap.max(0.0) / price * 10_000.0
+                    });
+                    let p = probability * (-distance_bps / decay_bps).exp();
+                    if rng.next_f64() < p {
+                        order.remaining
+                    } else {
+                        0.0
+                    }
+                }
+            };
+            levels.retain(|l| l.size > 0.0);
+
+            if filled <= 0.0 {
+                continue;
+            }
+            order.remaining -= filled;
+            fills.push((order.order.side, filled, price));
+        }
+        self.resting.retain(|o| o.remaining > SIZE_EPSILON);
+
+        for (side, size, price) in fills {
+            self.record_fill(backtest, ts, side, size, price);
+        }
+    }
+
+    fn record_fill(
+        &mut self,
+        backtest: &BacktestConfig,
+        ts: u64,
+        side: Side,
+        size: f64,
+        price: f64,
+    ) {
+        let fee = size * price * backtest.maker_fee_bps / 10_000.0;
+        let signed = match side {
+            Side::Buy => size,
+            Side::Sell => -size,
+        };
+        self.position += signed;
+        self.cash -= signed * price + fee;
+        self.fills.record(Fill {
+            market_index: 0,
+            side,
+            size,
+            price,
+            fee,
+        });
+
+        let position = self.position;
+        let report = &mut self.report;
+        report.fills += 1;
+        report.fees += fee;
+        report.min_position = report.min_position.min(position);
+        report.max_position = report.max_position.max(position);
+        report.inventory.push(InventoryPoint {
+            ts,
+            position,
+            pnl: self.cash + position * price,
+        });
+        self.fill_log.push((ts, side, price));
+
+        // Filled quotes no longer match what the live bot tracks
+        self.live_orders = None;
+    }
+
+    /// Mark to market at the oracle price and track drawdown
+    fn mark(&mut self, oracle_price: f64) {
+        let pnl = self.cash + self.position * oracle_price;
+        self.peak_pnl = self.peak_pnl.max(pnl);
+        self.report.max_drawdown = self.report.max_drawdown.max(self.peak_pnl - pnl);
+        self.report.total_pnl = pnl;
+    }
+
+    fn finish(mut self, backtest: &BacktestConfig) -> BacktestReport {
+        let stats = self.fills.stats();
+        self.report.volume = stats.traded_volume;
+        self.report.realized_pnl = stats.net_pnl();
+        self.report.final_position = self.position;
+        self.report.markouts = backtest
+            .markout_secs
+            .iter()
+            .map(|&secs| (secs, self.markout(secs * 1000)))
+            .collect();
+        self.report
+    }
+
+    /// Average oracle move after each fill in the fill's favour (bps), using the
+    /// first snapshot at or after the horizon
+    fn markout(&self, horizon_ms: u64) -> Option<f64> {
+        let markouts: Vec<f64> = self
+            .fill_log
+            .iter()
+            .filter_map(|&(ts, side, price)| {
+                let i = self
+                    .oracle_path
+                    .partition_point(|&(t, _)| t < ts + horizon_ms);
+                let (_, later) = self.oracle_path.get(i)?;
+                let sign = match side {
+                    Side::Buy => 1.0,
+                    Side::Sell => -1.0,
+                };
+                Some(sign * (later - price) / price * 10_000.0)
+            })
+            .collect();
+
+        (!markouts.is_empty()).then(|| markouts.iter().sum::<f64>() / markouts.len() as f64)
+    }
+}
+
+/// Rest a post-only quote, replacing any order with the same user order id.
+/// Quotes that would cross the book are dropped like a rejected post-only.
+fn rest_order(resting: &mut Vec<SimOrder>, order: QuoteOrder, snapshot: &MarketSnapshot) {
+    resting.retain(|o| o.order.user_order_id != order.user_order_id);
+
+    let price = snapshot.oracle_price + order.oracle_price_offset.to_f64();
+    let (crosses, queue_ahead) = match order.side {
+        Side::Buy => (
+            snapshot.asks.first().is_some_and(|ask| price >= ask.price),
+            book_size(&snapshot.bids, |level| level >= price),
+        ),
+        Side::Sell => (
+            snapshot.bids.first().is_some_and(|bid| price <= bid.price),
+            book_size(&snapshot.asks, |level| level <= price),
+        ),
+    };
+    if crosses {
+        return;
+    }
+
+    resting.push(SimOrder {
+        order,
+        remaining: order.base_asset_amount.to_f64(),
+        queue_ahead,
+    });
+}
+
+/// Total size of book levels whose price passes `filter`
+fn book_size(levels: &[L2Level], filter: impl Fn(f64) -> bool) -> f64 {
+    levels
+        .iter()
+        .filter(|level| filter(level.price))
+        .map(|level| level.size)
+        .sum()
+}
+
+/// Consume up to `size` from levels that pass `crosses`, best first
+fn take_liquidity(levels: &mut [L2Level], crosses: impl Fn(f64) -> bool, size: f64) -> f64 {
+    let mut filled = 0.0;
+    for level in levels.iter_mut() {
+        if !crosses(level.price) || filled >= size {
+            break;
+        }
+        let take = level.size.min(size - filled);
+        level.size -= take;
+        filled += take;
+    }
+    filled
+}
+
+/// Replay records through every configured market. Records for markets that
+/// are not configured are ignored.
+pub fn run(config: &BotConfig, records: &[MarketRecord]) -> Result<Vec<BacktestReport>> {
+    let backtest = config.backtest.clone().unwrap_or_default();
+
+    let mut markets = Vec::with_capacity(config.markets.len());
+    let mut by_symbol = HashMap::new();
+    let mut next_user_order_id = 0u8;
+    for market in &config.markets {
+        by_symbol.insert(market.symbol.as_str(), markets.len());
+        markets.push(SimMarket::new(config, market, next_user_order_id));
+        next_user_order_id += (market.ladder.levels * 2) as u8;
+    }
+
+    let mut rng = Rng::new(backtest.seed);
+    let mut replayed = 0;
+    for record in records {
+        let Some(&idx) = by_symbol.get(record.market.as_str()) else {
+            continue;
+        };
+        if !record.oracle.is_finite() || record.oracle <= 0.0 {
+            bail!(
+                "[{}] Invalid oracle price {} at {}",
+                record.market,
+                record.oracle,
+                record.ts
+            );
+        }
+        markets[idx].step(config, &backtest, &mut rng, record);
+        replayed += 1;
+    }
+    if replayed == 0 {
+        bail!("No records for the configured markets");
+    }
+
+    Ok(markets
+        .into_iter()
+        .map(|market| market.finish(&backtest))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bot_common::units::BaseAmount;
+
+    fn config(fill_model: FillModel) -> BotConfig {
+        BotConfig {
+            markets: vec![MarketConfig::test_default()],
+            debounce_ms: 0,
+            oracle_change_threshold_bps: 0.0,
+            backtest: Some(BacktestConfig {
+                fill_model,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn record(line: &str) -> MarketRecord {
+        serde_json::from_str(line).unwrap()
+    }
+
+    fn level(price: f64, size: f64) -> L2Level {
+        L2Level { price, size }
+    }
+
+    /// Bid resting at $99.90 with 0.3 ahead of it in the queue
+    fn resting_bid(config: &BotConfig) -> SimMarket {
+        let mut market = SimMarket::new(config, &config.markets[0], 0);
+        let snapshot = MarketSnapshot {
+            oracle_price: 100.0,
+            bids: vec![level(99.9, 0.3)],
+            asks: vec![level(100.1, 1.0)],
+            position: 0.0,
+            volatility: None,
+            precision: MarketPrecision::default(),
+            config: &config.markets[0],
+        };
+        let bid = QuoteOrder {
+            user_order_id: 0,
+            side: Side::Buy,
+            oracle_price_offset: Price::from_raw(-100_000),
+            base_asset_amount: BaseAmount::from_raw(500_000_000),
+        };
+        rest_order(&mut market.resting, bid, &snapshot);
+        market
+    }
+
+    #[test]
+    fn test_replay_report() {
+        let config = config(FillModel::Touch);
+        let records = [
+            r#"{"ts": 0, "market": "SOL-PERP", "oracle": 100.0, "bids": [[99.9, 1.0]], "asks": [[100.1, 1.0]]}"#,
+            // Asks drop through the $99.85 bid
+            r#"{"ts": 1000, "market": "SOL-PERP", "oracle": 100.0, "bids": [[99.7, 1.0]], "asks": [[99.8, 1.0]]}"#,
+            r#"{"ts": 11000, "market": "SOL-PERP", "oracle": 100.5, "bids": [[90.0, 1.0]], "asks": [[110.0, 1.0]]}"#,
+            r#"{"ts": 12000, "market": "SOL-PERP", "oracle": 99.5, "bids": [[90.0, 1.0]], "asks": [[110.0, 1.0]]}"#,
+            r#"{"ts": 12000, "market": "BTC-PERP", "oracle": 60000.0, "bids": [], "asks": []}"#,
+        ]
+        .map(record);
+
+        let reports = run(&config, &records).unwrap();
+        assert_eq!(reports.len(), 1);
+        let report = &reports[0];
+        assert_eq!(report.snapshots, 4);
+        assert_eq!(report.requotes, 4);
+        assert_eq!(report.fills, 1);
+        assert!((report.final_position - 0.1).abs() < 1e-9);
+        assert!((report.max_position - 0.1).abs() < 1e-9);
+
+        // Bought 0.1 @ $99.85, marked at $99.50 after peaking at $100.50
+        assert!((report.total_pnl + 0.035).abs() < 1e-6);
+        assert!((report.max_drawdown - 0.1).abs() < 1e-6);
+        assert_eq!(report.realized_pnl, 0.0);
+
+        // 1s and 10s markouts both use the $100.50 oracle at 11s, 60s has no data
+        let markout = (100.5 - 99.85) / 99.85 * 10_000.0;
+        assert!((report.markouts[0].1.unwrap() - markout).abs() < 1e-3);
+        assert!((report.markouts[1].1.unwrap() - markout).abs() < 1e-3);
+        assert_eq!(report.markouts[2], (60, None));
+    }
+
+    #[test]
+    fn test_queue_position() {
+        let backtest = BacktestConfig {
+            fill_model: FillModel::QueuePosition,
+            ..Default::default()
+        };
+        let config = config(backtest.fill_model);
+        let mut rng = Rng::new(1);
+        let mut market = resting_bid(&config);
+        assert_eq!(market.resting[0].queue_ahead, 0.3);
+
+        // 0.2 trades at our price, all of it ahead of us
+        market.match_orders(&backtest, &mut rng, 1000, 100.0, &[], &[level(99.9, 0.2)]);
+        assert_eq!(market.report.fills, 0);
+
+        // 0.4 more fills the last 0.1 ahead and then 0.3 of ours
+        market.match_orders(&backtest, &mut rng, 2000, 100.0, &[], &[level(99.9, 0.4)]);
+        assert_eq!(market.report.fills, 1);
+        assert!((market.position - 0.3).abs() < 1e-9);
+        assert!((market.resting[0].remaining - 0.2).abs() < 1e-9);
+
+        // Touch fills straight away
+        let backtest = BacktestConfig::default();
+        let mut market = resting_bid(&config);
+        market.match_orders(&backtest, &mut rng, 1000, 100.0, &[], &[level(99.9, 0.2)]);
+        assert!((market.position - 0.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_probabilistic_fills() {
+        let fill_model = FillModel::Probabilistic {
+            probability: 1.0,
+            decay_bps: 1.0,
+        };
+        let backtest = BacktestConfig {
+            fill_model,
+            ..Default::default()
+        };
+        let config = config(fill_model);
+        let mut rng = Rng::new(backtest.seed);
+
+        // 50 bps away from the best ask, practically never fills
+        let mut market = resting_bid(&config);
+        market.match_orders(&backtest, &mut rng, 1000, 100.0, &[], &[level(100.4, 1.0)]);
+        assert_eq!(market.report.fills, 0);
+
+        // Touched, fills in full whatever size is on the book
+        market.match_orders(&backtest, &mut rng, 2000, 100.0, &[], &[level(99.9, 0.01)]);
+        assert_eq!(market.report.fills, 1);
+        assert!((market.position - 0.5).abs() < 1e-9);
+        assert!(market.resting.is_empty());
+
+        // Same seed, same draws
+        let (mut a, mut b) = (Rng::new(7), Rng::new(7));
+        for _ in 0..10 {
+            let x = a.next_f64();
+            assert_eq!(x, b.next_f64());
+            assert!((0.0..1.0).contains(&x));
+        }
+    }
+}
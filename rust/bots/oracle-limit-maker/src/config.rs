@@ -0,0 +1,533 @@
+//! Bot configuration: file loading, CLI/env overrides and validation.
+
+use std::{collections::HashSet, fs, net::SocketAddr, path::Path, path::PathBuf, str::FromStr};
+
+use anyhow::{bail, Context, Result};
+use bot_common::shutdown::{ShutdownConfig, ShutdownPolicy};
+use bot_common::tx::TxConfig;
+use bot_common::validate::{ensure_non_negative, ensure_positive};
+use clap::{Parser, ValueEnum};
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::avellaneda::AvellanedaStoikovConfig;
+use crate::backtest::BacktestConfig;
+use crate::funding::FundingConfig;
+use crate::ladder::LadderConfig;
+use crate::oracle_guard::OracleGuardConfig;
+use crate::orders::OrderTolerance;
+use crate::paper::PaperConfig;
+use crate::precision::RoundingConfig;
+use crate::recorder::RecorderConfig;
+use crate::risk::RiskLimits;
+use crate::volatility::VolatilityConfig;
+
+// Realized volatility window for quote models that don't configure one
+pub const DEFAULT_VOLATILITY_WINDOW_SECS: u64 = 300;
+
+// Maximum open orders on a Drift user account
+pub const MAX_OPEN_ORDERS: usize = 32;
+
+/// Oracle limit market maker
+#[derive(Debug, Clone, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Config file (.toml, .yaml or .yml)
+    #[arg(short, long, env = "MAKER_CONFIG", default_value = "config.toml")]
+    pub config: PathBuf,
+
+    /// Drift network, overrides the config file
+    #[arg(long, env = "MAKER_NETWORK")]
+    pub network: Option<Network>,
+
+    /// Authority pubkey when trading as a delegate, overrides the config file
+    #[arg(long, env = "MAKER_AUTHORITY")]
+    pub authority: Option<String>,
+
+    /// Subaccount ID, overrides the config file
+    #[arg(long, env = "MAKER_SUBACCOUNT_ID")]
+    pub subaccount_id: Option<u16>,
+
+    /// Minimum time between quote updates (ms), overrides the config file
+    #[arg(long, env = "MAKER_DEBOUNCE_MS")]
+    pub debounce_ms: Option<u64>,
+
+    /// Minimum oracle move to requote (bps), overrides the config file
+    #[arg(long, env = "MAKER_ORACLE_CHANGE_THRESHOLD_BPS")]
+    pub oracle_change_threshold_bps: Option<f32>,
+
+    /// Only quote these configured markets, comma separated
+    #[arg(long, env = "MAKER_MARKETS", value_delimiter = ',')]
+    pub markets: Vec<String>,
+
+    /// Simulate fills against the live DLOB instead of sending orders
+    #[arg(long, env = "MAKER_PAPER")]
+    pub paper: bool,
+
+    /// Record market data, quotes and fills to this directory
+    #[arg(long, env = "MAKER_RECORD", value_name = "DIR")]
+    pub record: Option<PathBuf>,
+
+    /// Only record market data, don't quote (implies recording)
+    #[arg(long, env = "MAKER_RECORD_ONLY")]
+    pub record_only: bool,
+
+    /// Serve Prometheus metrics on this address, overrides the config file
+    #[arg(long, env = "MAKER_METRICS_ADDR")]
+    pub metrics_addr: Option<SocketAddr>,
+
+    /// Reload the config when the file changes (SIGUSR2 always reloads)
+    #[arg(long, env = "MAKER_WATCH_CONFIG")]
+    pub watch_config: bool,
+
+    /// Validate the config and exit
+    #[arg(long)]
+    pub check: bool,
+
+    /// Replay recorded market data (JSONL) through the quote pipeline and exit
+    #[arg(long, value_name = "FILE")]
+    pub backtest: Option<PathBuf>,
+
+    /// Write the backtest inventory and PnL path to this CSV file
+    #[arg(long, value_name = "FILE", requires = "backtest")]
+    pub backtest_output: Option<PathBuf>,
+}
+
+/// Drift cluster to trade on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    #[default]
+    Mainnet,
+    Devnet,
+}
+
+/// Per-market quoting parameters
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MarketConfig {
+    // Market symbol
+    pub symbol: String,
+    // Amount per order (base units)
+    pub order_size: f64,
+    // Maximum position size before skewing
+    pub max_position_size: f64,
+    // Multiplier for market spread (e.g. 1.5 = 150% of market spread)
+    pub spread_multiplier: f64,
+    // Quote levels per side
+    #[serde(default)]
+    pub ladder: LadderConfig,
+    // Spread around the oracle (BPS) when the book is one-sided, empty,
+    // locked or crossed; None skips quoting instead
+    pub fallback_spread_bps: Option<f64>,
+    // Rounding of quote prices and sizes to market precision
+    #[serde(default)]
+    pub rounding: RoundingConfig,
+}
+
+/// Bot configuration parameters
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BotConfig {
+    // Drift cluster
+    pub network: Network,
+    // Perp markets to quote
+    pub markets: Vec<MarketConfig>,
+    // Minimum time between oracle updates
+    pub debounce_ms: u64,
+    // Minimum price change to trigger update (BPS)
+    pub oracle_change_threshold_bps: f32,
+    // Authority pubkey (for delegation)
+    pub authority: Option<String>,
+    // Subaccount ID
+    pub subaccount_id: u16,
+    // Quote with Avellaneda–Stoikov instead of the spread multiplier model
+    pub avellaneda_stoikov: Option<AvellanedaStoikovConfig>,
+    // Scale spreads with realized oracle volatility
+    pub volatility: Option<VolatilityConfig>,
+    // Lean the reservation price toward the side that collects funding
+    pub funding: Option<FundingConfig>,
+    // Resting orders within this tolerance of the desired quote are left alone
+    pub order_tolerance: OrderTolerance,
+    // Compare tracked quotes with the user account's open orders this often
+    pub reconcile_interval_ms: Option<u64>,
+    // Kill switch limits
+    pub risk_limits: RiskLimits,
+    // Pull or widen quotes when the oracle is stale, uncertain or jumps
+    pub oracle_guard: Option<OracleGuardConfig>,
+    // Serve Prometheus metrics at http://<addr>/metrics
+    pub metrics_addr: Option<SocketAddr>,
+    // Paper trade against the live DLOB, nothing is sent on-chain
+    pub paper: Option<PaperConfig>,
+    // Fill model and fees for `--backtest`
+    pub backtest: Option<BacktestConfig>,
+    // Record L2 snapshots, oracle ticks, quotes and fills
+    pub recorder: Option<RecorderConfig>,
+    // Confirmation tracking and re-sends for sent transactions
+    pub tx: TxConfig,
+    // What happens to orders and positions when the bot stops
+    pub shutdown: ShutdownConfig,
+}
+
+impl Default for BotConfig {
+    fn default() -> Self {
+        Self {
+            network: Network::default(),
+            markets: Vec::new(),
+            debounce_ms: 1000,
+            oracle_change_threshold_bps: 0.5,
+            authority: None,
+            subaccount_id: 0,
+            avellaneda_stoikov: None,
+            volatility: None,
+            funding: None,
+            order_tolerance: OrderTolerance::default(),
+            reconcile_interval_ms: None,
+            risk_limits: RiskLimits::default(),
+            oracle_guard: None,
+            metrics_addr: None,
+            paper: None,
+            backtest: None,
+            recorder: None,
+            tx: TxConfig::default(),
+            shutdown: ShutdownConfig::default(),
+        }
+    }
+}
+
+impl BotConfig {
+    /// Load the config file, apply CLI/env overrides and validate
+    pub fn load(cli: &Cli) -> Result<Self> {
+        let contents = fs::read_to_string(&cli.config)
+            .with_context(|| format!("Failed to read config {}", cli.config.display()))?;
+        let mut config = Self::parse(&cli.config, &contents)
+            .with_context(|| format!("Invalid config {}", cli.config.display()))?;
+
+        config.apply_overrides(cli)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Parse TOML or YAML based on the file extension
+    fn parse(path: &Path, contents: &str) -> Result<Self> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Ok(toml::from_str(contents)?),
+            Some("yaml" | "yml") => Ok(serde_yaml::from_str(contents)?),
+            _ => bail!("Unsupported config format, expected .toml, .yaml or .yml"),
+        }
+    }
+
+    fn apply_overrides(&mut self, cli: &Cli) -> Result<()> {
+        if let Some(network) = cli.network {
+            self.network = network;
+        }
+        if let Some(authority) = &cli.authority {
+            self.authority = Some(authority.clone());
+        }
+        if let Some(subaccount_id) = cli.subaccount_id {
+            self.subaccount_id = subaccount_id;
+        }
+        if let Some(debounce_ms) = cli.debounce_ms {
+            self.debounce_ms = debounce_ms;
+        }
+        if let Some(threshold) = cli.oracle_change_threshold_bps {
+            self.oracle_change_threshold_bps = threshold;
+        }
+        if let Some(addr) = cli.metrics_addr {
+            self.metrics_addr = Some(addr);
+        }
+        if cli.paper && self.paper.is_none() {
+            self.paper = Some(PaperConfig::default());
+        }
+        if cli.record.is_some() || cli.record_only {
+            let recorder = self.recorder.get_or_insert_with(RecorderConfig::default);
+            if let Some(dir) = &cli.record {
+                recorder.dir = dir.clone();
+            }
+            recorder.record_only |= cli.record_only;
+        }
+
+        if !cli.markets.is_empty() {
+            for symbol in &cli.markets {
+                if !self.markets.iter().any(|m| &m.symbol == symbol) {
+                    bail!("Market '{}' is not in the config file", symbol);
+                }
+            }
+            self.markets.retain(|m| cli.markets.contains(&m.symbol));
+        }
+
+        Ok(())
+    }
+
+    /// Check the config is usable before connecting
+    pub fn validate(&self) -> Result<()> {
+        if self.markets.is_empty() {
+            bail!("No markets configured");
+        }
+
+        let mut symbols = HashSet::new();
+        for (i, market) in self.markets.iter().enumerate() {
+            if !symbols.insert(&market.symbol) {
+                bail!("Market '{}' configured twice", market.symbol);
+            }
+            market
+                .validate()
+                .with_context(|| format!("markets[{}] ({})", i, market.symbol))?;
+        }
+
+        // Every ladder level is a resting order
+        let total_orders: usize = self.markets.iter().map(|m| m.ladder.levels * 2).sum();
+        if total_orders > MAX_OPEN_ORDERS {
+            bail!(
+                "Ladders need up to {} open orders, account limit is {}",
+                total_orders,
+                MAX_OPEN_ORDERS
+            );
+        }
+
+        if let Some(authority) = &self.authority {
+            if Pubkey::from_str(authority).is_err() {
+                bail!("authority '{}' is not a valid pubkey", authority);
+            }
+        }
+        ensure_non_negative(
+            "oracle_change_threshold_bps",
+            self.oracle_change_threshold_bps as f64,
+        )?;
+        self.order_tolerance.validate().context("order_tolerance")?;
+
+        if let Some(as_config) = &self.avellaneda_stoikov {
+            as_config.validate().context("avellaneda_stoikov")?;
+        }
+        if let Some(vol) = &self.volatility {
+            vol.validate().context("volatility")?;
+        }
+        if let Some(funding) = &self.funding {
+            funding.validate().context("funding")?;
+        }
+
+        if self.reconcile_interval_ms == Some(0) {
+            bail!("reconcile_interval_ms must be > 0");
+        }
+
+        self.risk_limits.validate().context("risk_limits")?;
+        if self.risk_limits.flatten_on_breach
+            && matches!(self.shutdown.policy, ShutdownPolicy::CancelOnly)
+        {
+            bail!("risk_limits.flatten_on_breach needs a shutdown policy that flattens");
+        }
+
+        if let Some(paper) = &self.paper {
+            paper.validate().context("paper")?;
+        }
+        if let Some(backtest) = &self.backtest {
+            backtest.validate().context("backtest")?;
+        }
+        if let Some(recorder) = &self.recorder {
+            recorder.validate().context("recorder")?;
+        }
+
+        self.shutdown.validate().context("shutdown")?;
+        self.tx.validate().context("tx")?;
+        if let Some(guard) = &self.oracle_guard {
+            guard.validate().context("oracle_guard")?;
+        }
+
+        Ok(())
+    }
+
+    /// Record market data without quoting
+    pub fn record_only(&self) -> bool {
+        self.recorder.as_ref().is_some_and(|r| r.record_only)
+    }
+
+    /// Realized volatility window passed to quote models (seconds)
+    pub fn model_volatility_window_secs(&self) -> u64 {
+        self.avellaneda_stoikov
+            .as_ref()
+            .map_or(DEFAULT_VOLATILITY_WINDOW_SECS, |c| c.volatility_window_secs)
+    }
+
+    /// Oracle history to keep for the model and every adaptive spread horizon (ms)
+    pub fn volatility_window_ms(&self) -> u64 {
+        self.volatility
+            .as_ref()
+            .map_or(0, |c| c.window_ms())
+            .max(self.model_volatility_window_secs() * 1000)
+    }
+}
+
+#[cfg(test)]
+impl MarketConfig {
+    /// SOL-PERP quoted at 0.1 per order with default ladder and rounding
+    pub fn test_default() -> Self {
+        Self {
+            symbol: "SOL-PERP".to_string(),
+            order_size: 0.1,
+            max_position_size: 1.0,
+            spread_multiplier: 1.5,
+            ladder: LadderConfig::default(),
+            fallback_spread_bps: None,
+            rounding: RoundingConfig::default(),
+        }
+    }
+}
+
+impl MarketConfig {
+    fn validate(&self) -> Result<()> {
+        ensure_positive("order_size", self.order_size)?;
+        ensure_positive("max_position_size", self.max_position_size)?;
+        if self.order_size > self.max_position_size {
+            bail!(
+                "order_size {} exceeds max_position_size {}",
+                self.order_size,
+                self.max_position_size
+            );
+        }
+        ensure_positive("spread_multiplier", self.spread_multiplier)?;
+        if let Some(bps) = self.fallback_spread_bps {
+            ensure_positive("fallback_spread_bps", bps)?;
+        }
+
+        self.ladder.validate().context("ladder")?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bot_common::fees::FeeMode;
+
+    const TOML_CONFIG: &str = r#"
+        debounce_ms = 500
+        authority = "11111111111111111111111111111111"
+
+        [[markets]]
+        symbol = "BTC-PERP"
+        order_size = 0.001
+        max_position_size = 0.01
+        spread_multiplier = 1.5
+        fallback_spread_bps = 5.0
+        ladder = { levels = 2, spacing = { linear = { step_bps = 2.0 } }, size_multipliers = [1.0, 2.0] }
+
+        [[markets]]
+        symbol = "SOL-PERP"
+        order_size = 0.1
+        max_position_size = 1.0
+        spread_multiplier = 1.5
+
+        [oracle_guard]
+        max_jump_bps = 25.0
+        action = { widen = { max_multiplier = 4.0 } }
+    "#;
+
+    fn cli(args: &[&str]) -> Cli {
+        Cli::parse_from(std::iter::once("oracle-limit-maker").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn test_parse_toml_and_yaml() {
+        let config = BotConfig::parse(Path::new("config.toml"), TOML_CONFIG).unwrap();
+        assert_eq!(config.debounce_ms, 500);
+        assert_eq!(config.network, Network::Mainnet);
+        assert_eq!(config.markets[0].ladder.levels, 2);
+        assert_eq!(config.markets[1].ladder, LadderConfig::default());
+        assert!(config.validate().is_ok());
+
+        let yaml = "
+markets:
+  - symbol: SOL-PERP
+    order_size: 0.1
+    max_position_size: 1.0
+    spread_multiplier: 1.5
+network: devnet
+";
+        let config = BotConfig::parse(Path::new("config.yaml"), yaml).unwrap();
+        assert_eq!(config.network, Network::Devnet);
+        assert!(config.validate().is_ok());
+
+        // Typos are rejected rather than silently ignored
+        let typo = "debounse_ms = 1\n";
+        assert!(BotConfig::parse(Path::new("config.toml"), typo).is_err());
+        assert!(BotConfig::parse(Path::new("config.json"), "{}").is_err());
+    }
+
+    #[test]
+    fn test_example_config_is_valid() {
+        let example = include_str!("../config.toml");
+        let config = BotConfig::parse(Path::new("config.toml"), example).unwrap();
+
+        config.validate().unwrap();
+        assert_eq!(config.markets.len(), 2);
+    }
+
+    #[test]
+    fn test_overrides() {
+        let mut config = BotConfig::parse(Path::new("config.toml"), TOML_CONFIG).unwrap();
+        let args = cli(&[
+            "--subaccount-id",
+            "3",
+            "--markets",
+            "SOL-PERP",
+            "--network",
+            "devnet",
+        ]);
+        config.apply_overrides(&args).unwrap();
+
+        assert_eq!(config.subaccount_id, 3);
+        assert_eq!(config.network, Network::Devnet);
+        assert_eq!(config.markets.len(), 1);
+        assert_eq!(config.markets[0].symbol, "SOL-PERP");
+
+        let unknown = cli(&["--markets", "ETH-PERP"]);
+        assert!(config.apply_overrides(&unknown).is_err());
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        let base = BotConfig::parse(Path::new("config.toml"), TOML_CONFIG).unwrap();
+        let error = |config: BotConfig| format!("{:#}", config.validate().unwrap_err());
+
+        let mut config = base.clone();
+        config.markets[0].order_size = 0.1;
+        assert_eq!(
+            error(config),
+            "markets[0] (BTC-PERP): order_size 0.1 exceeds max_position_size 0.01"
+        );
+
+        let mut config = base.clone();
+        config.authority = Some("not-a-pubkey".to_string());
+        assert!(error(config).contains("not a valid pubkey"));
+
+        let mut config = base.clone();
+        config.markets[1].symbol = "BTC-PERP".to_string();
+        assert!(error(config).contains("configured twice"));
+
+        let mut config = base.clone();
+        config.tx.priority_fee.mode = FeeMode::Adaptive {
+            percentile: 75.0,
+            multiplier: 0.5,
+        };
+        assert_eq!(
+            error(config),
+            "tx: priority_fee: multiplier must be >= 1, got 0.5"
+        );
+
+        let mut config = base.clone();
+        config.shutdown.policy = ShutdownPolicy::Twap {
+            minutes: 5.0,
+            slices: 10,
+            max_slippage_bps: 30.0,
+        };
+        assert_eq!(
+            error(config),
+            "shutdown: timeout_secs must cover the shutdown policy and confirmation, at least 310s"
+        );
+
+        let mut config = base;
+        config.markets[0].ladder.levels = 20;
+        assert!(error(config).contains("account limit is 32"));
+    }
+}
@@ -0,0 +1,142 @@
+//! Wakeups for the trading loop from gRPC oracle, slot and DLOB notifications,
+//! our own fills and transaction confirmations.
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use tokio::sync::Notify;
+
+/// What woke the trading loop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateSource {
+    // Oracle account of a quoted market changed
+    Oracle,
+    // New slot, re-checks oracle delay and debounced markets
+    Slot,
+    // User account update applied to the DLOB, recorded without a wakeup
+    Book,
+    // One of our orders filled
+    Fill,
+    // Sent transactions reached a final status
+    Tx,
+}
+
+impl UpdateSource {
+    pub const ALL: [UpdateSource; 5] = [
+        UpdateSource::Oracle,
+        UpdateSource::Slot,
+        UpdateSource::Book,
+        UpdateSource::Fill,
+        UpdateSource::Tx,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            UpdateSource::Oracle => "oracle",
+            UpdateSource::Slot => "slot",
+            UpdateSource::Book => "book",
+            UpdateSource::Fill => "fill",
+            UpdateSource::Tx => "tx",
+        }
+    }
+}
+
+/// Notifications received per source since the loop last woke. A timeout
+/// can still carry updates that were only recorded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Wakeup {
+    counts: [u64; 5],
+    timed_out: bool,
+}
+
+impl Wakeup {
+    pub fn count(&self, source: UpdateSource) -> u64 {
+        self.counts[source as usize]
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        self.timed_out
+    }
+}
+
+/// Coalescing wakeup for the trading loop. Safe to call from gRPC callback
+/// threads; notifications that arrive while the loop is busy collapse into
+/// a single pending wakeup so bursts never queue up behind each other.
+#[derive(Debug, Default)]
+pub struct UpdateNotifier {
+    notify: Notify,
+    counts: [AtomicU64; 5],
+}
+
+impl UpdateNotifier {
+    pub fn notify(&self, source: UpdateSource) {
+        self.counts[source as usize].fetch_add(1, Ordering::Relaxed);
+        self.notify.notify_one();
+    }
+
+    /// Count an update without waking the loop, it is reported with the next
+    /// wakeup. For high-rate sources the loop only needs to see eventually.
+    pub fn record(&self, source: UpdateSource) {
+        self.counts[source as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Wait for the next notification, or at most `timeout`
+    pub async fn wait(&self, timeout: Duration) -> Wakeup {
+        let timed_out = tokio::time::timeout(timeout, self.notify.notified())
+            .await
+            .is_err();
+
+        let mut wakeup = Wakeup {
+            timed_out,
+            ..Default::default()
+        };
+        for (count, pending) in wakeup.counts.iter_mut().zip(&self.counts) {
+            *count = pending.swap(0, Ordering::Relaxed);
+        }
+        wakeup
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_notifications_coalesce() {
+        let notifier = UpdateNotifier::default();
+        notifier.notify(UpdateSource::Oracle);
+        notifier.notify(UpdateSource::Book);
+        notifier.notify(UpdateSource::Book);
+
+        // Sent before the wait, still wakes it straight away
+        let wakeup = notifier.wait(Duration::from_secs(10)).await;
+        assert_eq!(wakeup.count(UpdateSource::Oracle), 1);
+        assert_eq!(wakeup.count(UpdateSource::Book), 2);
+        assert_eq!(wakeup.count(UpdateSource::Slot), 0);
+
+        // Nothing pending, times out
+        let wakeup = notifier.wait(Duration::from_millis(10)).await;
+        assert!(wakeup.is_timeout());
+
+        // Recorded updates don't wake the loop but are reported with the next wakeup
+        notifier.record(UpdateSource::Book);
+        let wakeup = notifier.wait(Duration::from_millis(10)).await;
+        assert!(wakeup.is_timeout());
+        assert_eq!(wakeup.count(UpdateSource::Book), 1);
+    }
+
+    #[tokio::test]
+    async fn test_notify_from_another_thread() {
+        let notifier = std::sync::Arc::new(UpdateNotifier::default());
+        let remote = notifier.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            remote.notify(UpdateSource::Slot);
+        });
+
+        let wakeup = notifier.wait(Duration::from_secs(10)).await;
+        assert_eq!(wakeup.count(UpdateSource::Slot), 1);
+    }
+}
@@ -0,0 +1,206 @@
+//! Fill tracking with FIFO matching for realized PnL.
+
+use std::collections::{HashMap, VecDeque};
+
+use log::info;
+use serde::Serialize;
+
+/// Remaining size below which a lot is considered fully consumed
+const LOT_EPSILON: f64 = 1e-9;
+
+/// Side of a fill from the bot's perspective
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+/// Fill of one of the bot's orders
+#[derive(Debug, Clone)]
+pub struct Fill {
+    pub market_index: u16,
+    pub side: Side,
+    // Filled size (base units)
+    pub size: f64,
+    // Average fill price (quote units)
+    pub price: f64,
+    // Fee in quote units, negative when a rebate was earned
+    pub fee: f64,
+}
+
+/// Open lot waiting to be matched against the opposite side
+#[derive(Debug, Clone)]
+struct Lot {
+    size: f64,
+    price: f64,
+}
+
+/// FIFO buy/sell queues for a single market
+#[derive(Debug, Default)]
+struct MarketLots {
+    buys: VecDeque<Lot>,
+    sells: VecDeque<Lot>,
+}
+
+/// Aggregated fill statistics
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PnlStats {
+    // Realized PnL from matched round trips, excluding fees
+    pub realized_pnl: f64,
+    // Total fees paid (quote units)
+    pub fees_paid: f64,
+    // Total rebates earned (quote units)
+    pub rebates_earned: f64,
+    // Number of FIFO matches
+    pub total_matches: u64,
+    // Number of fills received
+    pub total_fills: u64,
+    // Traded notional (quote units)
+    pub traded_volume: f64,
+}
+
+impl PnlStats {
+    /// Realized PnL after fees and rebates
+    pub fn net_pnl(&self) -> f64 {
+        self.realized_pnl - self.fees_paid + self.rebates_earned
+    }
+}
+
+/// Tracks fills per market and matches them FIFO
+#[derive(Debug, Default)]
+pub struct FillTracker {
+    markets: HashMap<u16, MarketLots>,
+    stats: PnlStats,
+}
+
+impl FillTracker {
+    /// Record a fill, match it against open lots and return the realized PnL
+    pub fn record(&mut self, fill: Fill) -> f64 {
+        self.stats.total_fills += 1;
+        self.stats.traded_volume += fill.size * fill.price;
+        if fill.fee >= 0.0 {
+            self.stats.fees_paid += fill.fee;
+        } else {
+            self.stats.rebates_earned += -fill.fee;
+        }
+
+        let lots = self.markets.entry(fill.market_index).or_default();
+        let lot = Lot {
+            size: fill.size,
+            price: fill.price,
+        };
+        match fill.side {
+            Side::Buy => lots.buys.push_back(lot),
+            Side::Sell => lots.sells.push_back(lot),
+        }
+
+        let mut realized = 0.0;
+        while let (Some(buy), Some(sell)) = (lots.buys.front_mut(), lots.sells.front_mut()) {
+            // Match the smaller of the two sizes
+            let match_size = buy.size.min(sell.size);
+            let pnl = (sell.price - buy.price) * match_size;
+
+            realized += pnl;
+            self.stats.realized_pnl += pnl;
+            self.stats.total_matches += 1;
+
+            info!(
+                "Round trip #{} on market {}: size {:.6}, buy ${:.2}, sell ${:.2}, pnl ${:.4}",
+                self.stats.total_matches, fill.market_index, match_size, buy.price, sell.price, pnl
+            );
+
+            buy.size -= match_size;
+            sell.size -= match_size;
+
+            // Remove fully consumed lots
+            if buy.size <= LOT_EPSILON {
+                lots.buys.pop_front();
+            }
+            if sell.size <= LOT_EPSILON {
+                lots.sells.pop_front();
+            }
+        }
+
+        realized
+    }
+
+    /// Net size of unmatched lots for a market (positive = long)
+    pub fn open_size(&self, market_index: u16) -> f64 {
+        self.markets
+            .get(&market_index)
+            .map(|lots| {
+                let buys: f64 = lots.buys.iter().map(|l| l.size).sum();
+                let sells: f64 = lots.sells.iter().map(|l| l.size).sum();
+                buys - sells
+            })
+            .unwrap_or_default()
+    }
+
+    /// Aggregated statistics across all markets
+    pub fn stats(&self) -> &PnlStats {
+        &self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(side: Side, size: f64, price: f64, fee: f64) -> Fill {
+        Fill {
+            market_index: 1,
+            side,
+            size,
+            price,
+            fee,
+        }
+    }
+
+    #[test]
+    fn test_fifo_matching() {
+        let mut tracker = FillTracker::default();
+
+        assert_eq!(tracker.record(fill(Side::Buy, 1.0, 100.0, 0.0)), 0.0);
+        assert_eq!(tracker.record(fill(Side::Buy, 1.0, 102.0, 0.0)), 0.0);
+
+        // Oldest buy (100) is matched first
+        assert_eq!(tracker.record(fill(Side::Sell, 1.5, 104.0, 0.0)), 4.0 + 1.0);
+        assert_eq!(tracker.stats().total_matches, 2);
+        assert!((tracker.open_size(1) - 0.5).abs() < 1e-12);
+
+        assert_eq!(tracker.record(fill(Side::Sell, 0.5, 101.0, 0.0)), -0.5);
+        assert_eq!(tracker.open_size(1), 0.0);
+        assert_eq!(tracker.stats().realized_pnl, 4.5);
+    }
+
+    #[test]
+    fn test_fees_and_volume() {
+        let mut tracker = FillTracker::default();
+
+        tracker.record(fill(Side::Buy, 2.0, 50.0, 0.05));
+        tracker.record(fill(Side::Sell, 2.0, 51.0, -0.02));
+
+        let stats = tracker.stats();
+        assert_eq!(stats.total_fills, 2);
+        assert_eq!(stats.traded_volume, 202.0);
+        assert_eq!(stats.fees_paid, 0.05);
+        assert_eq!(stats.rebates_earned, 0.02);
+        assert!((stats.net_pnl() - 1.97).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_markets_are_independent() {
+        let mut tracker = FillTracker::default();
+
+        tracker.record(fill(Side::Buy, 1.0, 100.0, 0.0));
+        tracker.record(Fill {
+            market_index: 2,
+            ..fill(Side::Sell, 1.0, 10.0, 0.0)
+        });
+
+        assert_eq!(tracker.stats().total_matches, 0);
+        assert_eq!(tracker.open_size(1), 1.0);
+        assert_eq!(tracker.open_size(2), -1.0);
+    }
+}
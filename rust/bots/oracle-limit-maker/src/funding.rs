@@ -0,0 +1,216 @@
+//! Funding carry: leans the reservation price toward the side of a perp
+//! position that collects funding.
+//!
+//! A position held for `holding_secs` is expected to pay (long, positive
+//! rate) or receive funding of `rate * holding_secs / period` of its
+//! notional. Both quotes shift by that carry, so with longs paying the bot
+//! sells a little more readily and buys a little less, within `max_skew_bps`.
+
+use std::fmt;
+
+use anyhow::{bail, Result};
+use bot_common::validate::ensure_non_negative;
+use drift_rs::math::constants::{FUNDING_RATE_PRECISION, PRICE_PRECISION};
+use serde::Deserialize;
+
+use crate::quote::Quote;
+
+// Drift funding pays the mark/oracle TWAP spread over a day of hourly periods
+const FUNDING_PERIODS_PER_DAY: f64 = 24.0;
+
+/// Funding skew parameters
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FundingConfig {
+    // How long a fill is expected to be held (seconds), funding accrues over it
+    pub holding_secs: u64,
+    // Fraction of the expected carry moved into the reservation price
+    pub weight: f64,
+    // Max reservation price shift either way (BPS of oracle price)
+    pub max_skew_bps: f64,
+}
+
+impl Default for FundingConfig {
+    fn default() -> Self {
+        Self {
+            holding_secs: 3600,
+            weight: 1.0,
+            max_skew_bps: 5.0,
+        }
+    }
+}
+
+impl FundingConfig {
+    /// Check the settings are usable
+    pub fn validate(&self) -> Result<()> {
+        if self.holding_secs == 0 {
+            bail!("holding_secs must be > 0");
+        }
+        ensure_non_negative("weight", self.weight)?;
+        ensure_non_negative("max_skew_bps", self.max_skew_bps)
+    }
+}
+
+/// Funding state of a perp market. Rates are fractions of the oracle price
+/// per funding period, positive when longs pay shorts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FundingRates {
+    // Rate of the last funding payment
+    pub last_rate: f64,
+    // Rate the next payment settles at if the current TWAPs hold
+    pub predicted_rate: f64,
+    pub period_secs: u64,
+    pub secs_to_next: u64,
+}
+
+impl FundingRates {
+    /// From a market's AMM: the last funding rate (quote per base,
+    /// FUNDING_RATE_PRECISION), mark and oracle TWAPs (PRICE_PRECISION) and
+    /// the last funding time and period (seconds). `None` without a usable
+    /// oracle TWAP or period.
+    pub fn from_amm(
+        last_funding_rate: i64,
+        mark_twap: u64,
+        oracle_twap: i64,
+        last_funding_ts: i64,
+        funding_period: i64,
+        now_secs: i64,
+    ) -> Option<Self> {
+        if oracle_twap <= 0 || funding_period <= 0 {
+            return None;
+        }
+        let oracle_twap_f64 = oracle_twap as f64;
+
+        // Quote per base over the price, both scaled to PRICE_PRECISION
+        let price_ratio = FUNDING_RATE_PRECISION as f64 / PRICE_PRECISION as f64;
+        let last_rate = last_funding_rate as f64 / price_ratio / oracle_twap_f64;
+        let predicted_rate =
+            (mark_twap as f64 - oracle_twap_f64) / oracle_twap_f64 / FUNDING_PERIODS_PER_DAY;
+
+        // Funding settles on period boundaries
+        let next_ts = (last_funding_ts.div_euclid(funding_period) + 1) * funding_period;
+        let secs_to_next = (next_ts - now_secs).clamp(0, funding_period) as u64;
+
+        Some(Self {
+            last_rate,
+            predicted_rate,
+            period_secs: funding_period as u64,
+            secs_to_next,
+        })
+    }
+
+    /// Expected rate per period: the predicted rate close to the next
+    /// payment, blending toward the last rate early in the period while the
+    /// TWAPs behind the prediction are mostly last period's
+    pub fn expected_rate(&self) -> f64 {
+        let elapsed = 1.0 - self.secs_to_next as f64 / self.period_secs.max(1) as f64;
+        elapsed * self.predicted_rate + (1.0 - elapsed) * self.last_rate
+    }
+
+    /// Expected funding paid by a long over `holding_secs`, as a fraction of notional
+    pub fn carry(&self, holding_secs: u64) -> f64 {
+        self.expected_rate() * holding_secs as f64 / self.period_secs.max(1) as f64
+    }
+}
+
+impl fmt::Display for FundingRates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "last {:.4}%, predicted {:.4}%, next in {}s",
+            self.last_rate * 100.0,
+            self.predicted_rate * 100.0,
+            self.secs_to_next
+        )
+    }
+}
+
+/// Reservation price shift (BPS of oracle price): down when longs pay so
+/// quotes lean short, up when shorts pay
+pub fn funding_skew_bps(rates: &FundingRates, config: &FundingConfig) -> f64 {
+    let carry_bps = rates.carry(config.holding_secs) * 10_000.0 * config.weight;
+    if !carry_bps.is_finite() {
+        return 0.0;
+    }
+    (-carry_bps).clamp(-config.max_skew_bps, config.max_skew_bps)
+}
+
+/// Shift both sides of a quote by `skew_bps` of the oracle price
+pub fn skew_quote(quote: &Quote, oracle_price: f64, skew_bps: f64) -> Quote {
+    let shift = oracle_price * skew_bps / 10_000.0;
+    Quote {
+        bid_price: quote.bid_price + shift,
+        ask_price: quote.ask_price + shift,
+        ..*quote
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rates_from_amm() {
+        // $0.01 per base at a $100 oracle TWAP, mark TWAP 0.24% above the oracle
+        let rates =
+            FundingRates::from_amm(10_000_000, 100_240_000, 100_000_000, 7_200, 3_600, 9_000)
+                .unwrap();
+        assert!((rates.last_rate - 0.0001).abs() < 1e-12);
+        assert!((rates.predicted_rate - 0.0001).abs() < 1e-12);
+        // Next payment on the hour after the last one
+        assert_eq!(rates.secs_to_next, 1_800);
+        assert_eq!(rates.period_secs, 3_600);
+
+        // Late funding updates don't push the next payment out
+        let rates = FundingRates::from_amm(0, 100, 100, 7_230, 3_600, 10_790).unwrap();
+        assert_eq!(rates.secs_to_next, 10);
+
+        assert!(FundingRates::from_amm(0, 100, 0, 0, 3_600, 0).is_none());
+        assert!(FundingRates::from_amm(0, 100, 100, 0, 0, 0).is_none());
+    }
+
+    #[test]
+    fn test_funding_skew() {
+        let mut rates = FundingRates {
+            last_rate: 0.0,
+            predicted_rate: 0.0002,
+            period_secs: 3_600,
+            secs_to_next: 900,
+        };
+        let config = FundingConfig {
+            holding_secs: 7_200,
+            weight: 0.5,
+            max_skew_bps: 5.0,
+        };
+
+        // 3/4 through the period: 1.5 bps/period expected, 2 periods, half weight
+        assert!((rates.expected_rate() - 0.00015).abs() < 1e-12);
+        assert!((funding_skew_bps(&rates, &config) + 1.5).abs() < 1e-9);
+
+        // Shorts paying lean long, bounded either way
+        rates.predicted_rate = -0.01;
+        assert_eq!(funding_skew_bps(&rates, &config), 5.0);
+        rates.predicted_rate = 0.01;
+        assert_eq!(funding_skew_bps(&rates, &config), -5.0);
+
+        // Right after a payment the last rate dominates
+        rates.secs_to_next = 3_600;
+        rates.last_rate = 0.0;
+        assert_eq!(funding_skew_bps(&rates, &config), 0.0);
+    }
+
+    #[test]
+    fn test_skew_quote() {
+        let quote = Quote {
+            bid_price: 99.9,
+            bid_size: 1.0,
+            ask_price: 100.1,
+            ask_size: 2.0,
+        };
+        let skewed = skew_quote(&quote, 100.0, -2.0);
+        assert!((skewed.bid_price - 99.88).abs() < 1e-9);
+        assert!((skewed.ask_price - 100.08).abs() < 1e-9);
+        assert!((skewed.spread() - quote.spread()).abs() < 1e-9);
+        assert_eq!(skewed.ask_size, 2.0);
+    }
+}
@@ -0,0 +1,251 @@
+//! Multi-level quote ladders built around the quote model's top of book.
+
+use anyhow::{bail, Result};
+use bot_common::validate::{ensure_non_negative, ensure_positive};
+use serde::Deserialize;
+
+use crate::quote::Quote;
+
+/// How far each ladder level sits beyond the top level
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LevelSpacing {
+    // Level i sits `i * step_bps` beyond the top level
+    Linear { step_bps: f64 },
+    // Gaps between levels grow by `ratio`: step, step * ratio, step * ratio^2, ...
+    Geometric { step_bps: f64, ratio: f64 },
+    // Explicit offset (bps) of every level, starting with the top level
+    Explicit(Vec<f64>),
+}
+
+/// Ladder configuration for one market
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LadderConfig {
+    // Number of levels per side
+    pub levels: usize,
+    // Distance between levels, in bps of the oracle price
+    pub spacing: LevelSpacing,
+    // Size multiplier per level relative to the quoted size, last value repeats
+    pub size_multipliers: Vec<f64>,
+}
+
+impl Default for LadderConfig {
+    /// Single level, i.e. one bid and one ask
+    fn default() -> Self {
+        Self {
+            levels: 1,
+            spacing: LevelSpacing::Linear { step_bps: 0.0 },
+            size_multipliers: vec![1.0],
+        }
+    }
+}
+
+impl LadderConfig {
+    /// Check the settings are usable
+    pub fn validate(&self) -> Result<()> {
+        if self.levels == 0 {
+            bail!("levels must be at least 1");
+        }
+        for multiplier in &self.size_multipliers {
+            ensure_positive("size_multipliers", *multiplier)?;
+        }
+        match &self.spacing {
+            LevelSpacing::Linear { step_bps } => ensure_non_negative("step_bps", *step_bps)?,
+            LevelSpacing::Geometric { step_bps, ratio } => {
+                ensure_non_negative("step_bps", *step_bps)?;
+                ensure_positive("ratio", *ratio)?;
+            }
+            LevelSpacing::Explicit(offsets) => {
+                if offsets.len() < self.levels {
+                    bail!(
+                        "{} levels but only {} explicit offsets",
+                        self.levels,
+                        offsets.len()
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Offset of a level beyond the top level (bps), `None` past the last level
+    pub fn offset_bps(&self, level: usize) -> Option<f64> {
+        if level >= self.levels {
+            return None;
+        }
+
+        match &self.spacing {
+            LevelSpacing::Linear { step_bps } => Some(step_bps * level as f64),
+            LevelSpacing::Geometric { step_bps, ratio } => {
+                if (ratio - 1.0).abs() < f64::EPSILON {
+                    Some(step_bps * level as f64)
+                } else {
+                    Some(step_bps * (ratio.powi(level as i32) - 1.0) / (ratio - 1.0))
+                }
+            }
+            LevelSpacing::Explicit(offsets) => offsets.get(level).copied(),
+        }
+    }
+
+    /// Size multiplier for a level
+    pub fn size_multiplier(&self, level: usize) -> f64 {
+        self.size_multipliers
+            .get(level)
+            .or(self.size_multipliers.last())
+            .copied()
+            .unwrap_or(1.0)
+    }
+}
+
+/// Single ladder level (quote price, base size)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LadderLevel {
+    pub price: f64,
+    pub size: f64,
+}
+
+/// Bid and ask levels, closest to the mid first
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Ladder {
+    pub bids: Vec<LadderLevel>,
+    pub asks: Vec<LadderLevel>,
+}
+
+impl Ladder {
+    /// Expand a quote into ladder levels. Offsets are relative to the quoted
+    /// bid/ask so inventory skew and dynamic sizing carry through every level.
+    pub fn build(quote: &Quote, oracle_price: f64, config: &LadderConfig) -> Self {
+        let mut ladder = Self::default();
+
+        for level in 0.. {
+            let Some(offset_bps) = config.offset_bps(level) else {
+                break;
+            };
+            let offset = oracle_price * offset_bps / 10_000.0;
+            let size_mult = config.size_multiplier(level);
+
+            // Skip sides the quote model turned off
+            if quote.bid_size > 0.0 {
+                ladder.bids.push(LadderLevel {
+                    price: quote.bid_price - offset,
+                    size: quote.bid_size * size_mult,
+                });
+            }
+            if quote.ask_size > 0.0 {
+                ladder.asks.push(LadderLevel {
+                    price: quote.ask_price + offset,
+                    size: quote.ask_size * size_mult,
+                });
+            }
+        }
+
+        ladder
+    }
+
+    pub fn len(&self) -> usize {
+        self.bids.len() + self.asks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bids.is_empty() && self.asks.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote() -> Quote {
+        Quote {
+            bid_price: 99.0,
+            bid_size: 1.0,
+            ask_price: 101.0,
+            ask_size: 2.0,
+        }
+    }
+
+    #[test]
+    fn test_default_is_single_level() {
+        let ladder = Ladder::build(&quote(), 100.0, &LadderConfig::default());
+
+        assert_eq!(
+            ladder.bids,
+            vec![LadderLevel {
+                price: 99.0,
+                size: 1.0
+            }]
+        );
+        assert_eq!(
+            ladder.asks,
+            vec![LadderLevel {
+                price: 101.0,
+                size: 2.0
+            }]
+        );
+    }
+
+    #[test]
+    fn test_spacing() {
+        let linear = LadderConfig {
+            levels: 3,
+            spacing: LevelSpacing::Linear { step_bps: 10.0 },
+            size_multipliers: vec![1.0],
+        };
+        let offsets: Vec<_> = (0..4).map(|i| linear.offset_bps(i)).collect();
+        assert_eq!(offsets, vec![Some(0.0), Some(10.0), Some(20.0), None]);
+
+        let geometric = LadderConfig {
+            spacing: LevelSpacing::Geometric {
+                step_bps: 10.0,
+                ratio: 2.0,
+            },
+            ..linear.clone()
+        };
+        let offsets: Vec<_> = (0..3).map(|i| geometric.offset_bps(i)).collect();
+        assert_eq!(offsets, vec![Some(0.0), Some(10.0), Some(30.0)]);
+
+        // Explicit list caps the number of levels
+        let explicit = LadderConfig {
+            spacing: LevelSpacing::Explicit(vec![0.0, 5.0]),
+            ..linear
+        };
+        assert_eq!(explicit.offset_bps(1), Some(5.0));
+        assert_eq!(explicit.offset_bps(2), None);
+    }
+
+    #[test]
+    fn test_ladder_levels_follow_quote() {
+        let config = LadderConfig {
+            levels: 3,
+            spacing: LevelSpacing::Linear { step_bps: 10.0 },
+            size_multipliers: vec![1.0, 2.0],
+        };
+        let ladder = Ladder::build(&quote(), 100.0, &config);
+
+        assert_eq!(ladder.len(), 6);
+        assert_eq!(
+            ladder.bids[2],
+            LadderLevel {
+                price: 98.8,
+                size: 2.0
+            }
+        );
+        assert_eq!(
+            ladder.asks[1],
+            LadderLevel {
+                price: 101.1,
+                size: 4.0
+            }
+        );
+
+        // Zero-sized side is dropped entirely
+        let one_sided = Quote {
+            bid_size: 0.0,
+            ..quote()
+        };
+        let ladder = Ladder::build(&one_sided, 100.0, &config);
+        assert!(ladder.bids.is_empty());
+        assert_eq!(ladder.asks.len(), 3);
+    }
+}
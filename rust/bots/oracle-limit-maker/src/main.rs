@@ -5,32 +5,68 @@
 //! Example bot demonstrating Drift Protocol's Rust SDK with oracle limit market making.
 //!
 //! ## Strategy
-//! - Places oracle limit order based on L2 best bid/ask from DLOB
+//! - Places oracle limit orders based on L2 best bid/ask from DLOB
+//! - Quotes several perp markets from one process, each with its own sizing
 //! - Uses inventory skewing to manage position risk:
 //!   - Long position: widen bids, tighten asks (encourage selling)
 //!   - Short position: tighten bids, widen asks (encourage buying)
 //! - Dynamic order sizing: reduces size on position side as inventory grows
+//! - Reacts to gRPC oracle, slot and orderbook updates instead of polling
 //!
 //! ## Configuration
+//! Quoting parameters are read from a TOML or YAML file (`config.toml` by
+//! default, see `--help` for CLI/env overrides).
+//!
 //! Set environment variables:
 //! - RPC_ENDPOINT: Solana RPC endpoint
-//! - PRIVATE_KEY: Base58 encoded private key
+//! - PRIVATE_KEY: Base58 encoded private key (optional in paper and record-only modes)
 //! - GRPC_URL: GRPC endpoint for orderbook streaming
 //! - GRPC_X_TOKEN: Authentication token for GRPC
 //!
 //! ## Usage
-//! Press Ctrl+C for graceful shutdown (cancels orders and closes position).
+//! Press Ctrl+C or send SIGTERM/SIGHUP for graceful shutdown (cancels orders and
+//! applies the shutdown policy). Fatal errors and panics shut down the same way.
+//! Send SIGUSR1 to resume quoting after the kill switch trips.
+//! Send SIGUSR2 (or run with `--watch-config`) to reload parameters without restarting.
+//! Run with `--paper` to simulate fills against the live DLOB without sending orders.
+//! Run with `--record <DIR>` to record market data, quotes and fills for research.
+//! Run with `--backtest <FILE>` to replay recorded market data offline.
 
+mod avellaneda;
+mod backtest;
+mod config;
+mod events;
+mod fills;
+mod funding;
+mod ladder;
 mod maker;
+mod metrics;
+mod oracle_guard;
+mod orders;
+mod paper;
+mod pipeline;
+mod precision;
+mod quote;
+mod reconcile;
+mod recorder;
+mod reload;
+mod risk;
+mod volatility;
 
 use anyhow::Result;
+use bot_common::supervisor;
+use clap::Parser;
+use config::{BotConfig, Cli};
 use dotenv::dotenv;
 use env_logger::Builder;
 use log::info;
-use maker::{BotConfig, OracleLimitMakerBot};
+use maker::OracleLimitMakerBot;
+use std::process::ExitCode;
+use std::sync::atomic::Ordering;
+use tokio::signal::unix::{signal, SignalKind};
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> Result<ExitCode> {
     // Load environment variables
     dotenv().ok();
 
@@ -40,41 +76,84 @@ async fn main() -> Result<()> {
 
     info!("Starting Oracle Limit Market Maker Bot");
 
-    let config = BotConfig {
-        // Market and sizing
-        target_market: "BTC-PERP".to_string(),
-        order_size: 0.001,
-        max_position_size: 0.01,
-        spread_multiplier: 1.5,
+    // Load config file with CLI/env overrides
+    let cli = Cli::parse();
+    let config = BotConfig::load(&cli)?;
+    info!("Loaded config from {}: {:#?}", cli.config.display(), config);
 
-        // Update thresholds
-        debounce_ms: 1000,
-        oracle_change_threshold_bps: 0.5,
+    if cli.check {
+        info!("Config is valid");
+        return Ok(ExitCode::SUCCESS);
+    }
 
-        // Account
-        authority: None,
-        subaccount_id: 0,
-    };
+    // Replay recorded data offline, no RPC or keys needed
+    if let Some(data) = &cli.backtest {
+        let records = backtest::read_records(data)?;
+        info!(
+            "Replaying {} records from {}",
+            records.len(),
+            data.display()
+        );
+        let reports = backtest::run(&config, &records)?;
+        for report in &reports {
+            println!("{}", report);
+        }
+        if let Some(output) = &cli.backtest_output {
+            backtest::write_inventory_csv(output, &reports)?;
+            info!("Wrote inventory path to {}", output.display());
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
 
     // Initialize bot
+    let metrics_addr = config.metrics_addr;
     let mut bot = OracleLimitMakerBot::new(config).await?;
 
-    // Start trading with graceful shutdown handling
-    tokio::select! {
-        result = bot.start() => {
-            if let Err(e) = result {
-                log::error!("Bot encountered fatal error: {}", e);
-                std::process::exit(1);
+    // Serve Prometheus metrics if configured
+    if let Some(addr) = metrics_addr {
+        let metrics = bot.metrics();
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(addr, metrics).await {
+                log::error!("Metrics server stopped: {}", e);
             }
+        });
+    }
+
+    // Reload parameters on SIGUSR2 or config file changes
+    let reload_tx = bot.reload_sender();
+    tokio::spawn(async move {
+        if let Err(e) = reload::watch_config(cli, reload_tx).await {
+            log::error!("Config watcher stopped: {}", e);
         }
-        _ = tokio::signal::ctrl_c() => {
-            info!("Received Ctrl+C signal, initiating graceful shutdown...");
-            if let Err(e) = bot.stop().await {
-                log::error!("Error during shutdown: {}", e);
-            }
-            info!("Shutdown completed successfully");
+    });
+
+    // Operator resets a tripped kill switch with SIGUSR1
+    let risk_reset = bot.risk_reset_handle();
+    let mut reset_signal = signal(SignalKind::user_defined1())?;
+    tokio::spawn(async move {
+        while reset_signal.recv().await.is_some() {
+            info!("Received SIGUSR1, resetting kill switch");
+            risk_reset.store(true, Ordering::SeqCst);
+        }
+    });
+
+    // Trade until a signal, a fatal error or a panic, then always run the
+    // cancel path
+    let exit = supervisor::run(bot.start(), &supervisor::STOP_SIGNALS).await?;
+    match &exit {
+        supervisor::Exit::Signal(_) | supervisor::Exit::Finished => {
+            info!("Stopping: {}", exit)
         }
+        _ => log::error!("Stopping: {}", exit),
+    }
+
+    let timeout = bot.shutdown_timeout();
+    let cleanup = supervisor::cleanup(bot.stop(), timeout).await;
+    if cleanup.is_confirmed() {
+        info!("Shutdown completed: {}", cleanup);
+    } else {
+        log::error!("Shutdown incomplete: {}", cleanup);
     }
 
-    Ok(())
+    Ok(ExitCode::from(supervisor::exit_code(&exit, &cleanup)))
 }
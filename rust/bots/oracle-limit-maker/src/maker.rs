@@ -1,48 +1,135 @@
 use std::{
     env,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use bot_common::shutdown::{self, PositionCloser, ShutdownPolicy, ShutdownState, SHUTDOWN_POLL};
+use bot_common::tx::{TxOutcome, TxSender, TxStatus};
+use bot_common::units::{BaseAmount, Price, QuoteAmount};
 use drift_rs::{
     dlob::{builder::DLOBBuilder, DLOB},
-    math::constants::{BASE_PRECISION, QUOTE_PRECISION},
+    event_subscriber::{DriftEvent, EventSubscriber},
+    grpc::{AccountFilter, AccountUpdate},
     types::{
-        Context, MarketId, MarketType, OrderParams, OrderType, PerpPosition, PositionDirection,
-        PostOnlyParam, RpcSendTransactionConfig,
+        Context, MarketId, MarketType, ModifyOrderParams, OrderParams, OrderStatus, OrderType,
+        PerpPosition, PositionDirection, PostOnlyParam,
     },
-    DriftClient, GrpcSubscribeOpts, Pubkey, RpcClient, Wallet,
+    DriftClient, GrpcSubscribeOpts, Pubkey, RpcClient, TransactionBuilder, Wallet,
 };
+use futures_util::{future::join_all, StreamExt};
 use log::{error, info, warn};
-use solana_sdk::commitment_config::CommitmentLevel;
+use solana_sdk::{
+    commitment_config::CommitmentLevel, message::VersionedMessage, packet::PACKET_DATA_SIZE,
+};
 use std::str::FromStr;
+use tokio::sync::mpsc;
+
+use crate::avellaneda::AvellanedaStoikovModel;
+use crate::backtest::MarketRecord;
+use crate::config::{BotConfig, MarketConfig, Network};
+use crate::events::{UpdateNotifier, UpdateSource, Wakeup};
+use crate::fills::{Fill, FillTracker, Side};
+use crate::funding::{funding_skew_bps, FundingRates};
+use crate::metrics::Metrics;
+use crate::oracle_guard::{GuardTrip, OracleCheck, OracleGuard, OracleReading};
+use crate::orders::{OrderDiff, QuoteOrder};
+use crate::paper::PaperExchange;
+use crate::pipeline::{plan_quotes, requote_trigger};
+use crate::precision::MarketPrecision;
+use crate::quote::{L2Level, MarketSnapshot, QuoteModel, SpreadMultiplierModel};
+use crate::reconcile::{OnChainOrder, Reconciliation};
+use crate::recorder::Recorder;
+use crate::reload::config_changes;
+use crate::risk::{KillSwitch, RiskSnapshot};
+use crate::volatility::RealizedVol;
 
-/// Bot configuration parameters
-#[derive(Debug, Clone)]
-pub struct BotConfig {
-    // Market symbol
-    pub target_market: String,
-    // Amount per order (base units)
-    pub order_size: f64,
-    // Maximum position size before skewing
-    pub max_position_size: f64,
-    // Multiplier for market spread (e.g. 1.5 = 150% of market spread)
-    pub spread_multiplier: f64,
-    // Minimum time between oracle updates
-    pub debounce_ms: u64,
-    // Minimum price change to trigger update (BPS)
-    pub oracle_change_threshold_bps: f32,
-    // Authority pubkey (for delegation)
-    pub authority: Option<String>,
-    // Subaccount ID
-    pub subaccount_id: u16,
-}
 /// Runtime state
 #[derive(Default)]
 struct State {
-    prev_oracle_price: i64,
-    last_update_time: u64,
     is_running: bool,
+    fills: FillTracker,
+    last_reconcile_time: u64,
+    last_risk_check_time: u64,
+    // Id of the last batch of transactions sent
+    last_tx_batch: u64,
+}
+
+/// Transactions in flight for a market
+struct PendingTxs {
+    batch: u64,
+    // Resting orders once every transaction confirms, None if a fill made them unknown
+    next_live: Option<Vec<QuoteOrder>>,
+}
+
+/// Outcomes of a batch of transactions sent for a market
+struct TxReport {
+    market: usize,
+    batch: u64,
+    outcomes: Vec<TxOutcome>,
+}
+
+/// Per-market runtime state
+struct MarketState {
+    config: MarketConfig,
+    market_id: MarketId,
+    model: Box<dyn QuoteModel>,
+    volatility: RealizedVol,
+    // First user order id of this market's ladder slots
+    user_order_id_base: u8,
+    // Resting quotes, None when unknown and a full cancel/replace is needed
+    live_orders: Option<Vec<QuoteOrder>>,
+    // Sent but not yet confirmed, the market isn't requoted until they are
+    pending: Option<PendingTxs>,
+    oracle_guard: Option<OracleGuard>,
+    // Tick, step and minimum order size from the market account
+    precision: MarketPrecision,
+    // Spread multiplier from oracle guards, 1.0 when the oracle is healthy
+    oracle_widen: f64,
+    // Quotes cancelled by an oracle guard
+    quotes_pulled: bool,
+    prev_oracle_price: Price,
+    last_update_time: u64,
+}
+
+impl MarketState {
+    /// Check if quotes should be updated based on oracle price change and debounce
+    fn should_update(&self, bot_config: &BotConfig, new_price: Price) -> bool {
+        let now = get_current_timestamp_ms();
+        let Some(change_bps) = requote_trigger(
+            bot_config,
+            self.prev_oracle_price,
+            self.last_update_time,
+            now,
+            new_price,
+        ) else {
+            return false;
+        };
+
+        if !self.prev_oracle_price.is_zero() {
+            info!(
+                "[{}] Update triggered, oracle moved {} in {:.1}s",
+                self.config.symbol,
+                change_bps,
+                (now - self.last_update_time) as f64 / 1000.0
+            );
+        }
+        true
+    }
+
+    /// When a price change held back by the debounce can next requote (ms),
+    /// `None` if nothing is waiting on the debounce
+    fn debounce_deadline(&self, bot_config: &BotConfig, new_price: Price) -> Option<u64> {
+        let deadline = self.last_update_time + bot_config.debounce_ms;
+        (!self.prev_oracle_price.is_zero()
+            && new_price != self.prev_oracle_price
+            && get_current_timestamp_ms() < deadline)
+            .then_some(deadline)
+    }
 }
 
 /// Oracle-based market maker bot
@@ -50,44 +137,162 @@ pub struct OracleLimitMakerBot {
     config: BotConfig,
     client: DriftClient,
     dlob: &'static DLOB,
-    market_id: MarketId,
+    markets: Vec<MarketState>,
     state: State,
+    fill_rx: mpsc::UnboundedReceiver<Fill>,
+    kill_switch: KillSwitch,
+    // Set by the operator to reset a tripped kill switch
+    risk_reset: Arc<AtomicBool>,
+    // Validated configs to apply between update cycles
+    reload_tx: mpsc::UnboundedSender<BotConfig>,
+    reload_rx: mpsc::UnboundedReceiver<BotConfig>,
+    metrics: Arc<Metrics>,
+    // Simulated exchange in paper mode, nothing is sent on-chain
+    paper: Option<PaperExchange>,
+    // Writes market data, quotes and fills to disk when configured
+    recorder: Option<Recorder>,
+    // Wakes the trading loop on oracle, slot, DLOB, fill and transaction updates
+    updates: Arc<UpdateNotifier>,
+    tx_sender: TxSender,
+    // Confirmation outcomes of transactions sent in the background
+    tx_report_tx: mpsc::UnboundedSender<TxReport>,
+    tx_report_rx: mpsc::UnboundedReceiver<TxReport>,
 }
 
-// Local precision constants as f64
-const QUOTE_PRECISION_F64: f64 = QUOTE_PRECISION as f64;
-const BASE_PRECISION_F64: f64 = BASE_PRECISION as f64;
+// Number of L2 levels per side passed to the quote model
+const L2_DEPTH: usize = 20;
+
+// Skip reconciling markets updated this recently, their orders may not have landed yet
+const RECONCILE_GRACE_MS: u64 = 2_000;
+
+// Risk checks between fills, positions are re-valued at the latest oracle prices
+const RISK_CHECK_INTERVAL_MS: u64 = 1_000;
+
+// Longest the trading loop waits without an update, bounds how late risk checks,
+// reconciliation, config reloads and recording run when the market is quiet
+const IDLE_WAKEUP: Duration = Duration::from_secs(1);
 
 impl OracleLimitMakerBot {
-    /// Initialize the bot with client and subscriptions
+    /// Initialize the bot with the configured quote model
     pub async fn new(config: BotConfig) -> Result<Self> {
+        match config.avellaneda_stoikov.clone() {
+            Some(as_config) => {
+                Self::with_quote_model(config, move |_| {
+                    Box::new(AvellanedaStoikovModel::new(as_config.clone()))
+                })
+                .await
+            }
+            None => Self::with_quote_model(config, |_| Box::new(SpreadMultiplierModel)).await,
+        }
+    }
+
+    /// Initialize the bot with client, subscriptions and a custom quote model per market
+    pub async fn with_quote_model<F>(config: BotConfig, model_factory: F) -> Result<Self>
+    where
+        F: Fn(&MarketConfig) -> Box<dyn QuoteModel>,
+    {
         // Load environment variables
         let rpc_endpoint = env::var("RPC_ENDPOINT").expect("RPC_ENDPOINT not set");
-        let private_key = env::var("PRIVATE_KEY").expect("PRIVATE_KEY not set");
+        let private_key = env::var("PRIVATE_KEY").ok();
         let grpc_url = env::var("GRPC_URL").expect("GRPC_URL not set");
         let grpc_token = env::var("GRPC_X_TOKEN").expect("GRPC_X_TOKEN not set");
 
-        info!("Initializing market maker for '{}'", config.target_market);
+        config.validate()?;
+
+        let symbols: Vec<&str> = config.markets.iter().map(|m| m.symbol.as_str()).collect();
+        info!("Initializing market maker for {:?}", symbols);
 
         // Create drift client
-        let context = Context::MainNet;
-        let wallet = Wallet::try_from_str(&private_key)?;
+        let context = match config.network {
+            Network::Mainnet => Context::MainNet,
+            Network::Devnet => Context::DevNet,
+        };
+        // Paper and record-only modes only read market data and don't need a key
+        let sends_orders = config.paper.is_none() && !config.record_only();
+        let wallet = match private_key {
+            Some(private_key) => Wallet::try_from_str(&private_key)?,
+            None if !sends_orders => Wallet::read_only(Pubkey::default()),
+            None => anyhow::bail!("PRIVATE_KEY not set"),
+        };
         let rpc_client = RpcClient::new(rpc_endpoint);
         let client = DriftClient::new(context, rpc_client, wallet).await?;
 
         info!("Drift client initialized");
 
-        // Get market ID
-        let market_id = client
-            .market_lookup(&config.target_market)
-            .ok_or_else(|| anyhow::anyhow!("Market '{}' not found", config.target_market))?;
+        // Keep enough oracle history for the model and every adaptive spread horizon
+        let volatility_window_ms = config.volatility_window_ms();
+
+        // Get market IDs, each ladder slot gets a fixed user order id
+        let mut next_user_order_id = 1u8;
+        let mut markets = Vec::with_capacity(config.markets.len());
+        for market_config in &config.markets {
+            let market_id = client
+                .market_lookup(&market_config.symbol)
+                .ok_or_else(|| anyhow::anyhow!("Market '{}' not found", market_config.symbol))?;
+
+            if market_id.kind() != MarketType::Perp {
+                anyhow::bail!("Market '{}' is not a perp market", market_config.symbol);
+            }
+            if markets
+                .iter()
+                .any(|m: &MarketState| m.market_id == market_id)
+            {
+                anyhow::bail!("Market '{}' configured twice", market_config.symbol);
+            }
 
-        info!("Found market: {} -> {:?}", config.target_market, market_id);
+            info!("Found market: {} -> {:?}", market_config.symbol, market_id);
+
+            markets.push(MarketState {
+                config: market_config.clone(),
+                market_id,
+                model: model_factory(market_config),
+                volatility: RealizedVol::new(volatility_window_ms),
+                user_order_id_base: next_user_order_id,
+                live_orders: None,
+                pending: None,
+                oracle_guard: config.oracle_guard.clone().map(OracleGuard::new),
+                precision: MarketPrecision::default(),
+                oracle_widen: 1.0,
+                quotes_pulled: false,
+                prev_oracle_price: Price::ZERO,
+                last_update_time: 0,
+            });
+            next_user_order_id += (market_config.ladder.levels * 2) as u8;
+        }
 
         // Setup DLOB builder
-        let dlob_builder = DLOBBuilder::new(vec![market_id]);
+        let dlob_builder = DLOBBuilder::new(markets.iter().map(|m| m.market_id).collect());
+
+        // Oracle accounts of the quoted markets, updates to these wake the loop.
+        // Quotes are rounded to each market's order precision.
+        let mut oracles = Vec::with_capacity(markets.len());
+        for market in &mut markets {
+            let perp_market = client
+                .get_perp_market_account(market.market_id.index())
+                .await?;
+            oracles.push(perp_market.amm.oracle);
+            market.precision = MarketPrecision::new(
+                perp_market.amm.order_tick_size,
+                perp_market.amm.order_step_size,
+                perp_market.amm.min_order_size,
+            );
+            info!(
+                "[{}] Tick size {}, step size {}, min order size {}",
+                market.config.symbol,
+                market.precision.tick_size,
+                market.precision.step_size,
+                market.precision.min_order_size
+            );
+        }
 
-        // Subscribe via GRPC
+        // Subscribe via GRPC. Oracle and slot updates wake the loop; user account
+        // updates arrive for every account on the exchange, so they are only
+        // counted and the book is read on the next wakeup (at least every slot).
+        let updates = Arc::new(UpdateNotifier::default());
+        let on_user_account = dlob_builder.account_update_handler(client.backend().account_map());
+        let on_slot = dlob_builder.slot_update_handler(client.clone());
+        let (book_updates, slot_updates, oracle_updates) =
+            (updates.clone(), updates.clone(), updates.clone());
         client
             .grpc_subscribe(
                 grpc_url,
@@ -95,293 +300,1131 @@ impl OracleLimitMakerBot {
                 GrpcSubscribeOpts::default()
                     .commitment(CommitmentLevel::Processed)
                     .usermap_on()
-                    .on_user_account(
-                        dlob_builder.account_update_handler(client.backend().account_map()),
-                    )
-                    .on_slot(dlob_builder.slot_update_handler(client.clone())),
+                    .on_user_account(move |update: &AccountUpdate| {
+                        on_user_account(update);
+                        book_updates.record(UpdateSource::Book);
+                    })
+                    .on_slot(move |new_slot: u64| {
+                        on_slot(new_slot);
+                        slot_updates.notify(UpdateSource::Slot);
+                    })
+                    .on_account(
+                        AccountFilter::partial().with_accounts(oracles.into_iter()),
+                        move |_: &AccountUpdate| oracle_updates.notify(UpdateSource::Oracle),
+                    ),
                 true,
             )
             .await?;
 
         let dlob = dlob_builder.dlob();
 
+        // Subscribe to fill events for our subaccount, paper fills come from the simulator
+        let fill_rx = if sends_orders {
+            let subaccount = subaccount_pubkey(&client, &config);
+            Self::subscribe_fills(&client, subaccount, updates.clone()).await?
+        } else {
+            mpsc::unbounded_channel().1
+        };
+
         info!("Subscriptions active, DLOB ready");
+        if config.record_only() {
+            warn!("Record-only mode: market data is recorded, no quotes are computed or sent");
+        } else if config.paper.is_some() {
+            warn!("Paper trading: quotes are filled against the live DLOB, no orders are sent");
+        }
+        let recorder = config.recorder.clone().map(Recorder::start).transpose()?;
+
+        let (reload_tx, reload_rx) = mpsc::unbounded_channel();
+        let (tx_report_tx, tx_report_rx) = mpsc::unbounded_channel();
 
         Ok(Self {
+            kill_switch: KillSwitch::new(config.risk_limits.clone()),
+            paper: config.paper.clone().map(PaperExchange::new),
+            recorder,
+            tx_sender: TxSender::new(client.clone(), config.tx.clone()),
             config,
             client,
             dlob,
-            market_id,
+            markets,
             state: State::default(),
+            fill_rx,
+            risk_reset: Arc::new(AtomicBool::new(false)),
+            reload_tx,
+            reload_rx,
+            metrics: Arc::new(Metrics::new()?),
+            updates,
+            tx_report_tx,
+            tx_report_rx,
         })
     }
 
-    /// Main trading loop
-    async fn trading_loop(&mut self) -> Result<()> {
-        info!("Trading loop started");
+    /// Forward perp fill events for the subaccount into a channel
+    async fn subscribe_fills(
+        client: &DriftClient,
+        subaccount: Pubkey,
+        updates: Arc<UpdateNotifier>,
+    ) -> Result<mpsc::UnboundedReceiver<Fill>> {
+        let mut events = EventSubscriber::subscribe(client.ws(), subaccount).await?;
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            while let Some(event) = events.next().await {
+                let DriftEvent::OrderFill {
+                    side,
+                    fee,
+                    base_asset_amount_filled,
+                    quote_asset_amount_filled,
+                    market_index,
+                    market_type,
+                    ..
+                } = event
+                else {
+                    continue;
+                };
+
+                if market_type != MarketType::Perp || base_asset_amount_filled == 0 {
+                    continue;
+                }
 
-        self.state.is_running = true;
-        while self.state.is_running {
-            // Get current oracle price
-            let oracle = self
-                .client
-                .try_get_oracle_price_data_and_slot(self.market_id)
-                .ok_or_else(|| anyhow::anyhow!("Failed to get oracle price"))?;
-            let current_oracle_price = oracle.data.price;
-
-            // Check if we should update quotes
-            if self.should_update(current_oracle_price) {
-                if let Err(e) = self.process_update(current_oracle_price).await {
-                    error!("Update failed: {}", e);
+                let (Some(size), Some(quote)) = (
+                    BaseAmount::from_unsigned(base_asset_amount_filled),
+                    QuoteAmount::from_unsigned(quote_asset_amount_filled),
+                ) else {
+                    warn!("Fill in market {} out of range, skipped", market_index);
+                    continue;
+                };
+                let size = size.to_f64();
+                let fill = Fill {
+                    market_index,
+                    side: match side {
+                        PositionDirection::Long => Side::Buy,
+                        PositionDirection::Short => Side::Sell,
+                    },
+                    size,
+                    price: quote.to_f64() / size,
+                    fee: QuoteAmount::from_raw(fee as i64).to_f64(),
+                };
+
+                if tx.send(fill).is_err() {
+                    break;
                 }
+                updates.notify(UpdateSource::Fill);
             }
+            warn!("Fill event stream ended");
+        });
+
+        info!("Fill tracking active for {}", subaccount);
+        Ok(rx)
+    }
 
-            tokio::time::sleep(Duration::from_millis(100)).await;
+    /// Apply fills received since the last cycle
+    fn drain_fills(&mut self) {
+        while let Ok(fill) = self.fill_rx.try_recv() {
+            self.record_fill(fill);
         }
+    }
 
-        info!("Trading loop stopped");
-        Ok(())
+    /// Track a fill's PnL and mark its market's resting quotes as unknown
+    fn record_fill(&mut self, fill: Fill) {
+        info!(
+            "Fill: {:?} {:.6} @ ${:.2} on market {}, fee ${:.4}",
+            fill.side, fill.size, fill.price, fill.market_index, fill.fee
+        );
+
+        let market_index = fill.market_index;
+        let fee = fill.fee;
+        if let (Some(recorder), Some(market)) = (
+            &self.recorder,
+            self.markets
+                .iter()
+                .find(|m| m.market_id.index() == market_index),
+        ) {
+            recorder.record_fill(get_current_timestamp_ms(), &market.config.symbol, &fill);
+        }
+        let realized = self.state.fills.record(fill);
+
+        // Filled quotes no longer match what we track
+        if let Some(market) = self
+            .markets
+            .iter_mut()
+            .find(|m| m.market_id.index() == market_index)
+        {
+            market.live_orders = None;
+            if let Some(pending) = &mut market.pending {
+                pending.next_live = None;
+            }
+            self.metrics
+                .realized_pnl
+                .with_label_values(&[&market.config.symbol])
+                .add(realized - fee);
+        }
+
+        let stats = self.state.fills.stats();
+        info!(
+            "PnL: realized ${:.4}, fees ${:.4}, rebates ${:.4}, net ${:.4}, matches {}, volume ${:.2}, unmatched {:.6}",
+            stats.realized_pnl,
+            stats.fees_paid,
+            stats.rebates_earned,
+            stats.net_pnl(),
+            stats.total_matches,
+            stats.traded_volume,
+            self.state.fills.open_size(market_index)
+        );
     }
 
-    /// Check if quotes should be updated based on oracle price change and debounce
-    fn should_update(&self, new_price: i64) -> bool {
-        let now = get_current_timestamp_ms();
+    /// Fill paper quotes that the live book now trades through
+    fn match_paper_orders(&mut self) {
+        let Some(paper) = self.paper.as_mut() else {
+            return;
+        };
 
-        // Debounce check
-        if now - self.state.last_update_time < self.config.debounce_ms {
-            return false;
+        let mut fills = Vec::new();
+        for market in &self.markets {
+            let Some(oracle) = self
+                .client
+                .try_get_oracle_price_data_and_slot(market.market_id)
+            else {
+                continue;
+            };
+            let market_index = market.market_id.index();
+            let (bids, asks) = l2_levels(self.dlob, market_index, L2_DEPTH);
+            fills.extend(paper.match_orders(
+                market_index,
+                Price::from_raw(oracle.data.price).to_f64(),
+                &bids,
+                &asks,
+            ));
         }
 
-        // Allow first cycle
-        if self.state.prev_oracle_price == 0 {
-            return true;
+        for fill in fills {
+            self.record_fill(fill);
         }
+    }
 
-        // Price change check
-        let price_diff = (new_price - self.state.prev_oracle_price).abs() as f32;
-        let change_bps = (price_diff * 10_000.0) / self.state.prev_oracle_price.abs() as f32;
+    /// Record an oracle tick and L2 snapshot for every market that is due
+    fn record_market_data(&mut self) {
+        let Some(recorder) = self.recorder.as_mut() else {
+            return;
+        };
 
-        if change_bps >= self.config.oracle_change_threshold_bps {
-            info!(
-                "Update triggered, oracle moved {:.2} bps in {:.1}s",
-                change_bps,
-                (now - self.state.last_update_time) as f64 / 1000.0
-            );
-            return true;
+        let now = get_current_timestamp_ms();
+        for market in &self.markets {
+            if !recorder.snapshot_due(&market.config.symbol, now) {
+                continue;
+            }
+            let Some(oracle) = self
+                .client
+                .try_get_oracle_price_data_and_slot(market.market_id)
+            else {
+                continue;
+            };
+            let (bids, asks) = l2_levels(self.dlob, market.market_id.index(), recorder.depth());
+            let level = |l: &L2Level| (l.price, l.size);
+            recorder.record_snapshot(&MarketRecord {
+                ts: now,
+                market: market.config.symbol.clone(),
+                oracle: Price::from_raw(oracle.data.price).to_f64(),
+                confidence: Price::from_unsigned(oracle.data.confidence).map(Price::to_f64),
+                slot: Some(oracle.slot),
+                delay_slots: Some(oracle.data.delay.max(0) as u64),
+                bids: bids.iter().map(level).collect(),
+                asks: asks.iter().map(level).collect(),
+            });
         }
+    }
 
-        false
+    /// Sender for reloaded configs, applied at the start of the next cycle
+    pub fn reload_sender(&self) -> mpsc::UnboundedSender<BotConfig> {
+        self.reload_tx.clone()
     }
 
-    /// Process quote update based on new oracle price
-    async fn process_update(&mut self, new_price: i64) -> Result<()> {
-        let update_start = std::time::Instant::now();
-        let oracle_price = new_price as f64 / QUOTE_PRECISION_F64;
+    /// Limit on the cleanup run by `stop`, from the current config
+    pub fn shutdown_timeout(&self) -> Duration {
+        self.config.shutdown.timeout()
+    }
 
-        // Get L2 orderbook snapshot
-        let l2 = self
-            .dlob
-            .get_l2_snapshot(self.market_id.index(), MarketType::Perp);
+    /// Apply config reloads received since the last cycle
+    fn apply_reloads(&mut self) {
+        while let Ok(config) = self.reload_rx.try_recv() {
+            if let Err(e) = self.apply_config(config) {
+                error!("Config reload rejected, keeping current config: {:#}", e);
+                self.metrics.error("reload");
+            }
+        }
+    }
 
-        // Get best bid and ask
-        let (bid_price, _) = l2
-            .bids
-            .iter()
-            .next_back()
-            .map(|(p, s)| (*p as f64 / QUOTE_PRECISION_F64, *s))
-            .ok_or_else(|| {
-                warn!("Empty bid side");
-                anyhow::anyhow!("No bids in orderbook")
-            })?;
-
-        let (ask_price, _) = l2
-            .asks
-            .iter()
-            .next()
-            .map(|(p, s)| (*p as f64 / QUOTE_PRECISION_F64, *s))
-            .ok_or_else(|| {
-                warn!("Empty ask side");
-                anyhow::anyhow!("No asks in orderbook")
-            })?;
+    /// Swap in a reloaded config. Runs between update cycles so every market
+    /// sees either the old or the new parameters, never a mix.
+    fn apply_config(&mut self, config: BotConfig) -> Result<()> {
+        config.validate()?;
+        let changes = config_changes(&self.config, &config)?;
+        if changes.is_empty() {
+            info!("Config reloaded, no changes");
+            return Ok(());
+        }
+        for change in &changes {
+            info!("Config change: {}", change);
+        }
 
-        let mid_price = (bid_price + ask_price) / 2.0;
-        let current_spread = ask_price - bid_price;
+        let volatility_window_ms = config.volatility_window_ms();
+        for (market, market_config) in self.markets.iter_mut().zip(&config.markets) {
+            market.config = market_config.clone();
+            market.volatility.set_window_ms(volatility_window_ms);
 
-        info!(
-            "L2 snapshot: best_bid ${:.2}, best_ask ${:.2}, spread ${:.4}",
-            bid_price, ask_price, current_spread
-        );
+            if config.avellaneda_stoikov != self.config.avellaneda_stoikov {
+                if let Some(as_config) = &config.avellaneda_stoikov {
+                    market.model = Box::new(AvellanedaStoikovModel::new(as_config.clone()));
+                }
+            }
+            if config.oracle_guard != self.config.oracle_guard {
+                market.oracle_guard = config.oracle_guard.clone().map(OracleGuard::new);
+                market.oracle_widen = 1.0;
+            }
 
-        // Calculate our spread based on market spread
-        let our_spread = current_spread * self.config.spread_multiplier;
+            // Requote with the new parameters on the next cycle
+            market.prev_oracle_price = Price::ZERO;
+        }
 
-        // Get current position
-        let position = self.get_current_position().await?.unwrap_or_default();
-        let base_amount = position.base_asset_amount as f64 / BASE_PRECISION_F64;
-        let position_ratio = base_amount / self.config.max_position_size;
+        self.kill_switch.set_limits(config.risk_limits.clone());
+        self.tx_sender.set_config(config.tx.clone());
+        if let (Some(paper), Some(paper_config)) = (self.paper.as_mut(), &config.paper) {
+            paper.set_config(paper_config.clone());
+        }
+        self.config = config;
 
-        // Calculate dynamic sizing
-        let (bid_size, ask_size) =
-            Self::calculate_dynamic_sizing(self.config.order_size, position_ratio);
+        info!("Config reload applied ({} changes)", changes.len());
+        Ok(())
+    }
 
-        // Calculate inventory skew
-        let (bid_mult, ask_mult) = Self::calculate_inventory_skew(position_ratio);
+    /// Main trading loop
+    async fn trading_loop(&mut self) -> Result<()> {
+        info!("Trading loop started");
 
-        // Calculate our quotes
-        let our_bid = mid_price - (our_spread / 2.0 * bid_mult);
-        let our_ask = mid_price + (our_spread / 2.0 * ask_mult);
+        self.state.is_running = true;
+        // Run the first cycle straight away
+        let mut wait = Duration::ZERO;
+        while self.state.is_running {
+            // Sleep until an update arrives, a debounced requote is due or the idle timeout
+            let wakeup = self.updates.wait(wait).await;
+            self.record_wakeup(&wakeup);
+            wait = IDLE_WAKEUP;
+
+            self.drain_fills();
+            self.drain_tx_reports();
+            self.match_paper_orders();
+            self.apply_reloads();
+            self.record_market_data();
+
+            if self.config.record_only() {
+                continue;
+            }
 
-        // Convert to oracle offsets
-        let bid_offset = ((our_bid - oracle_price) * QUOTE_PRECISION_F64) as i32;
-        let ask_offset = ((our_ask - oracle_price) * QUOTE_PRECISION_F64) as i32;
+            // Halt quoting while the kill switch is tripped
+            if self.risk_reset.swap(false, Ordering::SeqCst) && self.kill_switch.tripped().is_some()
+            {
+                info!("Kill switch reset by operator, resuming quoting");
+                self.kill_switch.reset();
+                // Quote every market on the next cycle
+                for market in &mut self.markets {
+                    market.prev_oracle_price = Price::ZERO;
+                }
+            }
+            // Re-check risk after fills, otherwise on an interval as oracle prices move
+            let now = get_current_timestamp_ms();
+            if wakeup.count(UpdateSource::Fill) > 0
+                || now - self.state.last_risk_check_time >= RISK_CHECK_INTERVAL_MS
+            {
+                self.state.last_risk_check_time = now;
+                if let Err(e) = self.check_risk().await {
+                    error!("Risk check failed: {}", e);
+                    self.metrics.error("risk_check");
+                }
+            }
+            if self.kill_switch.tripped().is_some() {
+                continue;
+            }
 
-        info!(
-            "Position: base={:.4}, ratio={:.3}, bid_mult={:.3}, ask_mult={:.3}",
-            base_amount, position_ratio, bid_mult, ask_mult
-        );
+            // Periodically check resting orders against the user account
+            if let Some(interval) = self.config.reconcile_interval_ms {
+                let now = get_current_timestamp_ms();
+                if now - self.state.last_reconcile_time >= interval {
+                    self.state.last_reconcile_time = now;
+                    if let Err(e) = self.reconcile_orders().await {
+                        error!("Order reconciliation failed: {}", e);
+                        self.metrics.error("reconcile");
+                    }
+                }
+            }
 
-        info!(
-            "Quotes: mid ${:.2}, bid ${:.2} (offset {}), ask ${:.2} (offset {}), spread ${:.4}",
-            mid_price, our_bid, bid_offset, our_ask, ask_offset, our_spread
-        );
+            // Each market runs its own update cycle
+            for idx in 0..self.markets.len() {
+                let market = &mut self.markets[idx];
+
+                // Get current oracle price
+                let Some(oracle) = self
+                    .client
+                    .try_get_oracle_price_data_and_slot(market.market_id)
+                else {
+                    warn!("[{}] Failed to get oracle price", market.config.symbol);
+                    self.metrics.error("oracle");
+                    continue;
+                };
+                let current_oracle_price = Price::from_raw(oracle.data.price);
+                self.metrics
+                    .oracle_price
+                    .with_label_values(&[&market.config.symbol])
+                    .set(current_oracle_price.to_f64());
+
+                // Pull or widen quotes when the oracle can't be trusted
+                let reading = OracleReading {
+                    price: oracle.data.price,
+                    confidence: oracle.data.confidence,
+                    delay_slots: oracle.data.delay.max(0) as u64,
+                };
+                let check = market
+                    .oracle_guard
+                    .as_mut()
+                    .map_or(OracleCheck::Ok, |guard| guard.check(&reading));
+                let widen = match check {
+                    OracleCheck::Ok => 1.0,
+                    OracleCheck::Widen { multiplier, trips } => {
+                        if (multiplier - market.oracle_widen).abs() > 0.1 {
+                            warn!(
+                                "[{}] Oracle guard tripped ({}), widening spread x{:.2}",
+                                market.config.symbol,
+                                format_trips(&trips),
+                                multiplier
+                            );
+                        }
+                        multiplier
+                    }
+                    OracleCheck::Pull(trips) => {
+                        if !market.quotes_pulled {
+                            warn!(
+                                "[{}] Oracle guard tripped ({}), pulling quotes",
+                                market.config.symbol,
+                                format_trips(&trips)
+                            );
+                            if let Err(e) = self.pull_quotes(idx).await {
+                                error!(
+                                    "[{}] Failed to pull quotes: {}",
+                                    self.markets[idx].config.symbol, e
+                                );
+                                self.metrics.error("pull_quotes");
+                            }
+                        }
+                        continue;
+                    }
+                };
+
+                // Requote straight away when the guard state changes
+                if market.quotes_pulled || (widen - market.oracle_widen).abs() > 0.1 {
+                    if widen <= 1.0 {
+                        info!(
+                            "[{}] Oracle healthy, quoting normally",
+                            market.config.symbol
+                        );
+                    }
+                    market.quotes_pulled = false;
+                    market.prev_oracle_price = Price::ZERO;
+                }
+                market.oracle_widen = widen;
 
-        // Build orders
-        let subaccount = self.get_subaccount();
+                market
+                    .volatility
+                    .update(get_current_timestamp_ms(), current_oracle_price.to_f64());
 
-        let bid_order = OrderParams {
-            order_type: OrderType::Limit,
-            market_type: MarketType::Perp,
-            direction: PositionDirection::Long,
-            base_asset_amount: (bid_size * BASE_PRECISION_F64) as u64,
-            market_index: self.market_id.index(),
-            price: 0,
-            oracle_price_offset: Some(bid_offset),
-            post_only: PostOnlyParam::TryPostOnly,
-            ..Default::default()
+                // Wait for the previous update to confirm before requoting
+                if market.pending.is_some() {
+                    continue;
+                }
+
+                // Check if we should update quotes
+                if market.should_update(&self.config, current_oracle_price) {
+                    if let Err(e) = self.process_update(idx, current_oracle_price).await {
+                        error!("[{}] Update failed: {}", self.markets[idx].config.symbol, e);
+                        self.metrics.error("update");
+                    }
+                } else if let Some(deadline) =
+                    market.debounce_deadline(&self.config, current_oracle_price)
+                {
+                    // Come back when the debounce ends even if the oracle goes quiet
+                    let remaining = deadline.saturating_sub(get_current_timestamp_ms());
+                    wait = wait.min(Duration::from_millis(remaining));
+                }
+            }
+        }
+
+        info!("Trading loop stopped");
+        Ok(())
+    }
+
+    /// Count what woke the trading loop
+    fn record_wakeup(&self, wakeup: &Wakeup) {
+        if wakeup.is_timeout() {
+            self.metrics.wakeups.with_label_values(&["timeout"]).inc();
+        }
+        for source in UpdateSource::ALL {
+            let count = wakeup.count(source);
+            if count > 0 {
+                self.metrics
+                    .wakeups
+                    .with_label_values(&[source.label()])
+                    .inc_by(count);
+            }
+        }
+    }
+
+    /// Check risk limits, cancelling orders (and flattening if configured)
+    /// when one is breached
+    async fn check_risk(&mut self) -> Result<()> {
+        let snapshot = self.risk_snapshot().await?;
+        let Some(breach) = self
+            .kill_switch
+            .check(get_current_timestamp_ms(), &snapshot)
+        else {
+            return Ok(());
         };
 
-        let ask_order = OrderParams {
-            order_type: OrderType::Limit,
-            market_type: MarketType::Perp,
-            direction: PositionDirection::Short,
-            base_asset_amount: (ask_size * BASE_PRECISION_F64) as u64,
-            market_index: self.market_id.index(),
-            price: 0,
-            oracle_price_offset: Some(ask_offset),
-            post_only: PostOnlyParam::TryPostOnly,
+        error!("Kill switch tripped: {}", breach);
+        self.cancel_all().await?;
+        if self.kill_switch.limits().flatten_on_breach {
+            self.close_positions(self.config.shutdown.policy).await?;
+        }
+        error!("Quoting halted until the kill switch is reset (SIGUSR1)");
+        Ok(())
+    }
+
+    /// Unrealized PnL and notional of open positions at oracle prices, plus realized PnL
+    async fn risk_snapshot(&self) -> Result<RiskSnapshot> {
+        let mut snapshot = RiskSnapshot {
+            net_realized_pnl: self.state.fills.stats().net_pnl(),
             ..Default::default()
         };
 
-        // Build and send transaction
-        let tx_start = std::time::Instant::now();
-        let tx = self
-            .client
-            .init_tx(&subaccount, self.is_delegated())
-            .await?
-            .cancel_orders((self.market_id.index(), MarketType::Perp), None)
-            .place_orders(vec![bid_order, ask_order])
-            .build();
+        let positions = self.get_open_positions().await?;
+        for market in &self.markets {
+            let symbol = [&market.config.symbol];
+            self.metrics.position.with_label_values(&symbol).set(0.0);
+            self.metrics
+                .unrealized_pnl
+                .with_label_values(&symbol)
+                .set(0.0);
+        }
 
-        let config = RpcSendTransactionConfig {
-            skip_preflight: true,
-            ..Default::default()
+        for position in positions {
+            let Some(market) = self
+                .markets
+                .iter()
+                .find(|m| m.market_id.index() == position.market_index)
+            else {
+                continue;
+            };
+            let Some(oracle) = self
+                .client
+                .try_get_oracle_price_data_and_slot(market.market_id)
+            else {
+                anyhow::bail!("[{}] No oracle price for risk check", market.config.symbol);
+            };
+
+            let base_amount = BaseAmount::from_raw(position.base_asset_amount);
+            let entry_amount = QuoteAmount::from_raw(position.quote_entry_amount);
+            let Some((value, unrealized_pnl)) = Price::from_raw(oracle.data.price)
+                .checked_notional(base_amount)
+                .and_then(|value| Some((value, value.checked_add(entry_amount)?)))
+            else {
+                anyhow::bail!("[{}] Position value out of range", market.config.symbol);
+            };
+
+            let unrealized_pnl = unrealized_pnl.to_f64();
+            snapshot.unrealized_pnl += unrealized_pnl;
+            snapshot.notional += value.to_f64().abs();
+
+            let symbol = [&market.config.symbol];
+            self.metrics
+                .position
+                .with_label_values(&symbol)
+                .set(base_amount.to_f64());
+            self.metrics
+                .unrealized_pnl
+                .with_label_values(&symbol)
+                .set(unrealized_pnl);
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Shared metrics, served by `metrics::serve`
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    /// Handle for an operator to reset a tripped kill switch
+    pub fn risk_reset_handle(&self) -> Arc<AtomicBool> {
+        self.risk_reset.clone()
+    }
+
+    /// Process quote update based on new oracle price
+    async fn process_update(&mut self, idx: usize, new_price: Price) -> Result<()> {
+        let update_start = std::time::Instant::now();
+        let oracle_price = new_price.to_f64();
+        let market_index = self.markets[idx].market_id.index();
+
+        // Get L2 orderbook snapshot
+        let (bids, asks) = l2_levels(self.dlob, market_index, L2_DEPTH);
+
+        // Get current position
+        let position = self
+            .get_current_position(market_index)
+            .await?
+            .unwrap_or_default();
+        let base_amount = BaseAmount::from_raw(position.base_asset_amount).to_f64();
+        let funding_skew = self.funding_skew(idx).await;
+
+        // Compute desired quotes
+        let market = &mut self.markets[idx];
+        let snapshot = MarketSnapshot {
+            oracle_price,
+            bids,
+            asks,
+            position: base_amount,
+            volatility: market
+                .volatility
+                .volatility_over(self.config.model_volatility_window_secs() * 1000),
+            precision: market.precision,
+            config: &market.config,
         };
+        let symbol = [&market.config.symbol];
+        let best_price = |level: Option<L2Level>| level.map_or(f64::NAN, |l| l.price);
+        self.metrics
+            .best_bid
+            .with_label_values(&symbol)
+            .set(best_price(snapshot.best_bid()));
+        self.metrics
+            .best_ask
+            .with_label_values(&symbol)
+            .set(best_price(snapshot.best_ask()));
+
+        let plan = plan_quotes(
+            market.model.as_mut(),
+            &snapshot,
+            funding_skew,
+            &market.volatility,
+            self.config.volatility.as_ref(),
+            market.oracle_widen,
+            market.user_order_id_base,
+        )?;
+
+        if let Some((estimate, multiplier)) = &plan.volatility {
+            info!(
+                "[{}] Vol: {} -> spread x{:.2}",
+                market.config.symbol, estimate, multiplier
+            );
+        }
+        if let Some(recorder) = &self.recorder {
+            recorder.record_quotes(
+                get_current_timestamp_ms(),
+                &market.config.symbol,
+                oracle_price,
+                &plan.orders,
+            );
+        }
+        let quote = plan.quote;
+        info!(
+            "[{}] Quotes: mid ${:.2}, bid ${:.2}, ask ${:.2}, spread ${:.4}",
+            market.config.symbol,
+            quote.mid(),
+            quote.bid_price,
+            quote.ask_price,
+            quote.spread()
+        );
 
-        let signature = self
-            .client
-            .sign_and_send_with_config(tx, None, config)
-            .await?;
+        for planned in &plan.orders {
+            let order = planned.order;
+            info!(
+                "[{}] {:?} L{}: ${:.2} (offset {}), size {}",
+                market.config.symbol,
+                order.side,
+                planned.level,
+                planned.price,
+                order.oracle_price_offset,
+                order.base_asset_amount
+            );
+            self.metrics
+                .quote_price
+                .with_label_values(&[
+                    market.config.symbol.as_str(),
+                    side_label(order.side),
+                    &planned.level.to_string(),
+                ])
+                .set(planned.price);
+        }
+        let desired = plan.desired();
+
+        // Diff against resting orders, cancel/replace everything when they are unknown
+        let (ops, next_live) = match &market.live_orders {
+            Some(live) => {
+                let diff = OrderDiff::new(live, &desired, &self.config.order_tolerance, new_price);
+                info!(
+                    "[{}] Order diff: keep {}, modify {}, place {}, cancel {}",
+                    market.config.symbol,
+                    diff.keep.len(),
+                    diff.modify.len(),
+                    diff.place.len(),
+                    diff.cancel.len()
+                );
+
+                if diff.is_empty() {
+                    market.prev_oracle_price = new_price;
+                    market.last_update_time = get_current_timestamp_ms();
+                    return Ok(());
+                }
 
-        let tx_time_ms = tx_start.elapsed().as_millis();
+                let mut ops = Vec::with_capacity(diff.modify.len() + diff.place.len() + 1);
+                if !diff.cancel.is_empty() {
+                    ops.push(TxOp::CancelByUserId(diff.cancel.clone()));
+                }
+                ops.extend(diff.modify.iter().copied().map(TxOp::Modify));
+                ops.extend(diff.place.iter().copied().map(TxOp::Place));
+                (ops, diff.into_live())
+            }
+            None => {
+                let mut ops = vec![TxOp::CancelMarket];
+                ops.extend(desired.iter().copied().map(TxOp::Place));
+                (ops, desired)
+            }
+        };
 
-        info!("Orders placed successfully. Sig: {}", signature);
+        // Resting orders are unknown until every transaction confirms
+        let tracked = market.live_orders.take().map_or(0, |live| live.len());
+        let (placed, cancelled) = (order_count(&ops), cancel_count(&ops, tracked));
+        self.kill_switch
+            .record_orders(get_current_timestamp_ms(), placed);
 
-        // Update state
-        self.state.prev_oracle_price = new_price;
-        self.state.last_update_time = get_current_timestamp_ms();
+        // Build and send transactions, or hand the ops to the paper exchange
+        let tx_start = std::time::Instant::now();
+        let tx_count = match self.paper.as_mut() {
+            Some(paper) => {
+                apply_paper_ops(paper, market_index, &ops);
+                info!(
+                    "[{}] Paper orders updated, {} resting",
+                    self.markets[idx].config.symbol,
+                    paper.open_orders(market_index)
+                );
+                self.markets[idx].live_orders = Some(next_live);
+                0
+            }
+            None => self.send_quote_txs(idx, ops, next_live).await?,
+        };
+
+        let tx_time_ms = tx_start.elapsed().as_millis();
+        self.record_sent(idx, placed, cancelled);
+        let symbol = [&self.markets[idx].config.symbol];
+        self.metrics
+            .update_latency
+            .with_label_values(&symbol)
+            .observe(update_start.elapsed().as_secs_f64());
+
+        // Update state, a failed transaction resets the price to requote
+        let market = &mut self.markets[idx];
+        market.prev_oracle_price = new_price;
+        market.last_update_time = get_current_timestamp_ms();
 
         info!(
-            "Update completed in {}ms (tx: {}ms)",
+            "[{}] Update sent in {}ms ({} tx: {}ms)",
+            market.config.symbol,
             update_start.elapsed().as_millis(),
+            tx_count,
             tx_time_ms
         );
 
         Ok(())
     }
 
-    /// Calculate inventory skew multipliers based on position
-    fn calculate_inventory_skew(position_ratio: f64) -> (f64, f64) {
-        if position_ratio.abs() <= 0.1 {
-            return (1.0, 1.0);
+    /// Send a market's order ops, returning the number of transactions. They
+    /// confirm in the background and the market's resting orders become
+    /// `next_live` once every one has landed.
+    async fn send_quote_txs(
+        &mut self,
+        idx: usize,
+        ops: Vec<TxOp>,
+        next_live: Vec<QuoteOrder>,
+    ) -> Result<usize> {
+        let market_index = self.markets[idx].market_id.index();
+        let txs = self.build_quote_txs(market_index, ops).await?;
+        let tx_count = txs.len();
+
+        self.state.last_tx_batch += 1;
+        let batch = self.state.last_tx_batch;
+        self.markets[idx].pending = Some(PendingTxs {
+            batch,
+            next_live: Some(next_live),
+        });
+
+        let sender = self.tx_sender.clone();
+        let reports = self.tx_report_tx.clone();
+        let updates = self.updates.clone();
+        tokio::spawn(async move {
+            let outcomes = join_all(txs.into_iter().map(|tx| sender.send(tx))).await;
+            let report = TxReport {
+                market: idx,
+                batch,
+                outcomes,
+            };
+            if reports.send(report).is_ok() {
+                updates.notify(UpdateSource::Tx);
+            }
+        });
+
+        Ok(tx_count)
+    }
+
+    /// Apply confirmation outcomes of transactions sent in the background
+    fn drain_tx_reports(&mut self) {
+        while let Ok(report) = self.tx_report_rx.try_recv() {
+            self.apply_tx_report(report);
         }
+    }
 
-        let abs_ratio = position_ratio.abs();
-        let max_skew = 0.8;
-        let scale = 0.2;
-        let skew = max_skew * (abs_ratio / scale).tanh();
+    /// Log and count a batch's outcomes. Resting orders only advance when every
+    /// transaction confirmed, otherwise they are unknown and get replaced.
+    fn apply_tx_report(&mut self, report: TxReport) {
+        let market = &mut self.markets[report.market];
+        let symbol = market.config.symbol.as_str();
+        for outcome in &report.outcomes {
+            self.metrics
+                .tx_latency
+                .with_label_values(&[symbol])
+                .observe(outcome.elapsed.as_secs_f64());
+            self.metrics
+                .transactions
+                .with_label_values(&[symbol, outcome.status.label()])
+                .inc();
+            self.metrics
+                .priority_fees
+                .with_label_values(&[symbol])
+                .inc_by(outcome.priority_fee_lamports);
+            match outcome.status {
+                TxStatus::Confirmed => info!("[{}] Orders updated: {}", symbol, outcome),
+                _ => error!("[{}] Order update {}", symbol, outcome),
+            }
+        }
+
+        // Superseded by a later batch, e.g. quotes pulled while this one was in flight
+        if market.pending.as_ref().map(|p| p.batch) != Some(report.batch) {
+            return;
+        }
+        let Some(pending) = market.pending.take() else {
+            return;
+        };
+
+        if report.outcomes.iter().all(TxOutcome::is_confirmed) {
+            market.live_orders = pending.next_live;
+        } else {
+            // Some orders may have landed, cancel and replace after the debounce
+            market.live_orders = None;
+            market.prev_oracle_price = Price::ZERO;
+            market.quotes_pulled = false;
+            self.metrics.error("tx");
+        }
+    }
 
-        if position_ratio > 0.0 {
-            // Long position: widen bids, tighten asks
-            (1.0 + skew, 1.0 - skew)
+    /// Cancel a market's quotes until its oracle recovers
+    async fn pull_quotes(&mut self, idx: usize) -> Result<()> {
+        let market = &mut self.markets[idx];
+        let market_index = market.market_id.index();
+        // Don't retry every cycle, the next update does a full cancel/replace
+        market.quotes_pulled = true;
+        let tracked = market.live_orders.take().map_or(0, |live| live.len());
+
+        if let Some(paper) = self.paper.as_mut() {
+            paper.cancel_market(market_index);
+            info!("[{}] Paper quotes pulled", self.markets[idx].config.symbol);
+            self.markets[idx].live_orders = Some(Vec::new());
         } else {
-            // Short position: tighten bids, widen asks
-            (1.0 - skew, 1.0 + skew)
+            self.send_quote_txs(idx, vec![TxOp::CancelMarket], Vec::new())
+                .await?;
+            info!("[{}] Quotes pull sent", self.markets[idx].config.symbol);
         }
+
+        self.record_sent(idx, 0, tracked);
+        self.metrics.clear_quotes(&self.markets[idx].config.symbol);
+        Ok(())
     }
 
-    /// Calculate dynamic order sizing based on position
-    fn calculate_dynamic_sizing(base_size: f64, position_ratio: f64) -> (f64, f64) {
-        let abs_ratio = position_ratio.abs();
-        let reduction_start_pct = 0.2;
+    /// Compare tracked quotes with open orders on the user account, cancel
+    /// orphaned orders and re-place quotes that are missing or differ
+    async fn reconcile_orders(&mut self) -> Result<()> {
+        // Paper orders can't drift from what we track
+        if self.paper.is_some() {
+            return Ok(());
+        }
 
-        // At max position, stop adding to that side
-        if abs_ratio >= 1.0 {
-            return if position_ratio > 0.0 {
-                (0.0, base_size)
-            } else {
-                (base_size, 0.0)
+        let subaccount = self.get_subaccount();
+        let user_account = self.client.get_user_account(&subaccount).await?;
+        let now = get_current_timestamp_ms();
+
+        for idx in 0..self.markets.len() {
+            let market = &self.markets[idx];
+            if now - market.last_update_time < RECONCILE_GRACE_MS || market.pending.is_some() {
+                continue;
+            }
+            // Unknown orders are fully replaced on the next update anyway
+            let Some(tracked) = market.live_orders.clone() else {
+                continue;
             };
+
+            let market_index = market.market_id.index();
+            let on_chain: Vec<OnChainOrder> = user_account
+                .orders
+                .iter()
+                .filter(|o| {
+                    o.status == OrderStatus::Open
+                        && o.market_type == MarketType::Perp
+                        && o.market_index == market_index
+                })
+                .map(|o| {
+                    let unfilled = o
+                        .base_asset_amount
+                        .saturating_sub(o.base_asset_amount_filled);
+                    Ok(OnChainOrder {
+                        order_id: o.order_id,
+                        user_order_id: o.user_order_id,
+                        side: match o.direction {
+                            PositionDirection::Long => Side::Buy,
+                            PositionDirection::Short => Side::Sell,
+                        },
+                        oracle_price_offset: Price::from_offset(o.oracle_price_offset),
+                        base_asset_amount: BaseAmount::from_unsigned(unfilled).ok_or_else(
+                            || anyhow!("order {} size {} out of range", o.order_id, unfilled),
+                        )?,
+                    })
+                })
+                .collect::<Result<_>>()?;
+
+            let reconciliation = Reconciliation::new(&tracked, &on_chain);
+            if reconciliation.is_clean() {
+                continue;
+            }
+
+            let symbol = &market.config.symbol;
+            warn!(
+                "[{}] Reconciliation: {} matched, {} orphaned, {} missing, {} mismatched",
+                symbol,
+                reconciliation.matched,
+                reconciliation.orphaned.len(),
+                reconciliation.missing.len(),
+                reconciliation.mismatched.len()
+            );
+            for order in &reconciliation.orphaned {
+                warn!(
+                    "[{}] Orphaned order {} (user id {}): {:?} offset {}, size {}",
+                    symbol,
+                    order.order_id,
+                    order.user_order_id,
+                    order.side,
+                    order.oracle_price_offset,
+                    order.base_asset_amount
+                );
+            }
+            for order in &reconciliation.missing {
+                warn!(
+                    "[{}] Missing quote (user id {}): {:?} offset {}, size {}",
+                    symbol,
+                    order.user_order_id,
+                    order.side,
+                    order.oracle_price_offset,
+                    order.base_asset_amount
+                );
+            }
+            for (expected, actual) in &reconciliation.mismatched {
+                warn!(
+                    "[{}] Order {} (user id {}) differs: expected {:?} offset {} size {}, found {:?} offset {} size {}",
+                    symbol,
+                    actual.order_id,
+                    expected.user_order_id,
+                    expected.side,
+                    expected.oracle_price_offset,
+                    expected.base_asset_amount,
+                    actual.side,
+                    actual.oracle_price_offset,
+                    actual.base_asset_amount
+                );
+            }
+
+            let mut ops = vec![TxOp::CancelById(reconciliation.to_cancel())];
+            ops.extend(reconciliation.to_place().into_iter().map(TxOp::Place));
+
+            let (placed, cancelled) = (order_count(&ops), cancel_count(&ops, 0));
+            self.markets[idx].live_orders = None;
+            self.kill_switch
+                .record_orders(get_current_timestamp_ms(), placed);
+            self.send_quote_txs(idx, ops, tracked).await?;
+            info!(
+                "[{}] Reconciliation orders sent",
+                self.markets[idx].config.symbol
+            );
+
+            self.record_sent(idx, placed, cancelled);
+            self.markets[idx].last_update_time = get_current_timestamp_ms();
         }
 
-        // Gradually reduce size as position grows
-        let size_multiplier = if abs_ratio > reduction_start_pct {
-            let slope = -1.0 / (1.0 - reduction_start_pct);
-            let intercept = -slope;
-            slope * abs_ratio + intercept
-        } else {
-            1.0
-        };
+        Ok(())
+    }
 
-        if position_ratio > 0.0 {
-            (base_size * size_multiplier, base_size)
-        } else if position_ratio < 0.0 {
-            (base_size, base_size * size_multiplier)
-        } else {
-            (base_size, base_size)
+    /// Count orders sent for a market
+    fn record_sent(&self, idx: usize, placed: usize, cancelled: usize) {
+        let symbol = [&self.markets[idx].config.symbol];
+        self.metrics
+            .orders_placed
+            .with_label_values(&symbol)
+            .inc_by(placed as u64);
+        self.metrics
+            .orders_cancelled
+            .with_label_values(&symbol)
+            .inc_by(cancelled as u64);
+    }
+
+    /// Build transactions for a market's order ops, packing as many ops per
+    /// transaction as fit and splitting the rest into follow-up transactions
+    async fn build_quote_txs(
+        &self,
+        market_index: u16,
+        mut ops: Vec<TxOp>,
+    ) -> Result<Vec<VersionedMessage>> {
+        let subaccount = self.get_subaccount();
+        let mut txs = Vec::new();
+
+        while !ops.is_empty() {
+            let mut count = ops.len();
+
+            let tx = loop {
+                let builder = self.init_tx(&subaccount).await?;
+                let tx = apply_ops(builder, market_index, &ops[..count])?.build();
+
+                if count <= 1 || tx_fits(&tx) {
+                    break tx;
+                }
+                count -= 1;
+            };
+
+            txs.push(tx);
+            ops.drain(..count);
         }
+
+        Ok(txs)
+    }
+
+    /// Reservation price shift from the market's funding rates (bps), 0 when
+    /// funding skew is off or the rates can't be read
+    async fn funding_skew(&self, idx: usize) -> f64 {
+        let Some(config) = &self.config.funding else {
+            return 0.0;
+        };
+        let market = &self.markets[idx];
+        let perp_market = match self
+            .client
+            .get_perp_market_account(market.market_id.index())
+            .await
+        {
+            Ok(perp_market) => perp_market,
+            Err(e) => {
+                warn!(
+                    "[{}] Failed to get funding rates: {}",
+                    market.config.symbol, e
+                );
+                self.metrics.error("funding");
+                return 0.0;
+            }
+        };
+
+        let amm = &perp_market.amm;
+        let Some(rates) = FundingRates::from_amm(
+            amm.last_funding_rate,
+            amm.last_mark_price_twap,
+            amm.historical_oracle_data.last_oracle_price_twap,
+            amm.last_funding_rate_ts,
+            amm.funding_period,
+            (get_current_timestamp_ms() / 1000) as i64,
+        ) else {
+            warn!(
+                "[{}] No oracle TWAP for funding rates",
+                market.config.symbol
+            );
+            return 0.0;
+        };
+
+        let skew = funding_skew_bps(&rates, config);
+        info!(
+            "[{}] Funding: {} -> skew {:+.2} bps",
+            market.config.symbol, rates, skew
+        );
+        let symbol = [&market.config.symbol];
+        self.metrics
+            .predicted_funding_rate
+            .with_label_values(&symbol)
+            .set(rates.predicted_rate);
+        self.metrics
+            .funding_skew
+            .with_label_values(&symbol)
+            .set(skew);
+        skew
+    }
+
+    /// Get current perp position for a market
+    async fn get_current_position(&self, market_index: u16) -> Result<Option<PerpPosition>> {
+        Ok(self
+            .get_open_positions()
+            .await?
+            .into_iter()
+            .find(|pos| pos.market_index == market_index))
     }
 
-    /// Get current perp position
-    async fn get_current_position(&self) -> Result<Option<PerpPosition>> {
+    /// Get non-zero perp positions in the configured markets
+    async fn get_open_positions(&self) -> Result<Vec<PerpPosition>> {
+        if let Some(paper) = &self.paper {
+            let mut positions = Vec::new();
+            for market in &self.markets {
+                let market_index = market.market_id.index();
+                let position = paper.position(market_index);
+                let (Some(base), Some(entry)) = (
+                    BaseAmount::from_f64(position.base_asset_amount),
+                    QuoteAmount::from_f64(position.quote_entry_amount),
+                ) else {
+                    anyhow::bail!("[{}] Paper position out of range", market.config.symbol);
+                };
+                if !base.is_zero() {
+                    positions.push(PerpPosition {
+                        market_index,
+                        base_asset_amount: base.raw(),
+                        quote_entry_amount: entry.raw(),
+                        quote_asset_amount: entry.raw(),
+                        ..Default::default()
+                    });
+                }
+            }
+            return Ok(positions);
+        }
+
         let subaccount = self.get_subaccount();
         let user_account = self.client.get_user_account(&subaccount).await?;
 
         Ok(user_account
             .perp_positions
             .iter()
-            .find(|pos| pos.market_index == self.market_id.index())
-            .cloned())
+            .filter(|pos| pos.base_asset_amount != 0)
+            .filter(|pos| {
+                self.markets
+                    .iter()
+                    .any(|m| m.market_id.index() == pos.market_index)
+            })
+            .cloned()
+            .collect())
     }
 
     /// Get subaccount pubkey
     fn get_subaccount(&self) -> Pubkey {
-        match &self.config.authority {
-            Some(authority_str) => {
-                let authority = Pubkey::from_str(authority_str).expect("Invalid authority pubkey");
-                Wallet::derive_user_account(&authority, self.config.subaccount_id)
-            }
-            None => self.client.wallet().sub_account(self.config.subaccount_id),
-        }
+        subaccount_pubkey(&self.client, &self.config)
     }
 
     /// Check if using delegated signing
@@ -389,73 +1432,205 @@ impl OracleLimitMakerBot {
         self.config.authority.is_some()
     }
 
+    /// Start a transaction with compute budget instructions. The price is a
+    /// placeholder, the sender re-prices every attempt, but must be non-zero
+    /// for the price instruction to be included.
+    async fn init_tx(&self, subaccount: &Pubkey) -> Result<TransactionBuilder<'_>> {
+        let fees = &self.config.tx.priority_fee;
+        Ok(self
+            .client
+            .init_tx(subaccount, self.is_delegated())
+            .await?
+            .with_priority_fee(
+                fees.min_micro_lamports.max(1),
+                Some(fees.compute_unit_limit),
+            ))
+    }
+
     /// Start the bot
     pub async fn start(&mut self) -> Result<()> {
         self.trading_loop().await
     }
 
-    /// Stop the bot and clean up
-    pub async fn stop(&mut self) -> Result<()> {
-        info!("Stopping bot");
-        self.state.is_running = false;
+    /// Cancel all orders in every market
+    async fn cancel_all(&mut self) -> Result<()> {
+        if let Some(paper) = self.paper.as_mut() {
+            info!("Cancelling paper orders");
+            paper.cancel_all();
+        } else {
+            info!("Cancelling orders");
+            let subaccount = self.get_subaccount();
+            let tx = self.init_tx(&subaccount).await?.cancel_all_orders().build();
 
-        let subaccount = self.get_subaccount();
+            let outcome = self.tx_sender.send(tx).await;
+            if outcome.is_confirmed() {
+                info!("Cancelled orders: {}", outcome);
+            } else {
+                error!("Failed to cancel orders: {}", outcome);
+                self.metrics.error("cancel");
+            }
+        }
 
-        // Check if position exists
-        let should_close = self
-            .get_current_position()
-            .await
-            .ok()
-            .and_then(|pos| pos)
-            .filter(|pos| pos.base_asset_amount != 0);
+        for market in &mut self.markets {
+            // In-flight updates are superseded by the cancel
+            market.pending = None;
+            if let Some(live) = market.live_orders.take() {
+                let symbol = [&market.config.symbol];
+                self.metrics
+                    .orders_cancelled
+                    .with_label_values(&symbol)
+                    .inc_by(live.len() as u64);
+            }
+            self.metrics.clear_quotes(&market.config.symbol);
+        }
 
-        if let Some(pos) = should_close {
-            // Cancel orders + close position atomically
-            info!("Closing position and cancelling orders");
+        Ok(())
+    }
 
-            let close_direction = if pos.base_asset_amount > 0 {
-                PositionDirection::Short
-            } else {
-                PositionDirection::Long
-            };
+    /// Close open positions under a shutdown policy, see
+    /// `shutdown::close_positions`. Paper positions close at the oracle price.
+    async fn close_positions(&mut self, policy: ShutdownPolicy) -> Result<()> {
+        if !policy.flattens() {
+            return Ok(());
+        }
+        if self.paper.is_some() {
+            self.close_paper_positions();
+            return Ok(());
+        }
+        let auction_timeout_secs = self.config.shutdown.auction_timeout_secs;
+        shutdown::close_positions(self, policy, auction_timeout_secs).await
+    }
 
-            let close_order = OrderParams {
-                order_type: OrderType::Market,
-                market_type: MarketType::Perp,
-                direction: close_direction,
-                base_asset_amount: pos.base_asset_amount.unsigned_abs(),
-                market_index: self.market_id.index(),
-                reduce_only: true,
-                ..Default::default()
+    /// Close paper positions at the oracle price
+    fn close_paper_positions(&mut self) {
+        let Some(paper) = self.paper.as_mut() else {
+            return;
+        };
+        let mut fills = Vec::new();
+        for market in &self.markets {
+            let Some(oracle) = self
+                .client
+                .try_get_oracle_price_data_and_slot(market.market_id)
+            else {
+                warn!(
+                    "[{}] No oracle price to close paper position",
+                    market.config.symbol
+                );
+                continue;
             };
+            fills.extend(paper.close_position(
+                market.market_id.index(),
+                Price::from_raw(oracle.data.price).to_f64(),
+            ));
+        }
 
-            let tx = self
-                .client
-                .init_tx(&subaccount, self.is_delegated())
-                .await?
-                .cancel_all_orders()
-                .place_orders(vec![close_order])
-                .build();
+        for fill in fills {
+            self.record_fill(fill);
+        }
+    }
 
-            match self.client.sign_and_send(tx).await {
-                Ok(sig) => info!("Cancelled orders and closed position. Sig: {}", sig),
-                Err(e) => error!("Failed to cancel and close: {}", e),
+    /// Open orders and positions left in the quoted markets
+    async fn final_state(&self) -> Result<ShutdownState> {
+        let symbol = |market_index: u16| {
+            self.markets
+                .iter()
+                .find(|m| m.market_id.index() == market_index)
+                .map_or_else(|| market_index.to_string(), |m| m.config.symbol.clone())
+        };
+        let positions = self
+            .get_open_positions()
+            .await?
+            .iter()
+            .map(|pos| {
+                (
+                    symbol(pos.market_index),
+                    BaseAmount::from_raw(pos.base_asset_amount).to_f64(),
+                )
+            })
+            .collect();
+
+        let open_orders = match &self.paper {
+            Some(paper) => self
+                .markets
+                .iter()
+                .map(|m| paper.open_orders(m.market_id.index()))
+                .sum(),
+            None => {
+                let user_account = self.client.get_user_account(&self.get_subaccount()).await?;
+                user_account
+                    .orders
+                    .iter()
+                    .filter(|o| {
+                        o.status == OrderStatus::Open
+                            && o.market_type == MarketType::Perp
+                            && self
+                                .markets
+                                .iter()
+                                .any(|m| m.market_id.index() == o.market_index)
+                    })
+                    .count()
             }
-        } else {
-            // Just cancel orders
-            info!("Cancelling orders");
-            let tx = self
-                .client
-                .init_tx(&subaccount, self.is_delegated())
-                .await?
-                .cancel_all_orders()
-                .build();
+        };
+
+        Ok(ShutdownState {
+            open_orders,
+            positions,
+        })
+    }
 
-            match self.client.sign_and_send(tx).await {
-                Ok(sig) => info!("Cancelled orders. Sig: {}", sig),
-                Err(e) => error!("Failed to cancel orders: {}", e),
+    /// Cancel orders and handle positions under the shutdown policy, then
+    /// wait for the user account to show the result. Returns whether the
+    /// final state was confirmed.
+    async fn shutdown(&mut self) -> Result<bool> {
+        let config = self.config.shutdown.clone();
+        info!("Shutting down: {}", config.policy);
+        self.cancel_all().await?;
+        self.close_positions(config.policy).await?;
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(config.confirm_timeout_secs);
+        loop {
+            let state = self.final_state().await?;
+            if state.is_complete(&config.policy) {
+                info!("Shutdown state confirmed: {}", state);
+                return Ok(true);
+            }
+            if std::time::Instant::now() >= deadline {
+                error!(
+                    "Shutdown state not confirmed after {}s: {}",
+                    config.confirm_timeout_secs, state
+                );
+                return Ok(false);
             }
+            tokio::time::sleep(SHUTDOWN_POLL).await;
         }
+    }
+
+    /// Stop the bot and clean up. Returns whether the shutdown policy's final
+    /// state was confirmed on-chain.
+    pub async fn stop(&mut self) -> Result<bool> {
+        info!("Stopping bot");
+        self.state.is_running = false;
+
+        // Record-only mode never places orders
+        let confirmed = if self.config.record_only() {
+            true
+        } else {
+            self.shutdown().await?
+        };
+
+        // Final fill summary
+        self.drain_fills();
+        let stats = self.state.fills.stats();
+        info!(
+            "Session summary: fills {}, matches {}, volume ${:.2}, realized ${:.4}, fees ${:.4}, rebates ${:.4}, net ${:.4}",
+            stats.total_fills,
+            stats.total_matches,
+            stats.traded_volume,
+            stats.realized_pnl,
+            stats.fees_paid,
+            stats.rebates_earned,
+            stats.net_pnl()
+        );
 
         // Unsubscribe
         self.client.grpc_unsubscribe();
@@ -465,8 +1640,241 @@ impl OracleLimitMakerBot {
             info!("Unsubscribed successfully");
         }
 
+        if let Some(recorder) = self.recorder.take() {
+            recorder.close();
+        }
+
+        Ok(confirmed)
+    }
+}
+
+impl PositionCloser for OracleLimitMakerBot {
+    async fn open_positions(&mut self) -> Result<Vec<PerpPosition>> {
+        self.get_open_positions().await
+    }
+
+    async fn oracle_price(&mut self, market_index: u16) -> Result<Price> {
+        let market_id = MarketId::perp(market_index);
+        let Some(oracle) = self.client.try_get_oracle_price_data_and_slot(market_id) else {
+            anyhow::bail!(
+                "No oracle price to close position in market {}",
+                market_index
+            );
+        };
+        Ok(Price::from_raw(oracle.data.price))
+    }
+
+    async fn place_close_orders(&mut self, orders: Vec<OrderParams>) -> Result<()> {
+        let subaccount = self.get_subaccount();
+        let tx = self
+            .init_tx(&subaccount)
+            .await?
+            .place_orders(orders)
+            .build();
+
+        let outcome = self.tx_sender.send(tx).await;
+        if outcome.is_confirmed() {
+            info!("Close orders sent: {}", outcome);
+        } else {
+            error!("Failed to send close orders: {}", outcome);
+            self.metrics.error("close");
+        }
         Ok(())
     }
+
+    async fn cancel_all_orders(&mut self) -> Result<()> {
+        self.cancel_all().await
+    }
+}
+
+/// Single order instruction within a quote update
+#[derive(Debug, Clone)]
+enum TxOp {
+    // Cancel every order in the market
+    CancelMarket,
+    CancelByUserId(Vec<u8>),
+    CancelById(Vec<u32>),
+    Modify(QuoteOrder),
+    Place(QuoteOrder),
+}
+
+/// Add ops to a transaction: cancels, then modifies, then placements
+fn apply_ops<'a>(
+    mut builder: TransactionBuilder<'a>,
+    market_index: u16,
+    ops: &[TxOp],
+) -> Result<TransactionBuilder<'a>> {
+    let mut cancel_ids = Vec::new();
+    let mut cancel_order_ids = Vec::new();
+    let mut modifies = Vec::new();
+    let mut places = Vec::new();
+
+    for op in ops {
+        match op {
+            TxOp::CancelMarket => {
+                builder = builder.cancel_orders((market_index, MarketType::Perp), None);
+            }
+            TxOp::CancelByUserId(ids) => cancel_ids.extend_from_slice(ids),
+            TxOp::CancelById(ids) => cancel_order_ids.extend_from_slice(ids),
+            TxOp::Modify(order) => modifies.push((order.user_order_id, modify_params(order)?)),
+            TxOp::Place(order) => places.push(order_params(order, market_index)?),
+        }
+    }
+
+    if !cancel_ids.is_empty() {
+        builder = builder.cancel_orders_by_user_id(cancel_ids);
+    }
+    if !cancel_order_ids.is_empty() {
+        builder = builder.cancel_orders_by_id(cancel_order_ids);
+    }
+    if !modifies.is_empty() {
+        builder = builder.modify_orders_by_user_id(&modifies);
+    }
+    if !places.is_empty() {
+        builder = builder.place_orders(places);
+    }
+    Ok(builder)
+}
+
+fn format_trips(trips: &[GuardTrip]) -> String {
+    trips
+        .iter()
+        .map(|trip| trip.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Number of orders placed or modified by a set of ops
+fn order_count(ops: &[TxOp]) -> usize {
+    ops.iter()
+        .filter(|op| matches!(op, TxOp::Modify(_) | TxOp::Place(_)))
+        .count()
+}
+
+/// Apply a market's order ops to the paper exchange
+fn apply_paper_ops(paper: &mut PaperExchange, market_index: u16, ops: &[TxOp]) {
+    let terms = |order: &QuoteOrder| {
+        (
+            order.oracle_price_offset.to_f64(),
+            order.base_asset_amount.to_f64(),
+        )
+    };
+
+    for op in ops {
+        match op {
+            TxOp::CancelMarket => paper.cancel_market(market_index),
+            TxOp::CancelByUserId(ids) => paper.cancel(market_index, ids),
+            // Only sent by reconciliation, which paper mode skips
+            TxOp::CancelById(_) => {}
+            TxOp::Modify(order) => {
+                let (offset, size) = terms(order);
+                paper.modify(market_index, order.user_order_id, offset, size);
+            }
+            TxOp::Place(order) => {
+                let (offset, size) = terms(order);
+                paper.place(market_index, order.user_order_id, order.side, offset, size);
+            }
+        }
+    }
+}
+
+/// Top `depth` levels per side of a market's book, best first
+fn l2_levels(dlob: &DLOB, market_index: u16, depth: usize) -> (Vec<L2Level>, Vec<L2Level>) {
+    let l2 = dlob.get_l2_snapshot(market_index, MarketType::Perp);
+    let to_level = |(p, s): (&u64, &u64)| L2Level {
+        price: Price::from_unsigned(*p).map_or(f64::NAN, Price::to_f64),
+        size: BaseAmount::from_unsigned(*s).map_or(f64::NAN, BaseAmount::to_f64),
+    };
+    let bids = l2.bids.iter().rev().take(depth).map(to_level).collect();
+    let asks = l2.asks.iter().take(depth).map(to_level).collect();
+    (bids, asks)
+}
+
+/// Number of orders cancelled by a set of ops, market-wide cancels count the
+/// `tracked` quotes and modifies count as a cancel
+fn cancel_count(ops: &[TxOp], tracked: usize) -> usize {
+    ops.iter()
+        .map(|op| match op {
+            TxOp::CancelMarket => tracked,
+            TxOp::CancelByUserId(ids) => ids.len(),
+            TxOp::CancelById(ids) => ids.len(),
+            TxOp::Modify(_) => 1,
+            TxOp::Place(_) => 0,
+        })
+        .sum()
+}
+
+fn side_label(side: Side) -> &'static str {
+    match side {
+        Side::Buy => "bid",
+        Side::Sell => "ask",
+    }
+}
+
+/// Post-only oracle offset limit order for a quote
+fn order_params(order: &QuoteOrder, market_index: u16) -> Result<OrderParams> {
+    let (oracle_price_offset, base_asset_amount) = order_terms(order)?;
+    Ok(OrderParams {
+        order_type: OrderType::Limit,
+        market_type: MarketType::Perp,
+        direction: direction(order.side),
+        user_order_id: order.user_order_id,
+        base_asset_amount,
+        market_index,
+        price: 0,
+        oracle_price_offset: Some(oracle_price_offset),
+        post_only: PostOnlyParam::TryPostOnly,
+        ..Default::default()
+    })
+}
+
+/// Move a resting quote to a new offset and size
+fn modify_params(order: &QuoteOrder) -> Result<ModifyOrderParams> {
+    let (oracle_price_offset, base_asset_amount) = order_terms(order)?;
+    Ok(ModifyOrderParams {
+        base_asset_amount: Some(base_asset_amount),
+        oracle_price_offset: Some(oracle_price_offset),
+        post_only: Some(PostOnlyParam::TryPostOnly),
+        ..Default::default()
+    })
+}
+
+/// A quote's offset and size as order fields
+fn order_terms(order: &QuoteOrder) -> Result<(i32, u64)> {
+    let offset = order
+        .oracle_price_offset
+        .to_offset()
+        .ok_or_else(|| anyhow!("offset {} out of range", order.oracle_price_offset))?;
+    let size = order
+        .base_asset_amount
+        .to_unsigned()
+        .ok_or_else(|| anyhow!("size {} out of range", order.base_asset_amount))?;
+    Ok((offset, size))
+}
+
+fn direction(side: Side) -> PositionDirection {
+    match side {
+        Side::Buy => PositionDirection::Long,
+        Side::Sell => PositionDirection::Short,
+    }
+}
+
+/// Check a transaction fits in a single packet once signed
+fn tx_fits(message: &VersionedMessage) -> bool {
+    let signatures = message.header().num_required_signatures as usize;
+    // Compact-u16 signature count + signatures + message
+    1 + signatures * 64 + message.serialize().len() <= PACKET_DATA_SIZE
+}
+
+/// Derive the user account the bot trades from
+fn subaccount_pubkey(client: &DriftClient, config: &BotConfig) -> Pubkey {
+    match &config.authority {
+        Some(authority_str) => {
+            let authority = Pubkey::from_str(authority_str).expect("Invalid authority pubkey");
+            Wallet::derive_user_account(&authority, config.subaccount_id)
+        }
+        None => client.wallet().sub_account(config.subaccount_id),
+    }
 }
 
 fn get_current_timestamp_ms() -> u64 {
@@ -0,0 +1,278 @@
+//! Prometheus metrics and the `/metrics` HTTP endpoint.
+
+use std::{collections::HashMap, net::SocketAddr, sync::Arc};
+
+use anyhow::Result;
+use axum::{http::header, routing::get, Router};
+use log::{error, info};
+use prometheus::{
+    core::Collector, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry,
+    TextEncoder,
+};
+
+// Latency buckets in seconds, from a fast modify up to a slow confirmation
+const LATENCY_BUCKETS: &[f64] = &[
+    0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0,
+];
+
+/// Metrics exported by the maker, labelled by market symbol
+pub struct Metrics {
+    registry: Registry,
+    // Perp position (base units)
+    pub position: GaugeVec,
+    // Realized PnL from matched fills after fees and rebates (USD)
+    pub realized_pnl: GaugeVec,
+    // Unrealized PnL of the position at the oracle price (USD)
+    pub unrealized_pnl: GaugeVec,
+    // Orders placed, modifies count as a cancel and a place
+    pub orders_placed: IntCounterVec,
+    pub orders_cancelled: IntCounterVec,
+    // Errors by the operation that failed
+    pub errors: IntCounterVec,
+    pub oracle_price: GaugeVec,
+    // Best DLOB bid/ask at the last update, NaN when the side is empty
+    pub best_bid: GaugeVec,
+    pub best_ask: GaugeVec,
+    // Quoted price per side and ladder level
+    pub quote_price: GaugeVec,
+    // Predicted funding rate per period, positive when longs pay
+    pub predicted_funding_rate: GaugeVec,
+    // Reservation price shift from funding carry (BPS)
+    pub funding_skew: GaugeVec,
+    // Time from oracle trigger to all transactions sent
+    pub update_latency: HistogramVec,
+    // Time from sending a transaction to its final status, including re-sends
+    pub tx_latency: HistogramVec,
+    // Sent transactions by final status
+    pub transactions: IntCounterVec,
+    // Priority fees paid by attempts that landed (lamports)
+    pub priority_fees: IntCounterVec,
+    // Trading loop wakeups by update source, "timeout" when nothing woke it.
+    // Book updates are counted without waking the loop.
+    pub wakeups: IntCounterVec,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self> {
+        let registry = Registry::new_custom(Some("oracle_maker".to_string()), None)?;
+        let market = &["market"];
+
+        Ok(Self {
+            position: register(
+                &registry,
+                GaugeVec::new(Opts::new("position", "Perp position (base)"), market)?,
+            )?,
+            realized_pnl: register(
+                &registry,
+                GaugeVec::new(
+                    Opts::new("realized_pnl_usd", "Realized PnL after fees and rebates"),
+                    market,
+                )?,
+            )?,
+            unrealized_pnl: register(
+                &registry,
+                GaugeVec::new(
+                    Opts::new("unrealized_pnl_usd", "Unrealized PnL at the oracle price"),
+                    market,
+                )?,
+            )?,
+            orders_placed: register(
+                &registry,
+                IntCounterVec::new(Opts::new("orders_placed_total", "Orders placed"), market)?,
+            )?,
+            orders_cancelled: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new("orders_cancelled_total", "Orders cancelled"),
+                    market,
+                )?,
+            )?,
+            errors: register(
+                &registry,
+                IntCounterVec::new(Opts::new("errors_total", "Errors by kind"), &["kind"])?,
+            )?,
+            oracle_price: register(
+                &registry,
+                GaugeVec::new(Opts::new("oracle_price", "Oracle price"), market)?,
+            )?,
+            best_bid: register(
+                &registry,
+                GaugeVec::new(Opts::new("best_bid", "Best DLOB bid"), market)?,
+            )?,
+            best_ask: register(
+                &registry,
+                GaugeVec::new(Opts::new("best_ask", "Best DLOB ask"), market)?,
+            )?,
+            quote_price: register(
+                &registry,
+                GaugeVec::new(
+                    Opts::new("quote_price", "Quoted price per ladder level"),
+                    &["market", "side", "level"],
+                )?,
+            )?,
+            predicted_funding_rate: register(
+                &registry,
+                GaugeVec::new(
+                    Opts::new(
+                        "predicted_funding_rate",
+                        "Predicted funding rate per period",
+                    ),
+                    market,
+                )?,
+            )?,
+            funding_skew: register(
+                &registry,
+                GaugeVec::new(
+                    Opts::new("funding_skew_bps", "Reservation price shift from funding"),
+                    market,
+                )?,
+            )?,
+            update_latency: register(
+                &registry,
+                HistogramVec::new(
+                    HistogramOpts::new("update_latency_seconds", "Quote update latency")
+                        .buckets(LATENCY_BUCKETS.to_vec()),
+                    market,
+                )?,
+            )?,
+            tx_latency: register(
+                &registry,
+                HistogramVec::new(
+                    HistogramOpts::new("tx_latency_seconds", "Transaction confirmation latency")
+                        .buckets(LATENCY_BUCKETS.to_vec()),
+                    market,
+                )?,
+            )?,
+            transactions: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new("transactions_total", "Transactions by final status"),
+                    &["market", "status"],
+                )?,
+            )?,
+            priority_fees: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new(
+                        "priority_fees_lamports_total",
+                        "Priority fees paid (lamports)",
+                    ),
+                    market,
+                )?,
+            )?,
+            wakeups: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new("wakeups_total", "Trading loop wakeups by source"),
+                    &["source"],
+                )?,
+            )?,
+            registry,
+        })
+    }
+
+    /// Count an error of the given kind
+    pub fn error(&self, kind: &str) {
+        self.errors.with_label_values(&[kind]).inc();
+    }
+
+    /// Drop a market's quoted prices after its orders are cancelled
+    pub fn clear_quotes(&self, symbol: &str) {
+        for family in self.quote_price.collect() {
+            for metric in family.get_metric() {
+                let labels: HashMap<&str, &str> = metric
+                    .get_label()
+                    .iter()
+                    .map(|l| (l.name(), l.value()))
+                    .collect();
+                if labels.get("market") == Some(&symbol) {
+                    let _ = self.quote_price.remove(&labels);
+                }
+            }
+        }
+    }
+
+    /// Render all metrics in the Prometheus text format
+    pub fn encode(&self) -> Result<String> {
+        Ok(TextEncoder::new().encode_to_string(&self.registry.gather())?)
+    }
+}
+
+fn register<M: Collector + Clone + 'static>(registry: &Registry, metric: M) -> Result<M> {
+    registry.register(Box::new(metric.clone()))?;
+    Ok(metric)
+}
+
+/// Serve `GET /metrics` until the process exits
+pub async fn serve(addr: SocketAddr, metrics: Arc<Metrics>) -> Result<()> {
+    let app = Router::new().route(
+        "/metrics",
+        get(move || async move {
+            match metrics.encode() {
+                Ok(body) => Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)),
+                Err(e) => {
+                    error!("Failed to encode metrics: {}", e);
+                    Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR)
+                }
+            }
+        }),
+    );
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!("Serving metrics at http://{}/metrics", addr);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let metrics = Metrics::new().unwrap();
+        metrics.position.with_label_values(&["SOL-PERP"]).set(1.5);
+        metrics
+            .orders_placed
+            .with_label_values(&["SOL-PERP"])
+            .inc_by(6);
+        metrics.error("update");
+        metrics
+            .update_latency
+            .with_label_values(&["SOL-PERP"])
+            .observe(0.2);
+        metrics.wakeups.with_label_values(&["oracle"]).inc_by(3);
+
+        let text = metrics.encode().unwrap();
+        assert!(text.contains("oracle_maker_position{market=\"SOL-PERP\"} 1.5"));
+        assert!(text.contains("oracle_maker_orders_placed_total{market=\"SOL-PERP\"} 6"));
+        assert!(text.contains("oracle_maker_errors_total{kind=\"update\"} 1"));
+        assert!(text.contains("oracle_maker_wakeups_total{source=\"oracle\"} 3"));
+        assert!(text.contains(
+            "oracle_maker_update_latency_seconds_bucket{market=\"SOL-PERP\",le=\"0.25\"} 1"
+        ));
+    }
+
+    #[test]
+    fn test_clear_quotes() {
+        let metrics = Metrics::new().unwrap();
+        for (market, side) in [
+            ("SOL-PERP", "bid"),
+            ("SOL-PERP", "ask"),
+            ("BTC-PERP", "bid"),
+        ] {
+            metrics
+                .quote_price
+                .with_label_values(&[market, side, "0"])
+                .set(100.0);
+        }
+
+        metrics.clear_quotes("SOL-PERP");
+
+        let text = metrics.encode().unwrap();
+        assert!(!text.contains("market=\"SOL-PERP\""));
+        assert!(text.contains(
+            "oracle_maker_quote_price{level=\"0\",market=\"BTC-PERP\",side=\"bid\"} 100"
+        ));
+    }
+}
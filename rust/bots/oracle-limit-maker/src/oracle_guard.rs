@@ -0,0 +1,273 @@
+//! Oracle staleness, confidence and jump guards applied before quoting.
+
+use std::fmt;
+
+use anyhow::{bail, Result};
+use bot_common::validate::ensure_positive;
+use serde::Deserialize;
+
+use crate::quote::Quote;
+
+/// What to do with a market's quotes while a guard is tripped
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GuardAction {
+    // Cancel the market's quotes until the oracle recovers
+    Pull,
+    // Widen the spread by how far the worst guard is over its limit,
+    // pulling quotes beyond `max_multiplier`
+    Widen { max_multiplier: f64 },
+}
+
+/// Oracle guard limits, `None` disables a guard
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OracleGuardConfig {
+    // Max slots between the oracle's last publish and the slot it was read at
+    pub max_delay_slots: Option<u64>,
+    // Max oracle confidence interval (BPS of price)
+    pub max_confidence_bps: Option<f64>,
+    // Max move between consecutive oracle readings (BPS)
+    pub max_jump_bps: Option<f64>,
+    pub action: GuardAction,
+}
+
+impl OracleGuardConfig {
+    /// Check the settings are usable
+    pub fn validate(&self) -> Result<()> {
+        let bps_limits = [
+            ("max_confidence_bps", self.max_confidence_bps),
+            ("max_jump_bps", self.max_jump_bps),
+        ];
+        for (name, limit) in bps_limits {
+            limit.map_or(Ok(()), |l| ensure_positive(name, l))?;
+        }
+        if let GuardAction::Widen { max_multiplier } = self.action {
+            if max_multiplier.is_nan() || max_multiplier < 1.0 {
+                bail!("max_multiplier must be >= 1, got {}", max_multiplier);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Oracle price data read for a market
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OracleReading {
+    // Price (PRICE_PRECISION)
+    pub price: i64,
+    // Confidence interval (PRICE_PRECISION)
+    pub confidence: u64,
+    pub delay_slots: u64,
+}
+
+/// Guard that is over its limit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GuardTrip {
+    Delay { slots: u64, limit: u64 },
+    Confidence { bps: f64, limit: f64 },
+    Jump { bps: f64, limit: f64 },
+}
+
+impl GuardTrip {
+    /// How far over the limit the reading is (> 1)
+    fn ratio(&self) -> f64 {
+        match *self {
+            GuardTrip::Delay { slots, limit } => slots as f64 / limit.max(1) as f64,
+            GuardTrip::Confidence { bps, limit } | GuardTrip::Jump { bps, limit } => {
+                if limit > 0.0 {
+                    bps / limit
+                } else {
+                    f64::INFINITY
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for GuardTrip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GuardTrip::Delay { slots, limit } => {
+                write!(f, "delay {} slots > {}", slots, limit)
+            }
+            GuardTrip::Confidence { bps, limit } => {
+                write!(f, "confidence {:.2} bps > {:.2}", bps, limit)
+            }
+            GuardTrip::Jump { bps, limit } => write!(f, "jump {:.2} bps > {:.2}", bps, limit),
+        }
+    }
+}
+
+/// Result of checking an oracle reading
+#[derive(Debug, Clone, PartialEq)]
+pub enum OracleCheck {
+    Ok,
+    Widen {
+        multiplier: f64,
+        trips: Vec<GuardTrip>,
+    },
+    Pull(Vec<GuardTrip>),
+}
+
+/// Per-market oracle guard, remembers the previous price for the jump check
+#[derive(Debug, Clone)]
+pub struct OracleGuard {
+    config: OracleGuardConfig,
+    prev_price: Option<i64>,
+}
+
+impl OracleGuard {
+    pub fn new(config: OracleGuardConfig) -> Self {
+        Self {
+            config,
+            prev_price: None,
+        }
+    }
+
+    pub fn check(&mut self, reading: &OracleReading) -> OracleCheck {
+        let config = &self.config;
+        let mut trips = Vec::new();
+
+        if reading.price <= 0 {
+            return OracleCheck::Pull(trips);
+        }
+        let price = reading.price as f64;
+
+        if let Some(limit) = config.max_delay_slots {
+            if reading.delay_slots > limit {
+                trips.push(GuardTrip::Delay {
+                    slots: reading.delay_slots,
+                    limit,
+                });
+            }
+        }
+        if let Some(limit) = config.max_confidence_bps {
+            let bps = reading.confidence as f64 / price * 10_000.0;
+            if bps > limit {
+                trips.push(GuardTrip::Confidence { bps, limit });
+            }
+        }
+        if let (Some(limit), Some(prev)) = (config.max_jump_bps, self.prev_price) {
+            let bps = (reading.price - prev).abs() as f64 / prev as f64 * 10_000.0;
+            if bps > limit {
+                trips.push(GuardTrip::Jump { bps, limit });
+            }
+        }
+        self.prev_price = Some(reading.price);
+
+        if trips.is_empty() {
+            return OracleCheck::Ok;
+        }
+
+        match config.action {
+            GuardAction::Pull => OracleCheck::Pull(trips),
+            GuardAction::Widen { max_multiplier } => {
+                let multiplier = trips.iter().map(GuardTrip::ratio).fold(1.0, f64::max);
+                if multiplier > max_multiplier {
+                    OracleCheck::Pull(trips)
+                } else {
+                    OracleCheck::Widen { multiplier, trips }
+                }
+            }
+        }
+    }
+}
+
+/// Scale the quoted spread around its mid
+pub fn widen_spread(quote: &Quote, multiplier: f64) -> Quote {
+    let half_spread = quote.spread() * multiplier / 2.0;
+    let mid = quote.mid();
+
+    Quote {
+        bid_price: mid - half_spread,
+        ask_price: mid + half_spread,
+        ..*quote
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRICE: i64 = 100_000_000; // $100
+
+    fn config(action: GuardAction) -> OracleGuardConfig {
+        OracleGuardConfig {
+            max_delay_slots: Some(10),
+            max_confidence_bps: Some(5.0),
+            max_jump_bps: Some(20.0),
+            action,
+        }
+    }
+
+    fn reading(price: i64, confidence: u64, delay_slots: u64) -> OracleReading {
+        OracleReading {
+            price,
+            confidence,
+            delay_slots,
+        }
+    }
+
+    #[test]
+    fn test_healthy_oracle() {
+        let mut guard = OracleGuard::new(config(GuardAction::Pull));
+
+        assert_eq!(guard.check(&reading(PRICE, 10_000, 2)), OracleCheck::Ok);
+        // 10 bps move is within the jump limit
+        assert_eq!(
+            guard.check(&reading(PRICE + 100_000, 10_000, 2)),
+            OracleCheck::Ok
+        );
+    }
+
+    #[test]
+    fn test_pull() {
+        let mut guard = OracleGuard::new(config(GuardAction::Pull));
+
+        // 10 bps confidence on a 5 bps limit, 30 slots on a 10 slot limit
+        let check = guard.check(&reading(PRICE, 100_000, 30));
+        assert_eq!(
+            check,
+            OracleCheck::Pull(vec![
+                GuardTrip::Delay {
+                    slots: 30,
+                    limit: 10
+                },
+                GuardTrip::Confidence {
+                    bps: 10.0,
+                    limit: 5.0
+                },
+            ])
+        );
+
+        // 50 bps jump from the previous reading
+        let check = guard.check(&reading(PRICE + 500_000, 10_000, 2));
+        assert!(matches!(check, OracleCheck::Pull(trips) if trips.len() == 1));
+    }
+
+    #[test]
+    fn test_widen_proportionally() {
+        let mut guard = OracleGuard::new(config(GuardAction::Widen {
+            max_multiplier: 3.0,
+        }));
+
+        // Confidence at twice the limit doubles the spread
+        let check = guard.check(&reading(PRICE, 100_000, 2));
+        assert!(matches!(check, OracleCheck::Widen { multiplier, .. } if multiplier == 2.0));
+
+        // Beyond the max multiplier quotes are pulled
+        let check = guard.check(&reading(PRICE, 10_000, 50));
+        assert!(matches!(check, OracleCheck::Pull(_)));
+
+        let quote = Quote {
+            bid_price: 99.9,
+            bid_size: 1.0,
+            ask_price: 100.1,
+            ask_size: 1.0,
+        };
+        let wide = widen_spread(&quote, 2.0);
+        assert!((wide.spread() - 0.4).abs() < 1e-9);
+        assert!((wide.mid() - 100.0).abs() < 1e-9);
+    }
+}
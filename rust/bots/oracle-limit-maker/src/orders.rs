@@ -0,0 +1,197 @@
+//! Live quote tracking and diffing desired quotes against resting orders.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use bot_common::units::{BaseAmount, Bps, Price};
+use bot_common::validate::ensure_non_negative;
+use serde::Deserialize;
+
+use crate::fills::Side;
+
+/// Oracle offset quote, identified by its user order id
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuoteOrder {
+    pub user_order_id: u8,
+    pub side: Side,
+    // Offset from oracle price
+    pub oracle_price_offset: Price,
+    pub base_asset_amount: BaseAmount,
+}
+
+/// How far a resting order may drift from the desired quote before it is updated
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OrderTolerance {
+    // Max price difference (BPS of oracle price)
+    pub price_bps: f64,
+    // Max size difference (percent of desired size)
+    pub size_pct: f64,
+}
+
+impl Default for OrderTolerance {
+    fn default() -> Self {
+        Self {
+            price_bps: 0.5,
+            size_pct: 10.0,
+        }
+    }
+}
+
+impl OrderTolerance {
+    /// Check the settings are usable
+    pub fn validate(&self) -> Result<()> {
+        ensure_non_negative("price_bps", self.price_bps)?;
+        ensure_non_negative("size_pct", self.size_pct)
+    }
+}
+
+/// Changes needed to move resting orders to the desired quotes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OrderDiff {
+    // Resting orders within tolerance, left untouched
+    pub keep: Vec<QuoteOrder>,
+    // Resting orders to modify in place
+    pub modify: Vec<QuoteOrder>,
+    // New orders to place
+    pub place: Vec<QuoteOrder>,
+    // User order ids to cancel
+    pub cancel: Vec<u8>,
+}
+
+impl OrderDiff {
+    /// Compare resting orders with desired quotes. `oracle_price` converts the
+    /// price tolerance into offset units.
+    pub fn new(
+        live: &[QuoteOrder],
+        desired: &[QuoteOrder],
+        tolerance: &OrderTolerance,
+        oracle_price: Price,
+    ) -> Self {
+        let price_tolerance = Bps(tolerance.price_bps)
+            .of(oracle_price)
+            .and_then(Price::checked_abs)
+            .unwrap_or(Price::ZERO);
+        let mut resting: HashMap<u8, &QuoteOrder> =
+            live.iter().map(|o| (o.user_order_id, o)).collect();
+        let mut diff = Self::default();
+
+        for order in desired {
+            match resting.remove(&order.user_order_id) {
+                Some(current) if current.side == order.side => {
+                    let price_diff = current
+                        .oracle_price_offset
+                        .abs_diff(order.oracle_price_offset);
+                    let size_diff = current.base_asset_amount.abs_diff(order.base_asset_amount)
+                        as f64
+                        / order.base_asset_amount.raw().max(1) as f64
+                        * 100.0;
+
+                    if price_diff <= price_tolerance.raw() as u64 && size_diff <= tolerance.size_pct
+                    {
+                        diff.keep.push(*current);
+                    } else {
+                        diff.modify.push(*order);
+                    }
+                }
+                Some(current) => {
+                    // Side changed, modify can't flip direction
+                    diff.cancel.push(current.user_order_id);
+                    diff.place.push(*order);
+                }
+                None => diff.place.push(*order),
+            }
+        }
+
+        // Anything left is no longer wanted
+        let mut orphaned: Vec<u8> = resting.into_keys().collect();
+        orphaned.sort_unstable();
+        diff.cancel.extend(orphaned);
+
+        diff
+    }
+
+    /// True when every resting order is within tolerance
+    pub fn is_empty(&self) -> bool {
+        self.modify.is_empty() && self.place.is_empty() && self.cancel.is_empty()
+    }
+
+    /// Resting orders once the diff has been applied
+    pub fn into_live(self) -> Vec<QuoteOrder> {
+        let mut live = self.keep;
+        live.extend(self.modify);
+        live.extend(self.place);
+        live.sort_unstable_by_key(|o| o.user_order_id);
+        live
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORACLE: Price = Price::from_raw(100_000_000); // $100
+
+    fn order(user_order_id: u8, side: Side, offset: i64, size: i64) -> QuoteOrder {
+        QuoteOrder {
+            user_order_id,
+            side,
+            oracle_price_offset: Price::from_raw(offset),
+            base_asset_amount: BaseAmount::from_raw(size),
+        }
+    }
+
+    fn tolerance() -> OrderTolerance {
+        // 1 bps of $100 = 10_000 offset units
+        OrderTolerance {
+            price_bps: 1.0,
+            size_pct: 5.0,
+        }
+    }
+
+    #[test]
+    fn test_within_tolerance_is_kept() {
+        let live = vec![order(1, Side::Buy, -50_000, 1000)];
+        let desired = vec![order(1, Side::Buy, -45_000, 1040)];
+        let diff = OrderDiff::new(&live, &desired, &tolerance(), ORACLE);
+
+        assert!(diff.is_empty());
+        // Tracking keeps the resting version
+        assert_eq!(diff.into_live(), live);
+    }
+
+    #[test]
+    fn test_modify_place_cancel() {
+        let live = vec![
+            order(1, Side::Buy, -50_000, 1000),
+            order(2, Side::Sell, 50_000, 1000),
+            order(3, Side::Sell, 80_000, 1000),
+        ];
+        let desired = vec![
+            // Price moved beyond tolerance
+            order(1, Side::Buy, -70_000, 1000),
+            // Size moved beyond tolerance
+            order(2, Side::Sell, 50_000, 1200),
+            // New level
+            order(4, Side::Buy, -90_000, 1000),
+        ];
+        let diff = OrderDiff::new(&live, &desired, &tolerance(), ORACLE);
+
+        assert_eq!(diff.keep, vec![]);
+        assert_eq!(diff.modify, vec![desired[0], desired[1]]);
+        assert_eq!(diff.place, vec![desired[2]]);
+        assert_eq!(diff.cancel, vec![3]);
+        assert_eq!(diff.into_live(), desired);
+    }
+
+    #[test]
+    fn test_side_change_replaces() {
+        let live = vec![order(1, Side::Buy, -50_000, 1000)];
+        let desired = vec![order(1, Side::Sell, 50_000, 1000)];
+        let diff = OrderDiff::new(&live, &desired, &tolerance(), ORACLE);
+
+        assert_eq!(diff.cancel, vec![1]);
+        assert_eq!(diff.place, desired);
+        assert!(diff.modify.is_empty());
+    }
+}
@@ -0,0 +1,381 @@
+//! Paper trading: a simulated exchange that fills resting quotes against the live DLOB.
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use log::info;
+use serde::Deserialize;
+
+use crate::fills::{Fill, Side};
+use crate::quote::L2Level;
+
+/// Position size below which a paper position is considered flat
+const POSITION_EPSILON: f64 = 1e-12;
+
+/// Simulated fees, negative for a rebate
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PaperConfig {
+    // Fee on simulated quote fills (BPS of notional)
+    pub maker_fee_bps: f64,
+    // Fee on simulated position closes (BPS of notional)
+    pub taker_fee_bps: f64,
+}
+
+impl PaperConfig {
+    /// Check the settings are usable
+    pub fn validate(&self) -> Result<()> {
+        for (name, fee) in [
+            ("maker_fee_bps", self.maker_fee_bps),
+            ("taker_fee_bps", self.taker_fee_bps),
+        ] {
+            if !fee.is_finite() {
+                bail!("{} must be a number, got {}", name, fee);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Simulated perp position, same sign conventions as `PerpPosition`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PaperPosition {
+    // Base units, positive = long
+    pub base_asset_amount: f64,
+    // Quote units paid (negative) or received (positive) to open the position
+    pub quote_entry_amount: f64,
+}
+
+impl PaperPosition {
+    fn apply(&mut self, side: Side, size: f64, price: f64) {
+        let signed = match side {
+            Side::Buy => size,
+            Side::Sell => -size,
+        };
+
+        // Reduce first, anything left over opens a position on the other side
+        let mut remaining = signed;
+        if self.base_asset_amount * signed < 0.0 {
+            let closing = signed.abs().min(self.base_asset_amount.abs());
+            self.quote_entry_amount -=
+                self.quote_entry_amount * closing / self.base_asset_amount.abs();
+            self.base_asset_amount += closing * signed.signum();
+            remaining -= closing * signed.signum();
+        }
+        self.base_asset_amount += remaining;
+        self.quote_entry_amount -= remaining * price;
+
+        if self.base_asset_amount.abs() < POSITION_EPSILON {
+            *self = Self::default();
+        }
+    }
+}
+
+/// Resting paper order, priced off the oracle like the real quotes
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PaperOrder {
+    user_order_id: u8,
+    side: Side,
+    // Offset from the oracle price (quote units)
+    offset: f64,
+    // Remaining size (base units)
+    size: f64,
+}
+
+/// Last market data seen for a market, used for post-only checks
+#[derive(Debug, Clone, Copy, Default)]
+struct Book {
+    oracle_price: f64,
+    best_bid: Option<f64>,
+    best_ask: Option<f64>,
+}
+
+/// Simulated exchange holding paper orders and positions per market
+#[derive(Debug, Default)]
+pub struct PaperExchange {
+    config: PaperConfig,
+    orders: HashMap<u16, Vec<PaperOrder>>,
+    positions: HashMap<u16, PaperPosition>,
+    books: HashMap<u16, Book>,
+}
+
+impl PaperExchange {
+    pub fn new(config: PaperConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    pub fn set_config(&mut self, config: PaperConfig) {
+        self.config = config;
+    }
+
+    /// Place a post-only quote, dropped if it would cross the last seen book
+    pub fn place(
+        &mut self,
+        market_index: u16,
+        user_order_id: u8,
+        side: Side,
+        offset: f64,
+        size: f64,
+    ) {
+        self.cancel(market_index, &[user_order_id]);
+
+        let book = self.books.get(&market_index).copied().unwrap_or_default();
+        let price = book.oracle_price + offset;
+        let crosses = match side {
+            Side::Buy => book.best_ask.is_some_and(|ask| price >= ask),
+            Side::Sell => book.best_bid.is_some_and(|bid| price <= bid),
+        };
+        if crosses {
+            info!(
+                "[paper] Post-only {:?} at ${:.4} on market {} would cross, not placed",
+                side, price, market_index
+            );
+            return;
+        }
+
+        self.orders
+            .entry(market_index)
+            .or_default()
+            .push(PaperOrder {
+                user_order_id,
+                side,
+                offset,
+                size,
+            });
+    }
+
+    /// Move a resting quote, a modify on Drift is a cancel and re-place
+    pub fn modify(&mut self, market_index: u16, user_order_id: u8, offset: f64, size: f64) {
+        let side = self
+            .orders
+            .get(&market_index)
+            .and_then(|orders| orders.iter().find(|o| o.user_order_id == user_order_id))
+            .map(|o| o.side);
+        if let Some(side) = side {
+            self.place(market_index, user_order_id, side, offset, size);
+        }
+    }
+
+    pub fn cancel(&mut self, market_index: u16, user_order_ids: &[u8]) {
+        if let Some(orders) = self.orders.get_mut(&market_index) {
+            orders.retain(|o| !user_order_ids.contains(&o.user_order_id));
+        }
+    }
+
+    pub fn cancel_market(&mut self, market_index: u16) {
+        self.orders.remove(&market_index);
+    }
+
+    pub fn cancel_all(&mut self) {
+        self.orders.clear();
+    }
+
+    /// Number of resting paper orders in a market
+    pub fn open_orders(&self, market_index: u16) -> usize {
+        self.orders.get(&market_index).map_or(0, Vec::len)
+    }
+
+    pub fn position(&self, market_index: u16) -> PaperPosition {
+        self.positions
+            .get(&market_index)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Fill resting quotes that the book now trades through at the current oracle
+    /// price. Quotes fill at their own price, each book level is used once per call.
+    pub fn match_orders(
+        &mut self,
+        market_index: u16,
+        oracle_price: f64,
+        bids: &[L2Level],
+        asks: &[L2Level],
+    ) -> Vec<Fill> {
+        self.books.insert(
+            market_index,
+            Book {
+                oracle_price,
+                best_bid: bids.first().map(|l| l.price),
+                best_ask: asks.first().map(|l| l.price),
+            },
+        );
+
+        let Some(orders) = self.orders.get_mut(&market_index) else {
+            return Vec::new();
+        };
+
+        let mut bid_liquidity: Vec<L2Level> = bids.to_vec();
+        let mut ask_liquidity: Vec<L2Level> = asks.to_vec();
+        let mut fills = Vec::new();
+
+        for order in orders.iter_mut() {
+            let price = oracle_price + order.offset;
+            // A resting bid is hit by asks at or below it and vice versa
+            let levels = match order.side {
+                Side::Buy => &mut ask_liquidity,
+                Side::Sell => &mut bid_liquidity,
+            };
+
+            let mut filled = 0.0;
+            for level in levels.iter_mut() {
+                let crosses = match order.side {
+                    Side::Buy => level.price <= price,
+                    Side::Sell => level.price >= price,
+                };
+                if !crosses || filled >= order.size {
+                    break;
+                }
+                let size = level.size.min(order.size - filled);
+                level.size -= size;
+                filled += size;
+            }
+            levels.retain(|l| l.size > 0.0);
+
+            if filled <= 0.0 {
+                continue;
+            }
+            order.size -= filled;
+            fills.push(Fill {
+                market_index,
+                side: order.side,
+                size: filled,
+                price,
+                fee: filled * price * self.config.maker_fee_bps / 10_000.0,
+            });
+        }
+        orders.retain(|o| o.size > POSITION_EPSILON);
+
+        let position = self.positions.entry(market_index).or_default();
+        for fill in &fills {
+            position.apply(fill.side, fill.size, fill.price);
+        }
+        fills
+    }
+
+    /// Close a market's position at `price` as a taker
+    pub fn close_position(&mut self, market_index: u16, price: f64) -> Option<Fill> {
+        let position = self.positions.remove(&market_index)?;
+        let size = position.base_asset_amount.abs();
+        Some(Fill {
+            market_index,
+            side: if position.base_asset_amount > 0.0 {
+                Side::Sell
+            } else {
+                Side::Buy
+            },
+            size,
+            price,
+            fee: size * price * self.config.taker_fee_bps / 10_000.0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MARKET: u16 = 0;
+
+    fn level(price: f64, size: f64) -> L2Level {
+        L2Level { price, size }
+    }
+
+    fn exchange() -> PaperExchange {
+        let mut exchange = PaperExchange::new(PaperConfig {
+            maker_fee_bps: -1.0,
+            taker_fee_bps: 5.0,
+        });
+        // Book 99.9 / 100.1 around a $100 oracle
+        exchange.match_orders(MARKET, 100.0, &[level(99.9, 5.0)], &[level(100.1, 5.0)]);
+        exchange
+    }
+
+    #[test]
+    fn test_fill_on_oracle_move() {
+        let mut exchange = exchange();
+        exchange.place(MARKET, 1, Side::Buy, -0.05, 1.0);
+        exchange.place(MARKET, 2, Side::Sell, 0.05, 1.0);
+        assert_eq!(exchange.open_orders(MARKET), 2);
+
+        // Oracle rallies to $100.10, the resting ask at $100.15 is lifted
+        // by a bid at $100.20 but only 0.4 is available
+        let fills = exchange.match_orders(
+            MARKET,
+            100.1,
+            &[level(100.2, 0.4), level(100.0, 5.0)],
+            &[level(100.3, 5.0)],
+        );
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].side, Side::Sell);
+        assert!((fills[0].size - 0.4).abs() < 1e-9);
+        assert!((fills[0].price - 100.15).abs() < 1e-9);
+        // Maker rebate
+        assert!(fills[0].fee < 0.0);
+
+        let position = exchange.position(MARKET);
+        assert!((position.base_asset_amount + 0.4).abs() < 1e-9);
+        assert!((position.quote_entry_amount - 40.06).abs() < 1e-9);
+
+        // Same book again, the partially filled ask keeps resting with 0.6
+        let fills =
+            exchange.match_orders(MARKET, 100.1, &[level(100.0, 5.0)], &[level(100.3, 5.0)]);
+        assert!(fills.is_empty());
+        assert_eq!(exchange.open_orders(MARKET), 2);
+    }
+
+    #[test]
+    fn test_post_only_and_cancel() {
+        let mut exchange = exchange();
+
+        // Bid at $100.20 would cross the $100.10 ask
+        exchange.place(MARKET, 1, Side::Buy, 0.2, 1.0);
+        assert_eq!(exchange.open_orders(MARKET), 0);
+
+        exchange.place(MARKET, 1, Side::Buy, -0.05, 1.0);
+        exchange.place(MARKET, 2, Side::Sell, 0.05, 1.0);
+        // Modifying into the book drops the order
+        exchange.modify(MARKET, 2, -0.2, 1.0);
+        assert_eq!(exchange.open_orders(MARKET), 1);
+
+        exchange.cancel(MARKET, &[1]);
+        assert_eq!(exchange.open_orders(MARKET), 0);
+    }
+
+    #[test]
+    fn test_position_accounting() {
+        let mut position = PaperPosition::default();
+
+        position.apply(Side::Buy, 2.0, 100.0);
+        assert_eq!(position.base_asset_amount, 2.0);
+        assert_eq!(position.quote_entry_amount, -200.0);
+
+        // Half closed, entry cost scales down
+        position.apply(Side::Sell, 1.0, 110.0);
+        assert_eq!(position.base_asset_amount, 1.0);
+        assert_eq!(position.quote_entry_amount, -100.0);
+
+        // Flip to short 1 @ $120
+        position.apply(Side::Sell, 2.0, 120.0);
+        assert_eq!(position.base_asset_amount, -1.0);
+        assert_eq!(position.quote_entry_amount, 120.0);
+
+        position.apply(Side::Buy, 1.0, 100.0);
+        assert_eq!(position, PaperPosition::default());
+
+        let mut exchange = exchange();
+        exchange.positions.insert(
+            MARKET,
+            PaperPosition {
+                base_asset_amount: -1.0,
+                quote_entry_amount: 120.0,
+            },
+        );
+        let close = exchange.close_position(MARKET, 100.0).unwrap();
+        assert_eq!(close.side, Side::Buy);
+        assert!((close.fee - 0.05).abs() < 1e-9);
+        assert_eq!(exchange.position(MARKET), PaperPosition::default());
+    }
+}
@@ -0,0 +1,236 @@
+//! Quote pipeline shared by live quoting and the backtester: requote trigger,
+//! quote model, funding, volatility and oracle guard adjustments, ladder and
+//! order sizing rounded to market precision.
+
+use anyhow::Result;
+use bot_common::units::{Bps, Price};
+
+use crate::config::BotConfig;
+use crate::fills::Side;
+use crate::funding::skew_quote;
+use crate::ladder::Ladder;
+use crate::oracle_guard::widen_spread;
+use crate::orders::QuoteOrder;
+use crate::quote::{MarketSnapshot, Quote, QuoteModel};
+use crate::volatility::{adapt_spread, RealizedVol, VolEstimate, VolatilityConfig};
+
+/// Oracle move since the last update when a requote is due, `None` while
+/// debounced or below the threshold. The first cycle always requotes.
+pub fn requote_trigger(
+    config: &BotConfig,
+    prev_price: Price,
+    last_update_ms: u64,
+    now_ms: u64,
+    new_price: Price,
+) -> Option<Bps> {
+    // Debounce check
+    if now_ms.saturating_sub(last_update_ms) < config.debounce_ms {
+        return None;
+    }
+
+    // Allow first cycle
+    let Some(change) = Bps::change(prev_price, new_price) else {
+        return Some(Bps(0.0));
+    };
+
+    // Price change check
+    let change = Bps(change.0.abs());
+    (change.0 >= config.oracle_change_threshold_bps as f64).then_some(change)
+}
+
+/// Desired order at one ladder level
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlannedOrder {
+    pub level: usize,
+    // Quote price (quote units)
+    pub price: f64,
+    pub order: QuoteOrder,
+}
+
+/// Output of one pass through the quote pipeline
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuotePlan {
+    // Final top of ladder quote after all adjustments
+    pub quote: Quote,
+    // Volatility estimate and spread multiplier, when adaptive spreads are on
+    pub volatility: Option<(VolEstimate, f64)>,
+    // Orders to rest, bids first, levels rounding to zero or below the
+    // minimum size skipped unless bumped
+    pub orders: Vec<PlannedOrder>,
+}
+
+impl QuotePlan {
+    pub fn desired(&self) -> Vec<QuoteOrder> {
+        self.orders.iter().map(|planned| planned.order).collect()
+    }
+}
+
+/// Run the quote model on a snapshot, shift it by the funding skew, adapt the
+/// spread to realized volatility, widen it for tripped oracle guards, expand it
+/// into ladder levels and convert those to oracle offset orders on the
+/// market's tick and step sizes
+pub fn plan_quotes(
+    model: &mut dyn QuoteModel,
+    snapshot: &MarketSnapshot,
+    funding_skew_bps: f64,
+    volatility: &RealizedVol,
+    vol_config: Option<&VolatilityConfig>,
+    oracle_widen: f64,
+    user_order_id_base: u8,
+) -> Result<QuotePlan> {
+    let oracle_price = snapshot.oracle_price;
+    let mut quote = model.quote(snapshot)?;
+
+    // Move the reservation price toward the side that collects funding
+    if funding_skew_bps != 0.0 {
+        quote = skew_quote(&quote, oracle_price, funding_skew_bps);
+    }
+
+    // Widen or narrow with realized volatility
+    let mut vol_adjustment = None;
+    if let Some(vol_config) = vol_config {
+        let estimate = VolEstimate::new(volatility, vol_config);
+        let (adapted, multiplier) = adapt_spread(&quote, oracle_price, &estimate, vol_config);
+        quote = adapted;
+        vol_adjustment = Some((estimate, multiplier));
+    }
+
+    // Oracle guards widen on top of everything else
+    if oracle_widen > 1.0 {
+        quote = widen_spread(&quote, oracle_widen);
+    }
+
+    // Expand into ladder levels and convert to oracle offsets
+    let ladder = Ladder::build(&quote, oracle_price, &snapshot.config.ladder);
+    let levels_per_side = snapshot.config.ladder.levels as u8;
+    let sides = [
+        (Side::Buy, &ladder.bids, 0),
+        (Side::Sell, &ladder.asks, levels_per_side),
+    ];
+
+    let mut orders = Vec::with_capacity(ladder.len());
+    let precision = &snapshot.precision;
+    let rounding = &snapshot.config.rounding;
+    for (side, levels, id_offset) in sides {
+        for (level, quote_level) in levels.iter().enumerate() {
+            let Some(base_asset_amount) = precision.base_amount(quote_level.size, rounding)? else {
+                continue;
+            };
+            let oracle_price_offset =
+                precision.price_offset(side, quote_level.price - oracle_price, rounding.mode)?;
+            orders.push(PlannedOrder {
+                level,
+                price: oracle_price + oracle_price_offset.to_f64(),
+                order: QuoteOrder {
+                    user_order_id: user_order_id_base + id_offset + level as u8,
+                    side,
+                    oracle_price_offset,
+                    base_asset_amount,
+                },
+            });
+        }
+    }
+
+    Ok(QuotePlan {
+        quote,
+        volatility: vol_adjustment,
+        orders,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MarketConfig;
+    use crate::ladder::{LadderConfig, LevelSpacing};
+    use crate::precision::MarketPrecision;
+    use crate::quote::{L2Level, SpreadMultiplierModel};
+    use bot_common::units::BaseAmount;
+
+    #[test]
+    fn test_requote_trigger() {
+        let config = BotConfig {
+            debounce_ms: 1000,
+            oracle_change_threshold_bps: 5.0,
+            ..Default::default()
+        };
+        let price = Price::from_raw(100_000_000);
+        let moved = |units| Price::from_raw(price.raw() + units);
+
+        // First cycle always quotes once past the debounce
+        assert_eq!(
+            requote_trigger(&config, Price::ZERO, 0, 1000, price),
+            Some(Bps(0.0))
+        );
+        assert_eq!(
+            requote_trigger(&config, price, 5000, 5500, moved(price.raw())),
+            None
+        );
+        // 3 bps is below the threshold, 10 bps isn't
+        assert_eq!(
+            requote_trigger(&config, price, 0, 9000, moved(30_000)),
+            None
+        );
+        let bps = requote_trigger(&config, price, 0, 9000, moved(-100_000)).unwrap();
+        assert!((bps.0 - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_plan_quotes() {
+        let config = MarketConfig {
+            ladder: LadderConfig {
+                levels: 2,
+                spacing: LevelSpacing::Linear { step_bps: 10.0 },
+                // Second level rounds to zero size and is skipped
+                size_multipliers: vec![1.0, 1e-12],
+            },
+            ..MarketConfig::test_default()
+        };
+        let snapshot = MarketSnapshot {
+            oracle_price: 100.0,
+            bids: vec![L2Level {
+                price: 99.9,
+                size: 1.0,
+            }],
+            asks: vec![L2Level {
+                price: 100.1,
+                size: 1.0,
+            }],
+            position: 0.0,
+            volatility: None,
+            // $0.01 ticks, 0.001 steps
+            precision: MarketPrecision::new(10_000, 1_000_000, 1_000_000),
+            config: &config,
+        };
+
+        let plan = plan_quotes(
+            &mut SpreadMultiplierModel,
+            &snapshot,
+            -1.0,
+            &RealizedVol::new(0),
+            None,
+            2.0,
+            4,
+        )
+        .unwrap();
+
+        // 0.3 spread widened 2x by the oracle guard, 1 bp lower for funding
+        assert!((plan.quote.spread() - 0.6).abs() < 1e-9);
+        assert!((plan.quote.mid() - 99.99).abs() < 1e-9);
+        assert!(plan.volatility.is_none());
+
+        let desired = plan.desired();
+        assert_eq!(desired.len(), 2);
+        assert_eq!(desired[0].user_order_id, 4);
+        assert_eq!(desired[0].side, Side::Buy);
+        assert_eq!(desired[0].oracle_price_offset, Price::from_raw(-310_000));
+        assert_eq!(
+            desired[0].base_asset_amount,
+            BaseAmount::from_raw(100_000_000)
+        );
+        assert_eq!(desired[0].oracle_price_offset.to_string(), "-0.310000");
+        // Asks start after both bid slots
+        assert_eq!(desired[1].user_order_id, 6);
+        assert_eq!(desired[1].oracle_price_offset, Price::from_raw(290_000));
+    }
+}
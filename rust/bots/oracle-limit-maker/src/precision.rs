@@ -0,0 +1,173 @@
+//! Market precision: rounds quote prices and sizes to a perp market's tick
+//! size, step size and minimum order size, with checked conversions into
+//! order units.
+
+use anyhow::{anyhow, Result};
+use bot_common::units::{BaseAmount, Price};
+use serde::Deserialize;
+
+use crate::fills::Side;
+
+/// Which way prices and sizes round to market precision
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundingMode {
+    // Prices away from the book (bids down, asks up), sizes down
+    #[default]
+    Passive,
+    // Prices toward the book (bids up, asks down), sizes up
+    Aggressive,
+}
+
+/// What happens to orders below the market's minimum order size
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BelowMinimum {
+    // Leave the level out
+    #[default]
+    Skip,
+    // Quote the minimum order size instead
+    Bump,
+}
+
+/// Per-market rounding settings
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoundingConfig {
+    pub mode: RoundingMode,
+    pub below_minimum: BelowMinimum,
+}
+
+/// Order precision of a perp market, from its AMM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarketPrecision {
+    // Price increment
+    pub tick_size: Price,
+    // Base amount increment
+    pub step_size: BaseAmount,
+    // Smallest order
+    pub min_order_size: BaseAmount,
+}
+
+impl Default for MarketPrecision {
+    /// Smallest units, for markets without account data
+    fn default() -> Self {
+        Self::new(1, 1, 1)
+    }
+}
+
+impl MarketPrecision {
+    /// From protocol units, zero tick and step sizes are treated as 1
+    pub fn new(tick_size: u64, step_size: u64, min_order_size: u64) -> Self {
+        let unit = |raw: u64| i64::try_from(raw.max(1)).unwrap_or(i64::MAX);
+        Self {
+            tick_size: Price::from_raw(unit(tick_size)),
+            step_size: BaseAmount::from_raw(unit(step_size)),
+            min_order_size: BaseAmount::from_unsigned(min_order_size)
+                .unwrap_or(BaseAmount::from_raw(i64::MAX)),
+        }
+    }
+
+    /// Oracle price offset for a quote `offset` from the oracle price (quote
+    /// units), on a tick multiple. Errors if it doesn't fit an order.
+    pub fn price_offset(&self, side: Side, offset: f64, mode: RoundingMode) -> Result<Price> {
+        let round_up = match (side, mode) {
+            (Side::Buy, RoundingMode::Passive) | (Side::Sell, RoundingMode::Aggressive) => false,
+            (Side::Buy, RoundingMode::Aggressive) | (Side::Sell, RoundingMode::Passive) => true,
+        };
+        Price::from_f64(offset)
+            .and_then(|price| price.checked_round_to(self.tick_size, round_up))
+            .filter(|price| price.to_offset().is_some())
+            .ok_or_else(|| anyhow!("price offset {} out of range", offset))
+    }
+
+    /// Base amount for a `size` (base units) on a step multiple. `None` for
+    /// empty levels, and below the minimum order size unless bumped to it.
+    pub fn base_amount(&self, size: f64, config: &RoundingConfig) -> Result<Option<BaseAmount>> {
+        let out_of_range = || anyhow!("order size {} out of range", size);
+        let amount = BaseAmount::from_f64(size)
+            .filter(|amount| !amount.is_negative())
+            .ok_or_else(out_of_range)?;
+        if amount.is_zero() {
+            return Ok(None);
+        }
+        let step = self.step_size;
+        let rounded = amount
+            .checked_round_to(step, config.mode == RoundingMode::Aggressive)
+            .ok_or_else(out_of_range)?;
+
+        if !rounded.is_zero() && rounded >= self.min_order_size {
+            Ok(Some(rounded))
+        } else if config.below_minimum == BelowMinimum::Skip {
+            Ok(None)
+        } else {
+            // Smallest step multiple at or above the minimum
+            self.min_order_size
+                .max(step)
+                .checked_round_to(step, true)
+                .map(Some)
+                .ok_or_else(out_of_range)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_offsets() {
+        // $0.0001 ticks
+        let precision = MarketPrecision::new(100, 1, 1);
+        let offset = |side, offset, mode| precision.price_offset(side, offset, mode).unwrap().raw();
+        let (passive, aggressive) = (RoundingMode::Passive, RoundingMode::Aggressive);
+
+        // Passive rounds away from the book, aggressive toward it
+        assert_eq!(offset(Side::Buy, -0.01234, passive), -12_400);
+        assert_eq!(offset(Side::Buy, -0.01234, aggressive), -12_300);
+        assert_eq!(offset(Side::Sell, 0.01234, passive), 12_400);
+        assert_eq!(offset(Side::Sell, 0.01234, aggressive), 12_300);
+        // Exact multiples stay put, float noise doesn't add a tick
+        assert_eq!(offset(Side::Sell, 0.0123, passive), 12_300);
+
+        // Beyond an i32 offset or not a number
+        assert!(precision
+            .price_offset(Side::Sell, 5_000.0, passive)
+            .is_err());
+        assert!(precision
+            .price_offset(Side::Buy, f64::NAN, passive)
+            .is_err());
+    }
+
+    #[test]
+    fn test_base_amounts() {
+        // 0.01 steps, 0.05 minimum
+        let precision = MarketPrecision::new(1, 10_000_000, 50_000_000);
+        let mut config = RoundingConfig::default();
+
+        assert_eq!(
+            precision.base_amount(0.123, &config).unwrap(),
+            Some(BaseAmount::from_raw(120_000_000))
+        );
+        assert_eq!(precision.base_amount(0.04, &config).unwrap(), None);
+        assert_eq!(precision.base_amount(0.0, &config).unwrap(), None);
+
+        config.mode = RoundingMode::Aggressive;
+        assert_eq!(
+            precision.base_amount(0.123, &config).unwrap(),
+            Some(BaseAmount::from_raw(130_000_000))
+        );
+
+        // Bumped to the minimum, empty levels stay empty
+        config.below_minimum = BelowMinimum::Bump;
+        assert_eq!(
+            precision.base_amount(0.001, &config).unwrap(),
+            Some(BaseAmount::from_raw(50_000_000))
+        );
+        assert_eq!(precision.base_amount(0.0, &config).unwrap(), None);
+
+        assert!(precision.base_amount(-0.1, &config).is_err());
+        assert!(precision.base_amount(f64::INFINITY, &config).is_err());
+        assert!(precision.base_amount(1e12, &config).is_err());
+    }
+}
@@ -0,0 +1,383 @@
+//! Quote models: turn a market snapshot into desired bid/ask prices and sizes.
+
+use std::fmt;
+
+use anyhow::{bail, Result};
+use log::{info, warn};
+
+use crate::config::MarketConfig;
+use crate::precision::MarketPrecision;
+
+/// Single L2 price level (quote price, base size)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct L2Level {
+    pub price: f64,
+    pub size: f64,
+}
+
+/// Everything a quote model needs to price one update
+#[derive(Debug, Clone)]
+pub struct MarketSnapshot<'a> {
+    // Oracle price (quote units)
+    pub oracle_price: f64,
+    // Bid levels, best first
+    pub bids: Vec<L2Level>,
+    // Ask levels, best first
+    pub asks: Vec<L2Level>,
+    // Current position (base units, positive = long)
+    pub position: f64,
+    // Realized oracle volatility per sqrt(second), if enough samples
+    pub volatility: Option<f64>,
+    // Tick, step and minimum order size quotes are rounded to
+    pub precision: MarketPrecision,
+    pub config: &'a MarketConfig,
+}
+
+impl MarketSnapshot<'_> {
+    pub fn best_bid(&self) -> Option<L2Level> {
+        self.bids.first().copied()
+    }
+
+    pub fn best_ask(&self) -> Option<L2Level> {
+        self.asks.first().copied()
+    }
+
+    /// Classify the top of book
+    pub fn book_state(&self) -> BookState {
+        match (self.best_bid(), self.best_ask()) {
+            (None, None) => BookState::Empty,
+            (None, Some(_)) => BookState::NoBids,
+            (Some(_), None) => BookState::NoAsks,
+            (Some(bid), Some(ask)) if bid.price > ask.price => BookState::Crossed,
+            (Some(bid), Some(ask)) if bid.price == ask.price => BookState::Locked,
+            _ => BookState::Normal,
+        }
+    }
+
+    /// Position as a fraction of the configured max position
+    pub fn position_ratio(&self) -> f64 {
+        self.position / self.config.max_position_size
+    }
+}
+
+/// Top of book shape, anything but `Normal` has no usable market spread
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookState {
+    Normal,
+    NoBids,
+    NoAsks,
+    Empty,
+    // Best bid equals best ask
+    Locked,
+    // Best bid above best ask
+    Crossed,
+}
+
+impl fmt::Display for BookState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self {
+            BookState::Normal => "normal",
+            BookState::NoBids => "no bids",
+            BookState::NoAsks => "no asks",
+            BookState::Empty => "empty",
+            BookState::Locked => "locked",
+            BookState::Crossed => "crossed",
+        };
+        write!(f, "{}", state)
+    }
+}
+
+/// Desired quotes, prices in quote units and sizes in base units
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quote {
+    pub bid_price: f64,
+    pub bid_size: f64,
+    pub ask_price: f64,
+    pub ask_size: f64,
+}
+
+impl Quote {
+    pub fn spread(&self) -> f64 {
+        self.ask_price - self.bid_price
+    }
+
+    pub fn mid(&self) -> f64 {
+        (self.bid_price + self.ask_price) / 2.0
+    }
+}
+
+/// Pricing model used by the maker to compute quotes
+pub trait QuoteModel: Send {
+    /// Compute desired quotes for the given snapshot
+    fn quote(&mut self, snapshot: &MarketSnapshot) -> Result<Quote>;
+}
+
+/// Default model: quote around the L2 mid at a multiple of the market spread,
+/// with tanh inventory skew and linear size reduction. Falls back to quoting
+/// around the oracle when the book is one-sided, empty, locked or crossed.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SpreadMultiplierModel;
+
+impl QuoteModel for SpreadMultiplierModel {
+    fn quote(&mut self, snapshot: &MarketSnapshot) -> Result<Quote> {
+        let (best_bid, best_ask) = match (snapshot.best_bid(), snapshot.best_ask()) {
+            (Some(bid), Some(ask)) if snapshot.book_state() == BookState::Normal => (bid, ask),
+            _ => {
+                let state = snapshot.book_state();
+                let Some(spread_bps) = snapshot.config.fallback_spread_bps else {
+                    bail!("Orderbook is {}, no fallback spread configured", state);
+                };
+                warn!(
+                    "[{}] Orderbook is {}, quoting {:.1} bps around oracle",
+                    snapshot.config.symbol, state, spread_bps
+                );
+                return Ok(fallback_quote(snapshot, spread_bps));
+            }
+        };
+
+        let mid_price = (best_bid.price + best_ask.price) / 2.0;
+        let current_spread = best_ask.price - best_bid.price;
+
+        info!(
+            "[{}] L2 snapshot: best_bid ${:.2}, best_ask ${:.2}, spread ${:.4}",
+            snapshot.config.symbol, best_bid.price, best_ask.price, current_spread
+        );
+
+        // Calculate our spread based on market spread
+        let our_spread = current_spread * snapshot.config.spread_multiplier;
+
+        let position_ratio = snapshot.position_ratio();
+        let (bid_size, ask_size) =
+            calculate_dynamic_sizing(snapshot.config.order_size, position_ratio);
+        let (bid_mult, ask_mult) = calculate_inventory_skew(position_ratio);
+
+        info!(
+            "[{}] Position: base={:.4}, ratio={:.3}, bid_mult={:.3}, ask_mult={:.3}",
+            snapshot.config.symbol, snapshot.position, position_ratio, bid_mult, ask_mult
+        );
+
+        Ok(Quote {
+            bid_price: mid_price - (our_spread / 2.0 * bid_mult),
+            bid_size,
+            ask_price: mid_price + (our_spread / 2.0 * ask_mult),
+            ask_size,
+        })
+    }
+}
+
+/// Quote around the oracle at a fixed spread, with the same inventory skew
+/// and sizing as the spread multiplier model. On a one-sided book the quote
+/// stays behind the remaining side so post-only orders aren't rejected.
+pub fn fallback_quote(snapshot: &MarketSnapshot, spread_bps: f64) -> Quote {
+    let oracle_price = snapshot.oracle_price;
+    let half_spread = oracle_price * spread_bps / 20_000.0;
+
+    let position_ratio = snapshot.position_ratio();
+    let (bid_size, ask_size) = calculate_dynamic_sizing(snapshot.config.order_size, position_ratio);
+    let (bid_mult, ask_mult) = calculate_inventory_skew(position_ratio);
+
+    let mut bid_price = oracle_price - half_spread * bid_mult;
+    let mut ask_price = oracle_price + half_spread * ask_mult;
+
+    // Crossed or locked levels are unreliable, only respect a lone side
+    match snapshot.book_state() {
+        BookState::NoBids => {
+            if let Some(best_ask) = snapshot.best_ask() {
+                bid_price = bid_price.min(best_ask.price - half_spread);
+            }
+        }
+        BookState::NoAsks => {
+            if let Some(best_bid) = snapshot.best_bid() {
+                ask_price = ask_price.max(best_bid.price + half_spread);
+            }
+        }
+        _ => {}
+    }
+
+    Quote {
+        bid_price,
+        bid_size,
+        ask_price,
+        ask_size,
+    }
+}
+
+/// Calculate inventory skew multipliers based on position
+pub fn calculate_inventory_skew(position_ratio: f64) -> (f64, f64) {
+    if position_ratio.abs() <= 0.1 {
+        return (1.0, 1.0);
+    }
+
+    let abs_ratio = position_ratio.abs();
+    let max_skew = 0.8;
+    let scale = 0.2;
+    let skew = max_skew * (abs_ratio / scale).tanh();
+
+    if position_ratio > 0.0 {
+        // Long position: widen bids, tighten asks
+        (1.0 + skew, 1.0 - skew)
+    } else {
+        // Short position: tighten bids, widen asks
+        (1.0 - skew, 1.0 + skew)
+    }
+}
+
+/// Calculate dynamic order sizing based on position
+pub fn calculate_dynamic_sizing(base_size: f64, position_ratio: f64) -> (f64, f64) {
+    let abs_ratio = position_ratio.abs();
+    let reduction_start_pct = 0.2;
+
+    // At max position, stop adding to that side
+    if abs_ratio >= 1.0 {
+        return if position_ratio > 0.0 {
+            (0.0, base_size)
+        } else {
+            (base_size, 0.0)
+        };
+    }
+
+    // Gradually reduce size as position grows
+    let size_multiplier = if abs_ratio > reduction_start_pct {
+        let slope = -1.0 / (1.0 - reduction_start_pct);
+        let intercept = -slope;
+        slope * abs_ratio + intercept
+    } else {
+        1.0
+    };
+
+    if position_ratio > 0.0 {
+        (base_size * size_multiplier, base_size)
+    } else if position_ratio < 0.0 {
+        (base_size, base_size * size_multiplier)
+    } else {
+        (base_size, base_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config() -> MarketConfig {
+        MarketConfig {
+            order_size: 0.001,
+            max_position_size: 0.01,
+            ..MarketConfig::test_default()
+        }
+    }
+
+    fn snapshot(config: &MarketConfig, position: f64) -> MarketSnapshot<'_> {
+        MarketSnapshot {
+            oracle_price: 100.0,
+            bids: vec![L2Level {
+                price: 99.0,
+                size: 1.0,
+            }],
+            asks: vec![L2Level {
+                price: 101.0,
+                size: 1.0,
+            }],
+            position,
+            volatility: None,
+            precision: MarketPrecision::default(),
+            config,
+        }
+    }
+
+    #[test]
+    fn test_inventory_skew() {
+        assert_eq!(calculate_inventory_skew(0.05), (1.0, 1.0));
+
+        let (bid_mult, ask_mult) = calculate_inventory_skew(0.5);
+        assert!(bid_mult > 1.0 && ask_mult < 1.0);
+        assert!((bid_mult + ask_mult - 2.0).abs() < 1e-12);
+
+        let (bid_mult, ask_mult) = calculate_inventory_skew(-0.5);
+        assert!(bid_mult < 1.0 && ask_mult > 1.0);
+    }
+
+    #[test]
+    fn test_dynamic_sizing() {
+        assert_eq!(calculate_dynamic_sizing(1.0, 0.0), (1.0, 1.0));
+        assert_eq!(calculate_dynamic_sizing(1.0, 1.0), (0.0, 1.0));
+        assert_eq!(calculate_dynamic_sizing(1.0, -1.2), (1.0, 0.0));
+
+        let (bid_size, ask_size) = calculate_dynamic_sizing(1.0, 0.6);
+        assert!((bid_size - 0.5).abs() < 1e-12);
+        assert_eq!(ask_size, 1.0);
+    }
+
+    #[test]
+    fn test_spread_multiplier_model() {
+        let config = test_config();
+        let quote = SpreadMultiplierModel
+            .quote(&snapshot(&config, 0.0))
+            .unwrap();
+
+        assert_eq!(quote.mid(), 100.0);
+        assert!((quote.spread() - 3.0).abs() < 1e-12);
+        assert_eq!(quote.bid_size, config.order_size);
+        assert_eq!(quote.ask_size, config.order_size);
+
+        // Long inventory skews quotes down
+        let quote = SpreadMultiplierModel
+            .quote(&snapshot(&config, 0.005))
+            .unwrap();
+        assert!(quote.mid() < 100.0);
+        assert!(quote.bid_size < quote.ask_size);
+    }
+
+    #[test]
+    fn test_empty_book_errors() {
+        let config = test_config();
+        let mut snap = snapshot(&config, 0.0);
+        snap.bids.clear();
+
+        assert_eq!(snap.book_state(), BookState::NoBids);
+        assert!(SpreadMultiplierModel.quote(&snap).is_err());
+    }
+
+    #[test]
+    fn test_fallback_quotes_around_oracle() {
+        let config = MarketConfig {
+            fallback_spread_bps: Some(20.0),
+            ..test_config()
+        };
+
+        let mut empty = snapshot(&config, 0.0);
+        empty.bids.clear();
+        empty.asks.clear();
+        assert_eq!(empty.book_state(), BookState::Empty);
+        let quote = SpreadMultiplierModel.quote(&empty).unwrap();
+        assert!((quote.bid_price - 99.9).abs() < 1e-9);
+        assert!((quote.ask_price - 100.1).abs() < 1e-9);
+
+        // Lone ask inside our spread pushes the bid behind it
+        let mut no_bids = snapshot(&config, 0.0);
+        no_bids.bids.clear();
+        no_bids.asks[0].price = 99.95;
+        let quote = SpreadMultiplierModel.quote(&no_bids).unwrap();
+        assert!((quote.bid_price - 99.85).abs() < 1e-9);
+        assert!((quote.ask_price - 100.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_crossed_and_locked_books() {
+        let config = MarketConfig {
+            fallback_spread_bps: Some(20.0),
+            ..test_config()
+        };
+
+        let mut crossed = snapshot(&config, 0.0);
+        crossed.bids[0].price = 101.5;
+        assert_eq!(crossed.book_state(), BookState::Crossed);
+        let quote = SpreadMultiplierModel.quote(&crossed).unwrap();
+        assert!(quote.spread() > 0.0);
+        assert!((quote.mid() - 100.0).abs() < 1e-9);
+
+        let mut locked = snapshot(&config, 0.0);
+        locked.bids[0].price = 101.0;
+        assert_eq!(locked.book_state(), BookState::Locked);
+        assert!(SpreadMultiplierModel.quote(&locked).unwrap().spread() > 0.0);
+    }
+}
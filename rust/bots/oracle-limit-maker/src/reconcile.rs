@@ -0,0 +1,149 @@
+//! Reconciliation of tracked quotes against open orders on the user account.
+
+use bot_common::units::{BaseAmount, Price};
+use std::collections::HashSet;
+
+use crate::fills::Side;
+use crate::orders::QuoteOrder;
+
+/// Open order as read from the user account
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OnChainOrder {
+    pub order_id: u32,
+    pub user_order_id: u8,
+    pub side: Side,
+    pub oracle_price_offset: Price,
+    // Unfilled size
+    pub base_asset_amount: BaseAmount,
+}
+
+/// Differences between tracked quotes and on-chain orders for one market
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Reconciliation {
+    // On-chain orders the bot doesn't track
+    pub orphaned: Vec<OnChainOrder>,
+    // Tracked quotes that aren't on-chain
+    pub missing: Vec<QuoteOrder>,
+    // Tracked quotes whose on-chain order differs (expected, actual)
+    pub mismatched: Vec<(QuoteOrder, OnChainOrder)>,
+    // Tracked quotes found on-chain as expected
+    pub matched: usize,
+}
+
+impl Reconciliation {
+    pub fn new(tracked: &[QuoteOrder], on_chain: &[OnChainOrder]) -> Self {
+        let mut result = Self::default();
+        let mut seen = HashSet::new();
+
+        for order in on_chain {
+            let expected = tracked
+                .iter()
+                .find(|t| t.user_order_id == order.user_order_id);
+
+            match expected {
+                // Duplicate user order ids can only be leftovers
+                Some(_) if !seen.insert(order.user_order_id) => result.orphaned.push(*order),
+                Some(expected)
+                    if expected.side == order.side
+                        && expected.oracle_price_offset == order.oracle_price_offset
+                        && expected.base_asset_amount == order.base_asset_amount =>
+                {
+                    result.matched += 1;
+                }
+                Some(expected) => result.mismatched.push((*expected, *order)),
+                None => result.orphaned.push(*order),
+            }
+        }
+
+        result.missing = tracked
+            .iter()
+            .filter(|t| !seen.contains(&t.user_order_id))
+            .copied()
+            .collect();
+
+        result
+    }
+
+    /// True when on-chain orders match what the bot tracks
+    pub fn is_clean(&self) -> bool {
+        self.orphaned.is_empty() && self.missing.is_empty() && self.mismatched.is_empty()
+    }
+
+    /// Order ids to cancel: orphans and mismatched orders
+    pub fn to_cancel(&self) -> Vec<u32> {
+        self.orphaned
+            .iter()
+            .map(|o| o.order_id)
+            .chain(self.mismatched.iter().map(|(_, actual)| actual.order_id))
+            .collect()
+    }
+
+    /// Quotes to (re-)place: missing and mismatched
+    pub fn to_place(&self) -> Vec<QuoteOrder> {
+        self.missing
+            .iter()
+            .copied()
+            .chain(self.mismatched.iter().map(|(expected, _)| *expected))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracked(user_order_id: u8, offset: i64) -> QuoteOrder {
+        QuoteOrder {
+            user_order_id,
+            side: Side::Buy,
+            oracle_price_offset: Price::from_raw(offset),
+            base_asset_amount: BaseAmount::from_raw(1000),
+        }
+    }
+
+    fn on_chain(order_id: u32, user_order_id: u8, offset: i64) -> OnChainOrder {
+        OnChainOrder {
+            order_id,
+            user_order_id,
+            side: Side::Buy,
+            oracle_price_offset: Price::from_raw(offset),
+            base_asset_amount: BaseAmount::from_raw(1000),
+        }
+    }
+
+    #[test]
+    fn test_clean() {
+        let result = Reconciliation::new(&[tracked(1, -100)], &[on_chain(10, 1, -100)]);
+
+        assert!(result.is_clean());
+        assert_eq!(result.matched, 1);
+    }
+
+    #[test]
+    fn test_discrepancies() {
+        let tracked_orders = [tracked(1, -100), tracked(2, -200), tracked(3, -300)];
+        let on_chain_orders = [
+            on_chain(10, 1, -100),
+            // Wrong offset (e.g. modify never landed)
+            on_chain(11, 2, -250),
+            // Not ours
+            on_chain(12, 0, -500),
+            // Duplicate id
+            on_chain(13, 1, -100),
+        ];
+        let result = Reconciliation::new(&tracked_orders, &on_chain_orders);
+
+        assert_eq!(result.matched, 1);
+        assert_eq!(result.missing, vec![tracked(3, -300)]);
+        assert_eq!(result.to_cancel(), vec![12, 13, 11]);
+        assert_eq!(result.to_place(), vec![tracked(3, -300), tracked(2, -200)]);
+    }
+
+    #[test]
+    fn test_nothing_tracked() {
+        let result = Reconciliation::new(&[], &[on_chain(10, 1, -100)]);
+
+        assert_eq!(result.to_cancel(), vec![10]);
+        assert!(result.to_place().is_empty());
+    }
+}
@@ -0,0 +1,486 @@
+//! Market data recorder: L2 snapshots, oracle ticks, our quotes and fills
+//! written to rotating (optionally gzipped) JSONL files.
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread::JoinHandle,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context, Result};
+use flate2::{write::GzEncoder, Compression};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+
+use crate::backtest::MarketRecord;
+use crate::fills::{Fill, Side};
+use crate::pipeline::PlannedOrder;
+
+/// File name prefix of book snapshots, the backtester's input
+pub const BOOK_PREFIX: &str = "book";
+/// File name prefix of our quotes and fills
+pub const ORDERS_PREFIX: &str = "orders";
+
+/// How often buffered output is flushed to disk
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Recorder settings
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RecorderConfig {
+    // Output directory, created if missing
+    pub dir: PathBuf,
+    // L2 levels recorded per side
+    pub depth: usize,
+    // Minimum time between snapshots of a market (ms)
+    pub interval_ms: u64,
+    // Start new files after this long
+    pub rotate_secs: u64,
+    // ...or once a file holds this much uncompressed data (MB)
+    pub max_file_mb: Option<u64>,
+    // Gzip files (.jsonl.gz)
+    pub compress: bool,
+    // Only record market data, don't quote
+    pub record_only: bool,
+}
+
+impl Default for RecorderConfig {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("recordings"),
+            depth: 20,
+            interval_ms: 250,
+            rotate_secs: 3600,
+            max_file_mb: None,
+            compress: true,
+            record_only: false,
+        }
+    }
+}
+
+impl RecorderConfig {
+    /// Check the settings are usable
+    pub fn validate(&self) -> Result<()> {
+        if self.depth == 0 {
+            bail!("depth must be at least 1");
+        }
+        if self.rotate_secs == 0 {
+            bail!("rotate_secs must be > 0");
+        }
+        if self.max_file_mb == Some(0) {
+            bail!("max_file_mb must be > 0");
+        }
+        Ok(())
+    }
+}
+
+/// One of our quotes at the time it was sent
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct QuoteRecord {
+    pub side: Side,
+    pub level: usize,
+    // Quote price (quote units)
+    pub price: f64,
+    // Size (base units)
+    pub size: f64,
+}
+
+/// Our own activity, written to the orders files
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OrderEvent {
+    // Desired quotes after an update
+    Quotes {
+        ts: u64,
+        market: String,
+        oracle: f64,
+        orders: Vec<QuoteRecord>,
+    },
+    Fill {
+        ts: u64,
+        market: String,
+        side: Side,
+        size: f64,
+        price: f64,
+        fee: f64,
+    },
+}
+
+/// Line sent to the writer thread
+enum Line {
+    Book(String),
+    Orders(String),
+}
+
+/// Handle to the background writer. Dropping it without `close` loses the
+/// tail of gzipped files.
+pub struct Recorder {
+    config: RecorderConfig,
+    tx: Option<mpsc::Sender<Line>>,
+    writer: Option<JoinHandle<()>>,
+    // Last snapshot time per market (ms)
+    last_snapshot: HashMap<String, u64>,
+}
+
+impl Recorder {
+    /// Create the output directory and start the writer thread
+    pub fn start(config: RecorderConfig) -> Result<Self> {
+        fs::create_dir_all(&config.dir)
+            .with_context(|| format!("Failed to create {}", config.dir.display()))?;
+
+        let (tx, rx) = mpsc::channel();
+        let mut book = RotatingWriter::new(&config, BOOK_PREFIX);
+        let mut orders = RotatingWriter::new(&config, ORDERS_PREFIX);
+        let writer = std::thread::spawn(move || loop {
+            let (line, writer) = match rx.recv_timeout(FLUSH_INTERVAL) {
+                Ok(Line::Book(line)) => (line, &mut book),
+                Ok(Line::Orders(line)) => (line, &mut orders),
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    for writer in [&mut book, &mut orders] {
+                        if let Err(e) = writer.flush() {
+                            error!("Recorder flush failed: {}", e);
+                        }
+                    }
+                    continue;
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    for writer in [&mut book, &mut orders] {
+                        if let Err(e) = writer.finish() {
+                            error!("Recorder failed to close file: {}", e);
+                        }
+                    }
+                    break;
+                }
+            };
+            if let Err(e) = writer.write_line(&line, timestamp_ms()) {
+                error!("Recorder write failed: {}", e);
+            }
+        });
+
+        info!("Recording market data to {}", config.dir.display());
+        Ok(Self {
+            config,
+            tx: Some(tx),
+            writer: Some(writer),
+            last_snapshot: HashMap::new(),
+        })
+    }
+
+    pub fn depth(&self) -> usize {
+        self.config.depth
+    }
+
+    /// Whether a market's next snapshot is due
+    pub fn snapshot_due(&self, market: &str, now_ms: u64) -> bool {
+        self.last_snapshot
+            .get(market)
+            .is_none_or(|&last| now_ms.saturating_sub(last) >= self.config.interval_ms)
+    }
+
+    pub fn record_snapshot(&mut self, record: &MarketRecord) {
+        self.last_snapshot.insert(record.market.clone(), record.ts);
+        self.send(Line::Book, record);
+    }
+
+    pub fn record_quotes(&self, ts: u64, market: &str, oracle: f64, orders: &[PlannedOrder]) {
+        let orders = orders
+            .iter()
+            .map(|planned| QuoteRecord {
+                side: planned.order.side,
+                level: planned.level,
+                price: planned.price,
+                size: planned.order.base_asset_amount.to_f64(),
+            })
+            .collect();
+        self.send(
+            Line::Orders,
+            &OrderEvent::Quotes {
+                ts,
+                market: market.to_string(),
+                oracle,
+                orders,
+            },
+        );
+    }
+
+    pub fn record_fill(&self, ts: u64, market: &str, fill: &Fill) {
+        self.send(
+            Line::Orders,
+            &OrderEvent::Fill {
+                ts,
+                market: market.to_string(),
+                side: fill.side,
+                size: fill.size,
+                price: fill.price,
+                fee: fill.fee,
+            },
+        );
+    }
+
+    fn send<T: Serialize>(&self, line: fn(String) -> Line, value: &T) {
+        let Some(tx) = &self.tx else {
+            return;
+        };
+        match serde_json::to_string(value) {
+            Ok(json) => {
+                if tx.send(line(json)).is_err() {
+                    error!("Recorder writer stopped, dropping record");
+                }
+            }
+            Err(e) => error!("Failed to serialize record: {}", e),
+        }
+    }
+
+    /// Stop the writer and finish the current files
+    pub fn close(mut self) {
+        self.finish();
+    }
+
+    fn finish(&mut self) {
+        self.tx.take();
+        if let Some(writer) = self.writer.take() {
+            if writer.join().is_err() {
+                error!("Recorder writer panicked");
+            }
+            info!("Recorder closed");
+        }
+    }
+}
+
+// Also finishes the files when a shutdown is cut short before `close`
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Open output file
+enum Output {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+}
+
+impl Output {
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            Output::Plain(w) => w,
+            Output::Gzip(w) => w,
+        }
+    }
+
+    fn finish(self) -> std::io::Result<()> {
+        match self {
+            Output::Plain(mut w) => w.flush(),
+            Output::Gzip(w) => w.finish()?.flush(),
+        }
+    }
+}
+
+/// JSONL writer that starts a new `<prefix>-<unix ms>.jsonl[.gz]` file when
+/// the current one gets too old or too large
+struct RotatingWriter {
+    dir: PathBuf,
+    prefix: &'static str,
+    compress: bool,
+    rotate_ms: u64,
+    max_bytes: Option<u64>,
+    // (output, opened at ms, uncompressed bytes written)
+    current: Option<(Output, u64, u64)>,
+}
+
+impl RotatingWriter {
+    fn new(config: &RecorderConfig, prefix: &'static str) -> Self {
+        Self {
+            dir: config.dir.clone(),
+            prefix,
+            compress: config.compress,
+            rotate_ms: config.rotate_secs * 1000,
+            max_bytes: config.max_file_mb.map(|mb| mb * 1024 * 1024),
+            current: None,
+        }
+    }
+
+    fn write_line(&mut self, line: &str, now_ms: u64) -> Result<()> {
+        let rotate = self.current.as_ref().is_some_and(|&(_, opened, bytes)| {
+            now_ms.saturating_sub(opened) >= self.rotate_ms
+                || self.max_bytes.is_some_and(|max| bytes >= max)
+        });
+        if rotate {
+            self.finish()?;
+        }
+
+        if self.current.is_none() {
+            let path = self.path(now_ms);
+            let file = File::create(&path)
+                .with_context(|| format!("Failed to create {}", path.display()))?;
+            let file = BufWriter::new(file);
+            let output = if self.compress {
+                Output::Gzip(GzEncoder::new(file, Compression::default()))
+            } else {
+                Output::Plain(file)
+            };
+            info!("Recording to {}", path.display());
+            self.current = Some((output, now_ms, 0));
+        }
+
+        let Some((output, _, bytes)) = self.current.as_mut() else {
+            return Ok(());
+        };
+        writeln!(output.writer(), "{}", line)?;
+        *bytes += line.len() as u64 + 1;
+        Ok(())
+    }
+
+    fn path(&self, now_ms: u64) -> PathBuf {
+        let extension = if self.compress { "jsonl.gz" } else { "jsonl" };
+        self.dir
+            .join(format!("{}-{}.{}", self.prefix, now_ms, extension))
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if let Some((output, _, _)) = self.current.as_mut() {
+            output.writer().flush()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if let Some((output, _, _)) = self.current.take() {
+            output.finish()?;
+        }
+        Ok(())
+    }
+}
+
+/// Recorded files with the given prefix in a directory, oldest first
+pub fn recorded_files(dir: &Path, prefix: &str) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<(u64, PathBuf)> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_str()?;
+            let opened = name
+                .strip_prefix(prefix)?
+                .strip_prefix('-')?
+                .split('.')
+                .next()?
+                .parse()
+                .ok()?;
+            Some((opened, path))
+        })
+        .collect();
+    files.sort();
+    Ok(files.into_iter().map(|(_, path)| path).collect())
+}
+
+fn timestamp_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtest::read_records;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("maker-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn snapshot(ts: u64, market: &str) -> MarketRecord {
+        MarketRecord {
+            ts,
+            market: market.to_string(),
+            oracle: 100.0,
+            confidence: Some(0.01),
+            slot: Some(1000 + ts),
+            delay_slots: Some(1),
+            bids: vec![(99.9, 1.5)],
+            asks: vec![(100.1, 2.0)],
+        }
+    }
+
+    #[test]
+    fn test_rotation_and_replay() {
+        let dir = temp_dir("rotation");
+        let config = RecorderConfig {
+            dir: dir.clone(),
+            rotate_secs: 60,
+            ..Default::default()
+        };
+        let mut writer = RotatingWriter::new(&config, BOOK_PREFIX);
+
+        // Two snapshots in the first file, the third starts a new one
+        for (ts, written_at) in [(1_000, 1_000), (2_000, 30_000), (3_000, 61_000)] {
+            let line = serde_json::to_string(&snapshot(ts, "SOL-PERP")).unwrap();
+            writer.write_line(&line, written_at).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let files = recorded_files(&dir, BOOK_PREFIX).unwrap();
+        assert_eq!(
+            files,
+            vec![
+                dir.join("book-1000.jsonl.gz"),
+                dir.join("book-61000.jsonl.gz")
+            ]
+        );
+        assert!(recorded_files(&dir, ORDERS_PREFIX).unwrap().is_empty());
+
+        // The directory reads straight back as backtest input
+        let records = read_records(&dir).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[2], snapshot(3_000, "SOL-PERP"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_size_rotation_and_events() {
+        let dir = temp_dir("size");
+        let config = RecorderConfig {
+            dir: dir.clone(),
+            compress: false,
+            max_file_mb: Some(1),
+            ..Default::default()
+        };
+        let mut writer = RotatingWriter::new(&config, ORDERS_PREFIX);
+
+        let fill = OrderEvent::Fill {
+            ts: 5,
+            market: "SOL-PERP".to_string(),
+            side: Side::Sell,
+            size: 0.1,
+            price: 100.15,
+            fee: -0.001,
+        };
+        let line = serde_json::to_string(&fill).unwrap();
+        assert_eq!(
+            line,
+            r#"{"type":"fill","ts":5,"market":"SOL-PERP","side":"sell","size":0.1,"price":100.15,"fee":-0.001}"#
+        );
+
+        // A 1 MB line fills the first file, the next line goes to a new one
+        writer.write_line(&"x".repeat(1024 * 1024), 1).unwrap();
+        writer.write_line(&line, 2).unwrap();
+        writer.finish().unwrap();
+
+        let files = recorded_files(&dir, ORDERS_PREFIX).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(
+            fs::read_to_string(&files[1]).unwrap(),
+            format!("{}\n", line)
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,211 @@
+//! Runtime config reloads: SIGUSR2/file watch triggers and change detection.
+
+use std::{fmt::Debug, fs, time::Duration, time::SystemTime};
+
+use anyhow::{bail, Result};
+use log::{error, info};
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::mpsc,
+};
+
+use crate::config::{BotConfig, Cli};
+
+/// How often the config file is checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Describe what changed between the running and the reloaded config.
+/// Errors if a field that needs a restart changed.
+pub fn config_changes(old: &BotConfig, new: &BotConfig) -> Result<Vec<String>> {
+    let mut restart = Vec::new();
+    let mut restart_only = |name: &str, changed: bool| {
+        if changed {
+            restart.push(name.to_string());
+        }
+    };
+
+    // Identity, subscriptions and user order id slots are fixed at startup
+    restart_only("network", old.network != new.network);
+    restart_only("authority", old.authority != new.authority);
+    restart_only("subaccount_id", old.subaccount_id != new.subaccount_id);
+    restart_only("metrics_addr", old.metrics_addr != new.metrics_addr);
+    restart_only("recorder", old.recorder != new.recorder);
+    restart_only(
+        "paper (enabled)",
+        old.paper.is_some() != new.paper.is_some(),
+    );
+    restart_only(
+        "avellaneda_stoikov (enabled)",
+        old.avellaneda_stoikov.is_some() != new.avellaneda_stoikov.is_some(),
+    );
+    let old_symbols: Vec<_> = old.markets.iter().map(|m| &m.symbol).collect();
+    let new_symbols: Vec<_> = new.markets.iter().map(|m| &m.symbol).collect();
+    restart_only("markets", old_symbols != new_symbols);
+    for (old_market, new_market) in old.markets.iter().zip(&new.markets) {
+        restart_only(
+            &format!("{}.ladder.levels", old_market.symbol),
+            old_market.ladder.levels != new_market.ladder.levels,
+        );
+    }
+
+    if !restart.is_empty() {
+        bail!("{} can't change without a restart", restart.join(", "));
+    }
+
+    let mut changes = Vec::new();
+    let mut changed = |name: &str, old: &dyn Debug, new: &dyn Debug| {
+        let (old, new) = (format!("{:?}", old), format!("{:?}", new));
+        if old != new {
+            changes.push(format!("{}: {} -> {}", name, old, new));
+        }
+    };
+
+    for (old_market, new_market) in old.markets.iter().zip(&new.markets) {
+        let symbol = &old_market.symbol;
+        changed(
+            &format!("{}.order_size", symbol),
+            &old_market.order_size,
+            &new_market.order_size,
+        );
+        changed(
+            &format!("{}.max_position_size", symbol),
+            &old_market.max_position_size,
+            &new_market.max_position_size,
+        );
+        changed(
+            &format!("{}.spread_multiplier", symbol),
+            &old_market.spread_multiplier,
+            &new_market.spread_multiplier,
+        );
+        changed(
+            &format!("{}.fallback_spread_bps", symbol),
+            &old_market.fallback_spread_bps,
+            &new_market.fallback_spread_bps,
+        );
+        changed(
+            &format!("{}.ladder", symbol),
+            &old_market.ladder,
+            &new_market.ladder,
+        );
+        changed(
+            &format!("{}.rounding", symbol),
+            &old_market.rounding,
+            &new_market.rounding,
+        );
+    }
+
+    changed("debounce_ms", &old.debounce_ms, &new.debounce_ms);
+    changed(
+        "oracle_change_threshold_bps",
+        &old.oracle_change_threshold_bps,
+        &new.oracle_change_threshold_bps,
+    );
+    changed(
+        "avellaneda_stoikov",
+        &old.avellaneda_stoikov,
+        &new.avellaneda_stoikov,
+    );
+    changed("volatility", &old.volatility, &new.volatility);
+    changed("funding", &old.funding, &new.funding);
+    changed(
+        "order_tolerance",
+        &old.order_tolerance,
+        &new.order_tolerance,
+    );
+    changed(
+        "reconcile_interval_ms",
+        &old.reconcile_interval_ms,
+        &new.reconcile_interval_ms,
+    );
+    changed("risk_limits", &old.risk_limits, &new.risk_limits);
+    changed("oracle_guard", &old.oracle_guard, &new.oracle_guard);
+    changed("paper", &old.paper, &new.paper);
+    changed("tx", &old.tx, &new.tx);
+    changed("shutdown", &old.shutdown, &new.shutdown);
+
+    Ok(changes)
+}
+
+/// Reload the config on SIGUSR2, and when the file changes if `--watch-config`
+/// is set. Configs that fail to load or validate are logged and dropped.
+pub async fn watch_config(cli: Cli, reload_tx: mpsc::UnboundedSender<BotConfig>) -> Result<()> {
+    let mut reload_signal = signal(SignalKind::user_defined2())?;
+    let mut interval = tokio::time::interval(WATCH_INTERVAL);
+    let mut last_modified = modified(&cli);
+
+    loop {
+        tokio::select! {
+            _ = reload_signal.recv() => info!("Received SIGUSR2, reloading {}", cli.config.display()),
+            _ = interval.tick(), if cli.watch_config => {
+                let modified = modified(&cli);
+                if modified == last_modified {
+                    continue;
+                }
+                last_modified = modified;
+                info!("{} changed, reloading", cli.config.display());
+            }
+        }
+
+        match BotConfig::load(&cli) {
+            Ok(config) => {
+                if reload_tx.send(config).is_err() {
+                    return Ok(());
+                }
+            }
+            Err(e) => error!("Config reload failed, keeping current config: {:#}", e),
+        }
+    }
+}
+
+fn modified(cli: &Cli) -> Option<SystemTime> {
+    fs::metadata(&cli.config).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MarketConfig;
+
+    fn config() -> BotConfig {
+        BotConfig {
+            markets: vec![MarketConfig::test_default()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_changes_are_listed() {
+        let old = config();
+        assert!(config_changes(&old, &old).unwrap().is_empty());
+
+        let mut new = old.clone();
+        new.markets[0].spread_multiplier = 2.0;
+        new.debounce_ms = 500;
+
+        assert_eq!(
+            config_changes(&old, &new).unwrap(),
+            vec![
+                "SOL-PERP.spread_multiplier: 1.5 -> 2.0",
+                "debounce_ms: 1000 -> 500"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_restart_only_fields() {
+        let old = config();
+
+        let mut new = old.clone();
+        new.subaccount_id = 1;
+        new.markets[0].ladder.levels = 2;
+        let error = config_changes(&old, &new).unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "subaccount_id, SOL-PERP.ladder.levels can't change without a restart"
+        );
+
+        let mut new = old.clone();
+        new.markets[0].symbol = "BTC-PERP".to_string();
+        assert!(config_changes(&old, &new).is_err());
+    }
+}
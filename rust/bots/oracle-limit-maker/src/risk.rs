@@ -0,0 +1,287 @@
+//! Risk limits and the kill switch that halts quoting when one is breached.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use anyhow::{bail, Result};
+use bot_common::validate::ensure_positive;
+use serde::Deserialize;
+
+/// Milliseconds per UTC day, for resetting the daily loss
+const DAY_MS: u64 = 86_400_000;
+
+/// Window for the order rate limit
+const ORDER_RATE_WINDOW_MS: u64 = 60_000;
+
+/// Risk limits, `None` disables a limit. Losses and notional are in quote units.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RiskLimits {
+    // Max unrealized loss across open positions
+    pub max_unrealized_loss: Option<f64>,
+    // Max realized loss after fees since UTC midnight
+    pub max_daily_loss: Option<f64>,
+    // Max total position notional at oracle prices
+    pub max_notional: Option<f64>,
+    // Max orders placed or modified per minute
+    pub max_orders_per_minute: Option<usize>,
+    // Close positions as well as cancelling orders on a breach
+    pub flatten_on_breach: bool,
+}
+
+impl RiskLimits {
+    /// Check the settings are usable
+    pub fn validate(&self) -> Result<()> {
+        let loss_limits = [
+            ("max_unrealized_loss", self.max_unrealized_loss),
+            ("max_daily_loss", self.max_daily_loss),
+            ("max_notional", self.max_notional),
+        ];
+        for (name, limit) in loss_limits {
+            limit.map_or(Ok(()), |l| ensure_positive(name, l))?;
+        }
+        if self.max_orders_per_minute == Some(0) {
+            bail!("max_orders_per_minute must be > 0");
+        }
+        Ok(())
+    }
+}
+
+/// Account risk at a point in time
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RiskSnapshot {
+    // Unrealized PnL of open positions at oracle prices
+    pub unrealized_pnl: f64,
+    // Cumulative realized PnL after fees and rebates
+    pub net_realized_pnl: f64,
+    // Total absolute position notional at oracle prices
+    pub notional: f64,
+}
+
+/// Limit that tripped the kill switch
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Breach {
+    UnrealizedLoss { loss: f64, limit: f64 },
+    DailyLoss { loss: f64, limit: f64 },
+    Notional { notional: f64, limit: f64 },
+    OrderRate { orders: usize, limit: usize },
+}
+
+impl fmt::Display for Breach {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breach::UnrealizedLoss { loss, limit } => {
+                write!(f, "unrealized loss ${:.2} exceeds ${:.2}", loss, limit)
+            }
+            Breach::DailyLoss { loss, limit } => {
+                write!(f, "daily realized loss ${:.2} exceeds ${:.2}", loss, limit)
+            }
+            Breach::Notional { notional, limit } => {
+                write!(f, "notional ${:.2} exceeds ${:.2}", notional, limit)
+            }
+            Breach::OrderRate { orders, limit } => {
+                write!(f, "{} orders in the last minute exceeds {}", orders, limit)
+            }
+        }
+    }
+}
+
+/// Checks risk limits and latches the kill switch until it is reset
+#[derive(Debug)]
+pub struct KillSwitch {
+    limits: RiskLimits,
+    tripped: Option<Breach>,
+    // UTC day index and realized PnL at its start
+    day: u64,
+    day_start_pnl: Option<f64>,
+    // Timestamps (ms) of recent order placements/modifications
+    order_times: VecDeque<u64>,
+}
+
+impl KillSwitch {
+    pub fn new(limits: RiskLimits) -> Self {
+        Self {
+            limits,
+            tripped: None,
+            day: 0,
+            day_start_pnl: None,
+            order_times: VecDeque::new(),
+        }
+    }
+
+    pub fn limits(&self) -> &RiskLimits {
+        &self.limits
+    }
+
+    /// Replace the limits, keeping the tripped state and loss/order windows
+    pub fn set_limits(&mut self, limits: RiskLimits) {
+        self.limits = limits;
+    }
+
+    /// Breach that tripped the switch, `None` while quoting is allowed
+    pub fn tripped(&self) -> Option<Breach> {
+        self.tripped
+    }
+
+    /// Count orders sent to the exchange
+    pub fn record_orders(&mut self, now_ms: u64, count: usize) {
+        self.order_times.extend(std::iter::repeat_n(now_ms, count));
+    }
+
+    /// Check every limit, returning the breach if this check tripped the switch
+    pub fn check(&mut self, now_ms: u64, snapshot: &RiskSnapshot) -> Option<Breach> {
+        // Daily loss is measured from the first snapshot of each UTC day
+        let day = now_ms / DAY_MS;
+        if self.day_start_pnl.is_none() || day != self.day {
+            self.day = day;
+            self.day_start_pnl = Some(snapshot.net_realized_pnl);
+        }
+
+        while let Some(&ts) = self.order_times.front() {
+            if now_ms.saturating_sub(ts) < ORDER_RATE_WINDOW_MS {
+                break;
+            }
+            self.order_times.pop_front();
+        }
+
+        if self.tripped.is_some() {
+            return None;
+        }
+
+        let limits = &self.limits;
+        let unrealized_loss = -snapshot.unrealized_pnl;
+        let daily_loss = self.day_start_pnl.unwrap_or_default() - snapshot.net_realized_pnl;
+        let orders = self.order_times.len();
+
+        let breach =
+            if let Some(limit) = limits.max_unrealized_loss.filter(|l| unrealized_loss > *l) {
+                Breach::UnrealizedLoss {
+                    loss: unrealized_loss,
+                    limit,
+                }
+            } else if let Some(limit) = limits.max_daily_loss.filter(|l| daily_loss > *l) {
+                Breach::DailyLoss {
+                    loss: daily_loss,
+                    limit,
+                }
+            } else if let Some(limit) = limits.max_notional.filter(|l| snapshot.notional > *l) {
+                Breach::Notional {
+                    notional: snapshot.notional,
+                    limit,
+                }
+            } else if let Some(limit) = limits.max_orders_per_minute.filter(|l| orders > *l) {
+                Breach::OrderRate { orders, limit }
+            } else {
+                return None;
+            };
+
+        self.tripped = Some(breach);
+        Some(breach)
+    }
+
+    /// Operator reset: allow quoting again and restart the daily loss and
+    /// order rate windows so the same breach doesn't immediately re-trip
+    pub fn reset(&mut self) {
+        self.tripped = None;
+        self.day_start_pnl = None;
+        self.order_times.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> RiskLimits {
+        RiskLimits {
+            max_unrealized_loss: Some(100.0),
+            max_daily_loss: Some(50.0),
+            max_notional: Some(10_000.0),
+            max_orders_per_minute: Some(10),
+            flatten_on_breach: false,
+        }
+    }
+
+    #[test]
+    fn test_limits_trip_and_latch() {
+        let mut switch = KillSwitch::new(limits());
+        let ok = RiskSnapshot::default();
+        assert_eq!(switch.check(0, &ok), None);
+
+        let losing = RiskSnapshot {
+            unrealized_pnl: -150.0,
+            ..ok
+        };
+        assert_eq!(
+            switch.check(1_000, &losing),
+            Some(Breach::UnrealizedLoss {
+                loss: 150.0,
+                limit: 100.0
+            })
+        );
+
+        // Stays tripped after recovery until reset
+        assert_eq!(switch.check(2_000, &ok), None);
+        assert!(switch.tripped().is_some());
+
+        switch.reset();
+        assert_eq!(switch.check(3_000, &ok), None);
+        assert!(switch.tripped().is_none());
+
+        let large = RiskSnapshot {
+            notional: 20_000.0,
+            ..ok
+        };
+        assert!(matches!(
+            switch.check(4_000, &large),
+            Some(Breach::Notional { .. })
+        ));
+    }
+
+    #[test]
+    fn test_daily_loss_resets_each_day() {
+        let mut switch = KillSwitch::new(limits());
+        let pnl = |net_realized_pnl| RiskSnapshot {
+            net_realized_pnl,
+            ..Default::default()
+        };
+
+        // Day starts at +20, losing 40 is within the limit
+        assert_eq!(switch.check(1_000, &pnl(20.0)), None);
+        assert_eq!(switch.check(2_000, &pnl(-20.0)), None);
+
+        // New day rebases, then 60 more is a breach
+        assert_eq!(switch.check(DAY_MS + 1_000, &pnl(-20.0)), None);
+        assert_eq!(
+            switch.check(DAY_MS + 2_000, &pnl(-80.0)),
+            Some(Breach::DailyLoss {
+                loss: 60.0,
+                limit: 50.0
+            })
+        );
+    }
+
+    #[test]
+    fn test_order_rate() {
+        let mut switch = KillSwitch::new(limits());
+        let ok = RiskSnapshot::default();
+
+        switch.record_orders(0, 6);
+        switch.record_orders(30_000, 4);
+        assert_eq!(switch.check(30_000, &ok), None);
+
+        switch.record_orders(40_000, 1);
+        assert_eq!(
+            switch.check(40_000, &ok),
+            Some(Breach::OrderRate {
+                orders: 11,
+                limit: 10
+            })
+        );
+
+        // Old orders fall out of the window after a reset
+        switch.reset();
+        switch.record_orders(61_000, 10);
+        assert_eq!(switch.check(61_000, &ok), None);
+    }
+}
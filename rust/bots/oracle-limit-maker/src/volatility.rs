@@ -0,0 +1,306 @@
+//! Rolling realized volatility of oracle prices and volatility-adaptive spreads.
+
+use std::collections::VecDeque;
+
+use anyhow::{bail, Result};
+use bot_common::validate::{check_range, ensure_positive};
+use serde::Deserialize;
+
+use crate::quote::Quote;
+
+/// Minimum samples before a volatility estimate is reported
+const MIN_SAMPLES: usize = 10;
+
+/// Seconds per year, for annualizing per-second volatility
+const SECONDS_PER_YEAR: f64 = 31_536_000.0;
+
+/// Realized volatility over a rolling time window
+#[derive(Debug, Clone)]
+pub struct RealizedVol {
+    window_ms: u64,
+    // (timestamp ms, price)
+    samples: VecDeque<(u64, f64)>,
+}
+
+impl RealizedVol {
+    pub fn new(window_ms: u64) -> Self {
+        Self {
+            window_ms,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Change the window, samples beyond a shorter window drop on the next update
+    pub fn set_window_ms(&mut self, window_ms: u64) {
+        self.window_ms = window_ms;
+    }
+
+    /// Add a price sample, dropping samples that fell out of the window
+    pub fn update(&mut self, timestamp_ms: u64, price: f64) {
+        if price <= 0.0 || !price.is_finite() {
+            return;
+        }
+        if let Some(&(last_ts, _)) = self.samples.back() {
+            if timestamp_ms <= last_ts {
+                return;
+            }
+        }
+
+        self.samples.push_back((timestamp_ms, price));
+        while let Some(&(ts, _)) = self.samples.front() {
+            if timestamp_ms - ts <= self.window_ms {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    /// Volatility of log returns per sqrt(second) over the whole window
+    pub fn volatility(&self) -> Option<f64> {
+        self.volatility_over(self.window_ms)
+    }
+
+    /// Volatility of log returns per sqrt(second) over the most recent
+    /// `horizon_ms`, `None` until enough samples
+    pub fn volatility_over(&self, horizon_ms: u64) -> Option<f64> {
+        let (last_ts, _) = *self.samples.back()?;
+        let start = self
+            .samples
+            .iter()
+            .position(|(ts, _)| last_ts - ts <= horizon_ms)?;
+        let samples = self.samples.range(start..);
+        if samples.len() < MIN_SAMPLES {
+            return None;
+        }
+
+        let (first_ts, _) = self.samples[start];
+        let elapsed_secs = (last_ts - first_ts) as f64 / 1000.0;
+        if elapsed_secs <= 0.0 {
+            return None;
+        }
+
+        // Sum of squared log returns over elapsed time handles irregular sampling
+        let sum_sq: f64 = samples
+            .clone()
+            .zip(samples.skip(1))
+            .map(|((_, prev), (_, next))| (next / prev).ln().powi(2))
+            .sum();
+
+        Some((sum_sq / elapsed_secs).sqrt())
+    }
+}
+
+/// Annualize a per-sqrt(second) volatility
+pub fn annualize(volatility: f64) -> f64 {
+    volatility * SECONDS_PER_YEAR.sqrt()
+}
+
+/// Volatility-adaptive spread parameters
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VolatilityConfig {
+    // Horizons (seconds) realized volatility is computed over
+    pub horizons_secs: Vec<u64>,
+    // Annualized volatility at which spreads are unchanged (e.g. 0.5 = 50%)
+    pub reference_volatility: f64,
+    // Bounds on the spread scaling factor
+    pub min_multiplier: f64,
+    pub max_multiplier: f64,
+    // Spread floor and ceiling (BPS of oracle price)
+    pub min_spread_bps: f64,
+    pub max_spread_bps: f64,
+}
+
+impl VolatilityConfig {
+    /// Check the settings are usable
+    pub fn validate(&self) -> Result<()> {
+        if self.horizons_secs.is_empty() || self.horizons_secs.contains(&0) {
+            bail!("horizons_secs must be non-empty and > 0");
+        }
+        ensure_positive("reference_volatility", self.reference_volatility)?;
+        check_range("min_multiplier", self.min_multiplier, self.max_multiplier)?;
+        check_range("min_spread_bps", self.min_spread_bps, self.max_spread_bps)
+    }
+
+    /// Longest configured horizon (ms)
+    pub fn window_ms(&self) -> u64 {
+        self.horizons_secs.iter().max().copied().unwrap_or_default() * 1000
+    }
+}
+
+/// Annualized volatility per horizon
+#[derive(Debug, Clone, PartialEq)]
+pub struct VolEstimate {
+    // (horizon seconds, annualized volatility if available)
+    pub horizons: Vec<(u64, Option<f64>)>,
+}
+
+impl VolEstimate {
+    pub fn new(vol: &RealizedVol, config: &VolatilityConfig) -> Self {
+        Self {
+            horizons: config
+                .horizons_secs
+                .iter()
+                .map(|&secs| (secs, vol.volatility_over(secs * 1000).map(annualize)))
+                .collect(),
+        }
+    }
+
+    /// Highest volatility across horizons, so short-term spikes widen quickly
+    pub fn max(&self) -> Option<f64> {
+        self.horizons
+            .iter()
+            .filter_map(|(_, vol)| *vol)
+            .reduce(f64::max)
+    }
+}
+
+impl std::fmt::Display for VolEstimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (secs, vol)) in self.horizons.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            match vol {
+                Some(vol) => write!(f, "{}s {:.1}%", secs, vol * 100.0)?,
+                None => write!(f, "{}s n/a", secs)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Scale the quoted spread around its mid by volatility relative to the
+/// reference, then clamp it to the configured floor and ceiling. Returns the
+/// adjusted quote and the applied multiplier.
+pub fn adapt_spread(
+    quote: &Quote,
+    oracle_price: f64,
+    estimate: &VolEstimate,
+    config: &VolatilityConfig,
+) -> (Quote, f64) {
+    let multiplier = estimate
+        .max()
+        .map(|vol| vol / config.reference_volatility)
+        .unwrap_or(1.0)
+        .clamp(config.min_multiplier, config.max_multiplier);
+
+    let spread = (quote.spread() * multiplier).clamp(
+        oracle_price * config.min_spread_bps / 10_000.0,
+        oracle_price * config.max_spread_bps / 10_000.0,
+    );
+    let mid = quote.mid();
+
+    let adapted = Quote {
+        bid_price: mid - spread / 2.0,
+        ask_price: mid + spread / 2.0,
+        ..*quote
+    };
+    (adapted, multiplier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alternating(vol: &mut RealizedVol, from_secs: u64, to_secs: u64, high: f64) {
+        for i in from_secs..to_secs {
+            let price = if i % 2 == 0 { 100.0 } else { high };
+            vol.update(i * 1000, price);
+        }
+    }
+
+    fn config() -> VolatilityConfig {
+        VolatilityConfig {
+            horizons_secs: vec![10, 60],
+            reference_volatility: 0.5,
+            min_multiplier: 0.5,
+            max_multiplier: 3.0,
+            min_spread_bps: 1.0,
+            max_spread_bps: 50.0,
+        }
+    }
+
+    #[test]
+    fn test_needs_samples() {
+        let mut vol = RealizedVol::new(60_000);
+        for i in 0..MIN_SAMPLES as u64 - 1 {
+            vol.update(i * 1000, 100.0);
+        }
+        assert_eq!(vol.volatility(), None);
+
+        vol.update(10_000, 100.0);
+        assert_eq!(vol.volatility(), Some(0.0));
+    }
+
+    #[test]
+    fn test_volatility_estimate() {
+        let mut vol = RealizedVol::new(60_000);
+        // Alternate +/-1% every second
+        alternating(&mut vol, 0, 20, 101.0);
+
+        let expected = (101.0f64 / 100.0).ln().abs();
+        assert!((vol.volatility().unwrap() - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_horizons() {
+        let mut vol = RealizedVol::new(60_000);
+        alternating(&mut vol, 0, 40, 100.01);
+        // Recent 10s are far more volatile
+        alternating(&mut vol, 40, 52, 101.0);
+
+        let short = vol.volatility_over(10_000).unwrap();
+        let long = vol.volatility_over(60_000).unwrap();
+        assert!(short > long);
+
+        let estimate = VolEstimate::new(&vol, &config());
+        assert_eq!(estimate.max(), Some(annualize(short)));
+        assert!(estimate.to_string().starts_with("10s "));
+    }
+
+    #[test]
+    fn test_window_and_bad_samples() {
+        let mut vol = RealizedVol::new(5_000);
+        for i in 0..20u64 {
+            vol.update(i * 1000, 100.0);
+        }
+        assert_eq!(vol.samples.len(), 6);
+
+        // Out of order, zero and NaN prices are ignored
+        vol.update(10_000, 100.0);
+        vol.update(30_000, 0.0);
+        vol.update(30_000, f64::NAN);
+        assert_eq!(vol.samples.len(), 6);
+    }
+
+    #[test]
+    fn test_adapt_spread() {
+        let quote = Quote {
+            bid_price: 99.9,
+            bid_size: 1.0,
+            ask_price: 100.1,
+            ask_size: 1.0,
+        };
+        let estimate = |vol| VolEstimate {
+            horizons: vec![(10, Some(vol)), (60, None)],
+        };
+
+        // Double the reference volatility doubles the spread around the mid
+        let (wide, mult) = adapt_spread(&quote, 100.0, &estimate(1.0), &config());
+        assert_eq!(mult, 2.0);
+        assert!((wide.spread() - 0.4).abs() < 1e-9);
+        assert!((wide.mid() - quote.mid()).abs() < 1e-9);
+
+        // Ceiling caps extreme volatility
+        let (capped, mult) = adapt_spread(&quote, 100.0, &estimate(10.0), &config());
+        assert_eq!(mult, 3.0);
+        assert!((capped.spread() - 0.5).abs() < 1e-9);
+
+        // No estimate leaves the spread unchanged
+        let none = VolEstimate { horizons: vec![] };
+        let (same, mult) = adapt_spread(&quote, 100.0, &none, &config());
+        assert_eq!(mult, 1.0);
+        assert!((same.spread() - quote.spread()).abs() < 1e-9);
+    }
+}